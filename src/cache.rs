@@ -0,0 +1,222 @@
+//! Tracks what every output file was built from, so unchanged files can be
+//! skipped.
+//!
+//! For each output file the cache stores a hash of the settings it was built
+//! with, the content hash of every file it was built from (its source file,
+//! then anything it included), and the content hash of the output itself. A
+//! file is only rebuilt if one of those changed. The cache is saved to
+//! [`CACHE_FILE`] in the output directory.
+
+use {
+    crate::{ErrorKind, WebbyError},
+    std::{
+        collections::HashMap,
+        fmt::Write,
+        fs, io,
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+/// The name of the cache file in the output directory.
+pub const CACHE_FILE: &str = ".webby-cache";
+/// The first line of the cache file. Changing this invalidates old caches.
+const HEADER: &str = "webby cache v1";
+
+/// A 128-bit FNV-1a hash. This isn't cryptographically secure, but it's
+/// stable across Rust versions and platforms, unlike std's hashers.
+pub type Hash = u128;
+
+pub fn hash(bytes: &[u8]) -> Hash {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013B;
+
+    bytes.iter().fold(OFFSET, |hash, byte| {
+        (hash ^ *byte as u128).wrapping_mul(PRIME)
+    })
+}
+
+/// Hashes the file at `path`. Folders are hashed by the names of the entries
+/// in them, so pages that list a folder are rebuilt when files are added to it
+/// or removed from it.
+pub fn hash_path(path: &Path) -> io::Result<Hash> {
+    if !path.is_dir() {
+        return fs::read(path).map(|bytes| hash(&bytes));
+    }
+
+    let mut names = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<io::Result<Vec<_>>>()?;
+    names.sort();
+    let mut bytes = Vec::new();
+    for name in names {
+        bytes.extend_from_slice(name.as_encoded_bytes());
+        bytes.push(b'\n');
+    }
+
+    Ok(hash(&bytes))
+}
+
+/// How an output file was built.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// A hash of the target settings the file was built with.
+    pub settings: Hash,
+    /// Every file or folder the output was built from, and their hashes. The
+    /// first one is the source file.
+    pub dependencies: Vec<(PathBuf, Hash)>,
+    /// The hash of the output file.
+    pub output: Hash,
+}
+
+/// The build cache for a project.
+pub struct Cache {
+    path: PathBuf,
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+}
+impl Cache {
+    /// An empty cache that will be saved in `output_dir`.
+    pub fn new(output_dir: &Path) -> Self {
+        Self {
+            path: output_dir.join(CACHE_FILE),
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Loads the cache from `output_dir`. If there isn't a cache there, or it
+    /// can't be read, this returns an empty cache, which just means
+    /// everything will be rebuilt.
+    pub fn load(output_dir: &Path) -> Self {
+        let cache = Self::new(output_dir);
+        let Ok(src) = fs::read_to_string(&cache.path) else {
+            return cache;
+        };
+        let mut lines = src.lines();
+        if lines.next() != Some(HEADER) {
+            return cache;
+        }
+
+        let mut entries = HashMap::new();
+        let mut current: Option<(PathBuf, Entry)> = None;
+        for line in lines {
+            if let Some(line) = line.strip_prefix("output ") {
+                let mut parts = line.splitn(3, ' ');
+                let (Some(output), Some(settings), Some(path)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    return cache;
+                };
+                let (Ok(output), Ok(settings)) = (
+                    Hash::from_str_radix(output, 16),
+                    Hash::from_str_radix(settings, 16),
+                ) else {
+                    return cache;
+                };
+
+                if let Some((path, entry)) = current.take() {
+                    entries.insert(path, entry);
+                }
+                current = Some((
+                    PathBuf::from(path),
+                    Entry {
+                        settings,
+                        dependencies: Vec::new(),
+                        output,
+                    },
+                ));
+            } else if let Some(line) = line.strip_prefix("dep ") {
+                let Some((hash, path)) = line.split_once(' ') else {
+                    return cache;
+                };
+                let (Some((_, entry)), Ok(hash)) =
+                    (current.as_mut(), Hash::from_str_radix(hash, 16))
+                else {
+                    return cache;
+                };
+                entry.dependencies.push((PathBuf::from(path), hash));
+            } else {
+                return cache;
+            }
+        }
+        if let Some((path, entry)) = current.take() {
+            entries.insert(path, entry);
+        }
+
+        *cache.entries.lock().unwrap() = entries;
+        cache
+    }
+
+    /// Writes the cache to the output directory.
+    pub fn save(&self) -> Result<(), WebbyError> {
+        let io_error = |err| {
+            WebbyError::new(ErrorKind::Io, "Failed to save the build cache")
+                .in_file(&self.path)
+                .caused_by(err)
+        };
+        if let Some(output_dir) = self.path.parent() {
+            fs::create_dir_all(output_dir).map_err(io_error)?;
+        }
+
+        let mut out = String::from(HEADER);
+        out.push('\n');
+
+        let entries = self.entries.lock().unwrap();
+        let mut outputs: Vec<_> = entries.keys().collect();
+        outputs.sort();
+        for output in outputs {
+            let entry = &entries[output];
+            let Some(output) = output.to_str().filter(|path| !path.contains('\n')) else {
+                continue;
+            };
+            writeln!(
+                out,
+                "output {:x} {:x} {output}",
+                entry.output, entry.settings
+            )
+            .unwrap();
+            for (path, hash) in &entry.dependencies {
+                if let Some(path) = path.to_str().filter(|path| !path.contains('\n')) {
+                    writeln!(out, "dep {hash:x} {path}").unwrap();
+                }
+            }
+        }
+
+        fs::write(&self.path, out).map_err(io_error)
+    }
+
+    /// Checks if `output` is up to date: it was built with the same settings,
+    /// none of the files it was built from have changed, and the output file
+    /// hasn't been changed or deleted since. If so, returns the files it was
+    /// built from.
+    pub fn fresh(&self, output: &Path, settings: Hash) -> Option<Vec<PathBuf>> {
+        let entry = self.entries.lock().unwrap().get(output).cloned()?;
+
+        let unchanged = entry.settings == settings
+            && entry
+                .dependencies
+                .iter()
+                .all(|(path, hash)| hash_path(path).is_ok_and(|current| current == *hash))
+            && fs::read(output).is_ok_and(|bytes| self::hash(&bytes) == entry.output);
+
+        unchanged.then(|| {
+            entry
+                .dependencies
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect()
+        })
+    }
+
+    /// Records how `output` was built.
+    pub fn record(&self, output: &Path, entry: Entry) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(output.to_path_buf(), entry);
+    }
+
+    /// The recorded dependency graph: every output file and how it was built.
+    pub fn entries(&self) -> HashMap<PathBuf, Entry> {
+        self.entries.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,192 @@
+//! Collections of pages, like every post in a blog, for listing them on other
+//! pages.
+//!
+//! A collection is every file matching a glob, like `posts/*.md`, relative to
+//! the file that uses it. Each file's front matter is read so its title, date
+//! and so on can be shown in the listing, and drafts are left out unless
+//! `drafts = true` is set in `webby.toml`.
+
+use {
+    crate::{
+        front_matter, glob, Context, ErrorKind, FileType, FrontMatter, Pattern, Value, WebbyError,
+    },
+    std::path::{Path, PathBuf},
+};
+
+/// A page in a collection.
+#[derive(Clone, Debug)]
+pub struct Item {
+    /// The page's source file.
+    pub path: PathBuf,
+    /// The page's front matter, or empty front matter if it doesn't have any.
+    pub front_matter: FrontMatter,
+    /// The page's URL, relative to the root of the site, if a target builds it.
+    pub url: Option<String>,
+}
+impl Item {
+    /// The page's title: the one in its front matter, or its file name without
+    /// the extension.
+    pub fn title(&self) -> String {
+        self.front_matter
+            .title()
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                self.path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            })
+    }
+
+    /// The page's `date` from its front matter. Dates should be written like
+    /// `"2024-06-01"` so they sort correctly.
+    pub fn date(&self) -> Option<&str> {
+        self.front_matter.get("date").and_then(Value::as_str)
+    }
+
+    /// The page's front matter, with its `title` filled in if it doesn't have
+    /// one and its `url` added. This is what item templates read with
+    /// `#!META(key)`.
+    pub fn template_front_matter(&self) -> FrontMatter {
+        let mut front_matter = self.front_matter.clone();
+        front_matter
+            .values
+            .entry("title".into())
+            .or_insert_with(|| Value::String(self.title()));
+        if let Some(url) = &self.url {
+            front_matter
+                .values
+                .insert("url".into(), Value::String(url.clone()));
+        }
+
+        front_matter
+    }
+}
+
+/// How a collection is ordered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sort {
+    /// By source path.
+    Path,
+    /// Newest first, by the `date` in each page's front matter. Pages without
+    /// a date go last.
+    Date,
+    /// Alphabetically by title.
+    Title,
+}
+impl Sort {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "path" => Some(Self::Path),
+            "date" => Some(Self::Date),
+            "title" => Some(Self::Title),
+            _ => None,
+        }
+    }
+}
+
+/// Finds every page matching `pattern`, which is relative to `source_path`,
+/// and sorts them. Every file and folder read is recorded as a dependency in
+/// `ctx`, so adding or changing a page rebuilds the pages listing it.
+pub fn collect(
+    pattern: &str,
+    source_path: &Path,
+    sort: Sort,
+    ctx: &mut Context,
+) -> Result<Vec<Item>, WebbyError> {
+    let (base, pattern) = glob::split(pattern.trim()).map_err(|err| err.in_file(source_path))?;
+    let base = crate::normalize_path(&source_path.parent().unwrap_or(Path::new("")).join(base));
+
+    collect_in(&base, pattern.as_ref(), sort, ctx)
+}
+
+/// Like [`collect`], but finds every page in `base` whose path relative to it
+/// matches `pattern`. If `base` is a file, it's the only page.
+pub fn collect_in(
+    base: &Path,
+    pattern: Option<&Pattern>,
+    sort: Sort,
+    ctx: &mut Context,
+) -> Result<Vec<Item>, WebbyError> {
+    let mut paths = Vec::new();
+    if base.is_dir() {
+        find_files(base, base, pattern, &mut paths, ctx)?;
+    } else {
+        paths.push(base.to_path_buf());
+    }
+
+    let drafts = ctx.config.is_some_and(|config| config.drafts);
+    let mut items = Vec::with_capacity(paths.len());
+    for path in paths {
+        let front_matter = if FileType::from(path.as_path()).is_translated() {
+            let source = ctx.read_to_string(&path).map_err(|err| {
+                WebbyError::new(ErrorKind::Io, "Failed to read a page in the collection")
+                    .in_file(&path)
+                    .caused_by(err)
+            })?;
+            front_matter::split(&source, &path)?.0.unwrap_or_default()
+        } else {
+            FrontMatter::default()
+        };
+        if front_matter.is_draft() && !drafts {
+            continue;
+        }
+
+        let url = ctx
+            .config
+            .and_then(|config| config.url_of(&path, ctx.format));
+        items.push(Item {
+            path,
+            front_matter,
+            url,
+        });
+    }
+
+    match sort {
+        Sort::Path => {}
+        Sort::Date => items.sort_by(|a, b| match (a.date(), b.date()) {
+            (Some(a), Some(b)) => b.cmp(a),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }),
+        Sort::Title => items.sort_by_cached_key(Item::title),
+    }
+
+    Ok(items)
+}
+
+/// Adds every file in `dir` whose path relative to `base` matches `pattern` to
+/// `paths`, in order.
+fn find_files(
+    base: &Path,
+    dir: &Path,
+    pattern: Option<&Pattern>,
+    paths: &mut Vec<PathBuf>,
+    ctx: &mut Context,
+) -> Result<(), WebbyError> {
+    let entries = ctx.read_dir(dir).map_err(|err| {
+        WebbyError::new(ErrorKind::Io, "Failed to open the collection's folder")
+            .in_file(dir)
+            .caused_by(err)
+    })?;
+
+    for path in entries {
+        if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(glob::is_ignored)
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            find_files(base, &path, pattern, paths, ctx)?;
+        } else if pattern.is_none_or(|pattern| {
+            path.strip_prefix(base)
+                .is_ok_and(|relative| pattern.matches(relative))
+        }) {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
@@ -1,23 +1,50 @@
 use {
-    crate::{line_number_of_offset, minifier, Cow, FileType},
+    crate::{
+        collection::{self, Sort},
+        front_matter::Value,
+        headings, minifier, Context, Cow, ErrorKind, FileType, WebbyError,
+    },
     base64::{engine::general_purpose::STANDARD, Engine},
-    std::{fs, path::Path},
+    std::{
+        fmt::Write,
+        fs,
+        ops::Range,
+        path::{Path, PathBuf},
+    },
 };
 
-pub fn compile_macros<'a>(original: &'a str, source_path: &'a Path) -> Result<Cow<'a>, String> {
+pub fn compile_macros<'a>(
+    original: &'a str,
+    source_path: &'a Path,
+    ctx: &mut Context,
+) -> Result<Cow<'a>, WebbyError> {
+    compile_macros_in(original, 0..original.len(), source_path, ctx)
+}
+
+/// Compiles the macros in `file[span]`. This takes the entire file, instead of
+/// just the text to compile, so errors can report where they are in the file
+/// instead of where they are in a macro's arguments.
+pub(crate) fn compile_macros_in<'a>(
+    file: &'a str,
+    span: Range<usize>,
+    source_path: &Path,
+    ctx: &mut Context,
+) -> Result<Cow<'a>, WebbyError> {
+    let original = &file[span.clone()];
+    let error_at = |kind: ErrorKind, message: String, offset: usize| {
+        WebbyError::new(kind, message).at(source_path, file, span.start + offset)
+    };
+
     let mut output = String::default();
     let mut offset = 0;
 
     while let Some(start_idx) = original[offset..].find("#!") {
-        if original[offset..]
-            .as_bytes()
+        if original.as_bytes()[offset..]
             .get(start_idx.saturating_sub(1))
             .copied()
             == Some(b'\\')
         {
-            if !output.is_empty() {
-                output += &original[offset..offset + start_idx + 1]
-            }
+            output += &original[offset..offset + start_idx + 1];
             offset += start_idx + 1;
             continue;
         }
@@ -26,80 +53,198 @@ pub fn compile_macros<'a>(original: &'a str, source_path: &'a Path) -> Result<Co
         offset += start_idx;
 
         let macro_src = &original[offset..];
-        let Some(paren_open) = macro_src.find('(') else {
-            return Err(format!(
-                "Expected ( in macro invocation at {source_path:?}:{}",
-                line_number_of_offset(original, offset)
+        let name_len = macro_src[2..]
+            .find(|char: char| !(char.is_ascii_alphanumeric() || char == '_'))
+            .unwrap_or(macro_src.len() - 2);
+        let paren_open = name_len + 2;
+        if macro_src.as_bytes().get(paren_open) != Some(&b'(') {
+            return Err(error_at(
+                ErrorKind::MacroSyntax,
+                "Expected ( after the macro name in macro invocation".into(),
+                offset,
+            ));
+        }
+        let Some(paren_close) = macro_end(macro_src, paren_open) else {
+            return Err(error_at(
+                ErrorKind::MacroSyntax,
+                "Expected ) to end macro invocation".into(),
+                offset,
             ));
         };
-        let mut paren_depth = 0u8;
-        let mut bytes = macro_src[paren_open..].bytes().enumerate();
-        let paren_close: usize;
-        loop {
-            let Some((idx, byte)) = bytes.next() else {
-                return Err(format!(
-                    "Expected ) to end macro invocation at {source_path:?}:{}",
-                    line_number_of_offset(original, offset)
-                ));
-            };
 
-            if byte == b'(' {
-                paren_depth += 1;
-            } else if byte == b')' {
-                paren_depth -= 1;
-                if paren_depth == 0 {
-                    paren_close = idx + paren_open;
-                    break;
-                }
-            }
+        let macro_name = &macro_src[2..paren_open];
+        let args_start = span.start + offset + paren_open + 1;
+
+        if macro_name == "IF" || macro_name == "IFDEF" {
+            let (branch, else_len) = compile_conditional(
+                file,
+                macro_name,
+                args_start..span.start + offset + paren_close,
+                span.start + offset + paren_close + 1..span.end,
+                source_path,
+                ctx,
+            )?;
+            output += branch.as_ref();
+            offset += paren_close + 1 + else_len;
+            continue;
         }
 
-        let macro_name = &macro_src[2..paren_open];
-        let macro_args = &macro_src[paren_open + 1..paren_close];
-        let macro_args = compile_macros(macro_args, source_path)?;
+        let macro_args = compile_macros_in(
+            file,
+            args_start..span.start + offset + paren_close,
+            source_path,
+            ctx,
+        )?;
         let macro_args = macro_args.as_ref();
 
         match macro_name {
             "INCLUDE" => {
-                let path = source_path.parent().unwrap().join(macro_args);
-                let src = fs::read_to_string(&path).unwrap_or_else(|err| {
-                    panic!(
-                        "Error in INCLUDE macro at {source_path:?}:{}: {err}",
-                        line_number_of_offset(original, offset)
+                let path = relative_path(source_path, macro_args);
+                let src = ctx.read_to_string(&path).map_err(|err| {
+                    error_at(
+                        ErrorKind::Io,
+                        format!("Failed to read {path:?} in INCLUDE macro"),
+                        offset,
                     )
-                });
-                let compiled = crate::compile_file(&src, &path, FileType::from(path.as_path()))?;
+                    .caused_by(err)
+                })?;
+                let (compiled, _) =
+                    crate::compile_file(&src, &path, FileType::from(path.as_path()), ctx).map_err(
+                        |err| {
+                            error_at(
+                                err.kind,
+                                format!("Failed to compile {path:?} in INCLUDE macro"),
+                                offset,
+                            )
+                            .caused_by(err)
+                        },
+                    )?;
                 output += compiled.as_ref();
             }
             "BASE64" => {
                 output += STANDARD.encode(macro_args).as_str();
             }
             "INCLUDE_BASE64" => {
-                let path = source_path.parent().unwrap().join(macro_args);
-                let src = fs::read(&path).unwrap_or_else(|err| {
-                    panic!(
-                        "Error in INCLUDE_BASE64 macro at {source_path:?}:{}: {err}",
-                        line_number_of_offset(original, offset)
+                let path = relative_path(source_path, macro_args);
+                let src = ctx.read(&path).map_err(|err| {
+                    error_at(
+                        ErrorKind::Io,
+                        format!("Failed to read {path:?} in INCLUDE_BASE64 macro"),
+                        offset,
                     )
-                });
+                    .caused_by(err)
+                })?;
                 output += STANDARD.encode(&src).as_str();
             }
             "MINIFY" => {
-                let mut split = macro_args.split(',');
-                let Some(file_type) = split.next() else {
-                    return Err(format!("Expected two arguments (file type and code) in {macro_name} at {source_path:?}:{}", line_number_of_offset(original, offset)));
+                let Some((file_type, remaining)) = macro_args.split_once(',') else {
+                    return Err(error_at(
+                        ErrorKind::MacroSyntax,
+                        format!(
+                            "Expected two arguments (file type and code) in {macro_name} macro"
+                        ),
+                        offset,
+                    ));
                 };
-                let remaining = &macro_args[file_type.len() + 1..];
-                match file_type {
-                    "html" => output += &minifier::minify_html(source_path.to_str().unwrap(), remaining, original)?,
+                match file_type.trim() {
+                    "html" => output += &minifier::minify_html(source_path, remaining, file)?,
                     "css" => output += &minifier::minify_css(remaining),
-                    _ => return Err(format!("Unknown file type given in {macro_name} macro at {source_path:?}:{} - file type was `{file_type}`, but can only be html or css", line_number_of_offset(original, offset)))
+                    "js" => output += &minifier::minify_js(remaining),
+                    other => return Err(error_at(
+                        ErrorKind::MacroSyntax,
+                        format!("Unknown file type given in {macro_name} macro - file type was `{other}`, but can only be html, css or js"),
+                        offset,
+                    )),
+                }
+            }
+            "META" => {
+                let (key, default) = match macro_args.split_once(',') {
+                    Some((key, default)) => (key.trim(), Some(default.trim())),
+                    None => (macro_args.trim(), None),
+                };
+                match ctx.front_matter.as_ref().and_then(|front_matter| front_matter.get(key)) {
+                    Some(value) => write!(output, "{value}").unwrap(),
+                    None => match default {
+                        Some(default) => output += default,
+                        None => return Err(error_at(
+                            ErrorKind::FrontMatter,
+                            format!("The front matter doesn't have a value for `{key}`. Give the META macro a default, like `#!META({key}, default)`, if it's optional"),
+                            offset,
+                        )),
+                    },
+                }
+            }
+            "VAR" => {
+                let (name, default) = match macro_args.split_once(',') {
+                    Some((name, default)) => (name.trim(), Some(default.trim())),
+                    None => (macro_args.trim(), None),
+                };
+                match ctx.var(name) {
+                    Some(value) => write!(output, "{value}").unwrap(),
+                    None => match default {
+                        Some(default) => output += default,
+                        None => return Err(error_at(
+                            ErrorKind::UnknownVariable,
+                            format!("The variable `{name}` isn't defined. Define it in `[vars]` in webby.toml, in the target's `vars`, or in the page's front matter, or give the VAR macro a default, like `#!VAR({name}, default)`"),
+                            offset,
+                        )),
+                    },
                 }
             }
+            "FOREACH" => {
+                output += &compile_foreach(macro_args, source_path, ctx).map_err(|err| {
+                    // Errors in the listed pages already say where they are
+                    if err.path.is_some() {
+                        error_at(
+                            err.kind,
+                            "Failed to list pages in FOREACH macro".into(),
+                            offset,
+                        )
+                        .caused_by(err)
+                    } else {
+                        err.at(source_path, file, span.start + offset)
+                    }
+                })?;
+            }
+            "ELSE" => {
+                return Err(error_at(
+                    ErrorKind::MacroSyntax,
+                    "The ELSE macro has to come right after an IF or IFDEF macro".into(),
+                    offset,
+                ))
+            }
+            "TOC" => {
+                let depth = match macro_args.trim() {
+                    "" => 6,
+                    depth => depth.parse::<usize>().ok().filter(|depth| *depth > 0).ok_or_else(|| {
+                        error_at(
+                            ErrorKind::MacroSyntax,
+                            format!("The depth in TOC macro should be a number above 0, but it was `{depth}`"),
+                            offset,
+                        )
+                    })?,
+                };
+                output += &headings::toc_marker(depth);
+            }
+            "CONTENT" | "TITLE" => {
+                let Some(page) = &ctx.page else {
+                    return Err(error_at(
+                        ErrorKind::MacroSyntax,
+                        format!("The {macro_name} macro can only be used in layouts"),
+                        offset,
+                    ));
+                };
+                output += if macro_name == "CONTENT" {
+                    &page.content
+                } else {
+                    &page.title
+                };
+            }
             other => {
-                return Err(format!(
-                    "Unknown macro '{other}' in macro invocation at {source_path:?}:{}",
-                    line_number_of_offset(original, offset)
+                return Err(error_at(
+                    ErrorKind::UnknownMacro,
+                    format!("Unknown macro '{other}' in macro invocation"),
+                    offset,
                 ))
             }
         }
@@ -107,7 +252,8 @@ pub fn compile_macros<'a>(original: &'a str, source_path: &'a Path) -> Result<Co
         offset += paren_close + 1;
     }
 
-    if output.is_empty() {
+    if offset == 0 {
+        // There weren't any macros
         Ok(Cow::Borrowed(original))
     } else {
         output += &original[offset..];
@@ -115,33 +261,338 @@ pub fn compile_macros<'a>(original: &'a str, source_path: &'a Path) -> Result<Co
     }
 }
 
-pub fn copy_batch_target(src: &Path, dest: &Path) {
+/// Compiles `#!FOREACH(pattern, template, options...)`: compiles `template`
+/// once for every page in the collection matching `pattern`, with that page's
+/// front matter (plus its `url`) readable through `#!META(key)`.
+///
+/// The options are `sort=date`, `sort=title`, `reverse` and `limit=N`.
+fn compile_foreach(
+    args: &str,
+    source_path: &Path,
+    ctx: &mut Context,
+) -> Result<String, WebbyError> {
+    let mut args = split_args(args).into_iter();
+    let (Some(pattern), Some(template)) = (args.next(), args.next()) else {
+        return Err(WebbyError::new(
+            ErrorKind::MacroSyntax,
+            "Expected at least two arguments (a glob and an item template) in FOREACH macro",
+        ));
+    };
+
+    let mut sort = Sort::Path;
+    let mut reverse = false;
+    let mut limit = None;
+    for option in args {
+        match option.split_once('=').map(|(key, value)| (key.trim(), value.trim())) {
+            Some(("sort", value)) => {
+                sort = Sort::from_name(value).ok_or_else(|| {
+                    WebbyError::new(
+                        ErrorKind::MacroSyntax,
+                        format!("Unknown sort `{value}` in FOREACH macro; it can be date, title or path"),
+                    )
+                })?;
+            }
+            Some(("limit", value)) => {
+                limit = Some(value.parse::<usize>().map_err(|_| {
+                    WebbyError::new(
+                        ErrorKind::MacroSyntax,
+                        format!("The limit in FOREACH macro should be a number, but it was `{value}`"),
+                    )
+                })?);
+            }
+            None if option == "reverse" => reverse = true,
+            _ => {
+                return Err(WebbyError::new(
+                    ErrorKind::MacroSyntax,
+                    format!("Unknown option `{option}` in FOREACH macro; it can be sort=..., limit=N or reverse"),
+                ))
+            }
+        }
+    }
+
+    let mut items = collection::collect(pattern, source_path, sort, ctx)?;
+    if reverse {
+        items.reverse();
+    }
+    items.truncate(limit.unwrap_or(items.len()));
+
+    let template_path = relative_path(source_path, template);
+    let template = ctx.read_to_string(&template_path).map_err(|err| {
+        WebbyError::new(
+            ErrorKind::Io,
+            format!("Failed to read {template_path:?} in FOREACH macro"),
+        )
+        .caused_by(err)
+    })?;
+    let file_type = FileType::from(template_path.as_path());
+
+    let outer_front_matter = ctx.front_matter.take();
+    let mut output = String::new();
+    let mut result = Ok(());
+    for item in &items {
+        ctx.front_matter = Some(item.template_front_matter());
+        match crate::compile_file(&template, &template_path, file_type, ctx) {
+            Ok((compiled, _)) => output += compiled.as_ref(),
+            Err(err) => {
+                result = Err(WebbyError::new(
+                    err.kind,
+                    format!(
+                        "Failed to compile {template_path:?} for {:?} in FOREACH macro",
+                        item.path
+                    ),
+                )
+                .caused_by(err));
+                break;
+            }
+        }
+    }
+    ctx.front_matter = outer_front_matter;
+
+    result.map(|_| output)
+}
+
+/// Splits a macro's arguments at commas. See [`arg_commas`] for the commas
+/// that are skipped.
+fn split_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for comma in arg_commas(args) {
+        parts.push(args[start..comma].trim());
+        start = comma + 1;
+    }
+    parts.push(args[start..].trim());
+
+    parts
+}
+
+/// Finds the commas that separate a macro's arguments. Commas inside `{}` or
+/// `[]` (which are part of a glob), inside the parentheses of a nested macro,
+/// or inside a quoted value like `"a, b"` don't separate arguments.
+fn arg_commas(args: &str) -> Vec<usize> {
+    let mut commas = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut previous = None;
+    for (idx, byte) in args.bytes().enumerate() {
+        match (byte, quote) {
+            (byte, Some(open)) if byte == open => quote = None,
+            (_, Some(_)) => {}
+            // Only quotes at the start of a value open one, so apostrophes
+            // like in `Bob's` don't
+            (b'"' | b'\'', None)
+                if previous.is_none_or(|previous: u8| {
+                    previous.is_ascii_whitespace() || matches!(previous, b'=' | b',' | b'(')
+                }) =>
+            {
+                quote = Some(byte)
+            }
+            (b'{' | b'[' | b'(', None) => depth += 1,
+            (b'}' | b']' | b')', None) => depth = depth.saturating_sub(1),
+            (b',', None) if depth == 0 => commas.push(idx),
+            _ => {}
+        }
+        previous = Some(byte);
+    }
+
+    commas
+}
+
+/// Finds the `)` that closes a macro's arguments. `paren_open` is the index of
+/// the `(` in `macro_src`.
+fn macro_end(macro_src: &str, paren_open: usize) -> Option<usize> {
+    let mut paren_depth = 0usize;
+    for (idx, byte) in macro_src.bytes().enumerate().skip(paren_open) {
+        if byte == b'(' {
+            paren_depth += 1;
+        } else if byte == b')' {
+            paren_depth -= 1;
+            if paren_depth == 0 {
+                return Some(idx);
+            }
+        }
+    }
+
+    None
+}
+
+/// Compiles `#!IF(condition, text)` or `#!IFDEF(variable, text)`, and the
+/// `#!ELSE(text)` that may come right after it. `args` is the span of the
+/// macro's arguments and `rest` is the span of the file after it. Only the
+/// branch that's used is compiled, so the other one can use variables that
+/// aren't defined or include files that don't exist.
+///
+/// Returns the compiled branch and the length of the ELSE macro (plus any
+/// whitespace before it), if there is one.
+fn compile_conditional<'a>(
+    file: &'a str,
+    macro_name: &str,
+    args: Range<usize>,
+    rest: Range<usize>,
+    source_path: &Path,
+    ctx: &mut Context,
+) -> Result<(Cow<'a>, usize), WebbyError> {
+    let error_at = |message: String, offset: usize| {
+        WebbyError::new(ErrorKind::MacroSyntax, message).at(source_path, file, offset)
+    };
+
+    // The condition can have commas in nested macros or quoted values
+    let Some(&comma) = arg_commas(&file[args.clone()]).first() else {
+        return Err(error_at(
+            format!("Expected two arguments (condition and text) in {macro_name} macro"),
+            args.start,
+        ));
+    };
+    let condition = compile_macros_in(file, args.start..args.start + comma, source_path, ctx)?;
+    let condition = condition.trim();
+    let met = if macro_name == "IFDEF" {
+        condition == "profile" || ctx.var(condition).is_some()
+    } else {
+        evaluate(condition, ctx).map_err(|message| error_at(message, args.start))?
+    };
+    let then_branch = trim_span(file, args.start + comma + 1..args.end);
+
+    let after = &file[rest.clone()];
+    let whitespace = after.len() - after.trim_start().len();
+    let else_src = &after[whitespace..];
+    let else_branch = match else_src.strip_prefix("#!ELSE") {
+        Some(else_args) if else_args.starts_with('(') => {
+            let Some(paren_close) = macro_end(else_src, "#!ELSE".len()) else {
+                return Err(error_at(
+                    "Expected ) to end macro invocation".into(),
+                    rest.start + whitespace,
+                ));
+            };
+            let else_start = rest.start + whitespace;
+            Some((
+                trim_span(file, else_start + "#!ELSE(".len()..else_start + paren_close),
+                whitespace + paren_close + 1,
+            ))
+        }
+        _ => None,
+    };
+
+    let else_len = else_branch.as_ref().map(|(_, len)| *len).unwrap_or(0);
+    let branch = match (met, else_branch) {
+        (true, _) => then_branch,
+        (false, Some((else_branch, _))) => else_branch,
+        (false, None) => return Ok((Cow::Borrowed(""), 0)),
+    };
+
+    Ok((compile_macros_in(file, branch, source_path, ctx)?, else_len))
+}
+
+/// Shrinks a span so it doesn't include whitespace at its start or end.
+fn trim_span(file: &str, span: Range<usize>) -> Range<usize> {
+    let text = &file[span.clone()];
+    let start = span.start + (text.len() - text.trim_start().len());
+    let end = span.end - (text.len() - text.trim_end().len());
+    start..end.max(start)
+}
+
+/// Evaluates an IF macro's condition. Conditions compare a variable (or
+/// `profile`, the build profile) to a value with `==` or `!=`, or check if a
+/// variable is truthy with `name` or `!name`. They can be combined with `&&`
+/// and `||`.
+fn evaluate(condition: &str, ctx: &Context) -> Result<bool, String> {
+    let resolve = |name: &str| -> Option<String> {
+        if name == "profile" {
+            Some(ctx.profile.name().to_string())
+        } else {
+            ctx.var(name).map(|value| value.to_string())
+        }
+    };
+    let term = |term: &str| -> Result<bool, String> {
+        let term = term.trim();
+        if term.is_empty() {
+            return Err(format!("Empty condition in IF macro: `{condition}`"));
+        }
+
+        for (operator, equal) in [("==", true), ("!=", false)] {
+            if let Some((name, value)) = term.split_once(operator) {
+                let value = value.trim().trim_matches(['"', '\'']);
+                return Ok((resolve(name.trim()).as_deref() == Some(value)) == equal);
+            }
+        }
+
+        let (name, negated) = match term.strip_prefix('!') {
+            Some(name) => (name.trim(), true),
+            None => (term, false),
+        };
+        let truthy = name == "profile"
+            || match ctx.var(name) {
+                None | Some(Value::Boolean(false)) | Some(Value::Integer(0)) => false,
+                Some(Value::String(string)) => !string.is_empty(),
+                Some(Value::Array(array)) => !array.is_empty(),
+                Some(_) => true,
+            };
+        Ok(truthy != negated)
+    };
+
+    for alternative in condition.split("||") {
+        let mut all = true;
+        for part in alternative.split("&&") {
+            all &= term(part)?;
+        }
+        if all {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Resolves a path given in a macro relative to the folder of the file the
+/// macro is in.
+fn relative_path(source_path: &Path, path: &str) -> PathBuf {
+    source_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(path.trim())
+}
+
+pub fn copy_batch_target(src: &Path, dest: &Path) -> Result<(), WebbyError> {
+    let io_error = |message: String, err| {
+        WebbyError::new(ErrorKind::Io, message)
+            .in_file(src)
+            .caused_by(err)
+    };
+
     if dest.is_file() {
-        fs::remove_file(dest).unwrap_or_else(|err| {
-                panic!("Failed to copy batch target {src:?}. There was already a file where its output should go ({dest:?}), which couldn't be removed: {err}");
-            });
+        fs::remove_file(dest).map_err(|err| {
+            io_error(
+                format!("Failed to remove the file at {dest:?}, which is where this batch target's output should go"),
+                err,
+            )
+        })?;
     }
     if !dest.exists() {
-        fs::create_dir_all(dest).unwrap_or_else(|err| {
-                panic!("Failed to copy batch target {src:?}. Couldn't create its output folder at {dest:?} because: {err}");
-            });
+        fs::create_dir_all(dest).map_err(|err| {
+            io_error(
+                format!("Failed to create this batch target's output folder at {dest:?}"),
+                err,
+            )
+        })?;
     }
 
-    let src = src.read_dir().unwrap_or_else(|err| {
-        panic!(
-            "Failed to copy batch target {dest:?}. Couldn't open its source directory because: {err}"
-        );
-    });
+    let entries = src.read_dir().map_err(|err| {
+        io_error(
+            "Failed to open this batch target's source folder".into(),
+            err,
+        )
+    })?;
 
-    for dir_entry in src.filter_map(|dir_entry| dir_entry.ok()) {
+    for dir_entry in entries.filter_map(|dir_entry| dir_entry.ok()) {
+        let name = dir_entry.file_name();
         let dir_entry = &dir_entry.path();
 
         if dir_entry.is_file() {
-            fs::copy(dir_entry, dest.join(dir_entry.file_name().unwrap())).unwrap_or_else(|err| {
-                panic!("Failed to copy batch target {dest:?}. Couldn't copy file at {dir_entry:?} because: {err}");
-            });
+            fs::copy(dir_entry, dest.join(&name)).map_err(|err| {
+                io_error(format!("Failed to copy the file at {dir_entry:?}"), err)
+            })?;
         } else {
-            copy_batch_target(dir_entry, &dest.join(dir_entry.file_name().unwrap()));
+            copy_batch_target(dir_entry, &dest.join(&name))?;
         }
     }
+
+    Ok(())
 }
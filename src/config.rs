@@ -0,0 +1,1246 @@
+use {
+    crate::{
+        feed::{Feed, FeedFormat},
+        front_matter::{self, Value},
+        glob,
+        headings::HeadingOptions,
+        minifier::MinifyOptions,
+        translator::{Constructs, GemtextOptions, MarkdownOptions},
+        ErrorKind, FileType, Mode, OutputFormat, Pattern, Target, WebbyError,
+    },
+    boml::{
+        table::{Table, TomlGetError},
+        Toml,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Settings, usually from the command line, that override what's in
+/// `webby.toml`.
+#[derive(Clone, Default)]
+pub struct Overrides {
+    pub output_dir: Option<PathBuf>,
+    pub dry_run: bool,
+    pub profile: Option<Profile>,
+}
+
+/// Which set of settings a project is built with. Conditional macros can check
+/// this with `profile == release`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// The profile used while working on a site. This is the default.
+    #[default]
+    Dev,
+    /// The profile used to build a site for publishing, selected with
+    /// `--release`.
+    Release,
+}
+impl Profile {
+    pub const ALL: [Self; 2] = [Self::Dev, Self::Release];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dev => "dev",
+            Self::Release => "release",
+        }
+    }
+}
+
+/// The settings for a profile, from its `[profile.<name>]` table.
+#[derive(Clone, Debug)]
+pub struct ProfileSettings {
+    /// Where compiled files are written to. Defaults to the project's
+    /// `output`.
+    pub output_dir: PathBuf,
+    pub minify: MinifyOptions,
+    /// Variables that override the ones in `[vars]`.
+    pub vars: BTreeMap<String, Value>,
+}
+
+/// The name targets use for the project's `output` folder in their `outputs`.
+pub const DEFAULT_OUTPUT: &str = "default";
+
+/// An extra output folder, from an `[outputs.<name>]` table. Targets can be
+/// written to several of these at once, like an HTML site and a Gemini
+/// capsule.
+#[derive(Clone, Debug)]
+pub struct OutputRoot {
+    pub path: PathBuf,
+    /// The format translated files are written in.
+    pub format: OutputFormat,
+}
+
+/// A parsed `webby.toml`.
+pub struct Config {
+    /// The path to the `webby.toml` file.
+    pub path: PathBuf,
+    /// The folder the `webby.toml` file is in. Paths in the config are relative
+    /// to this folder.
+    pub root: PathBuf,
+    /// The folder compiled files are written to.
+    pub output_dir: PathBuf,
+    /// Extra output folders, by name. Targets are only written to these if
+    /// they list them in their `outputs`.
+    pub outputs: BTreeMap<String, OutputRoot>,
+    /// The URL the site is published at, without a trailing `/`, like
+    /// `https://example.com`. Feeds and the sitemap need this to link to
+    /// pages.
+    pub base_url: Option<String>,
+    /// If set, a `sitemap.xml` listing every HTML page is written to the
+    /// output directory after the targets are built.
+    pub sitemap: bool,
+    /// If set, a `robots.txt` that allows every page (and links to the
+    /// sitemap, if there is one) is written to the output directory.
+    pub robots: bool,
+    /// If set, the CSS theme for syntax highlighting is written to this path
+    /// in every HTML output folder.
+    pub highlight_theme: Option<PathBuf>,
+    pub targets: Vec<Target>,
+    /// The extension compiled files of each file type are given in the output
+    /// directory. By default, translated files (Markdown and Gemtext) are
+    /// given a `.html` extension, and other files keep theirs.
+    pub output_extensions: HashMap<FileType, String>,
+    /// Variables from the `[vars]` table, which can be used with `#!VAR(name)`
+    /// in every target. The current profile's variables override these.
+    pub vars: BTreeMap<String, Value>,
+    /// The profile the project is being built with. Change it with
+    /// [`Config::set_profile`].
+    pub profile: Profile,
+    /// The settings for each profile.
+    pub profiles: HashMap<Profile, ProfileSettings>,
+    /// If set, files marked with `draft = true` in their front matter are
+    /// built. Otherwise they're skipped.
+    pub drafts: bool,
+    /// If set, targets are compiled as normal, but nothing is written to the
+    /// output directory. This is used by `webby check`.
+    pub dry_run: bool,
+}
+impl Config {
+    /// Searches `dir` and its parent folders for a `webby.toml` file.
+    pub fn find(dir: &Path) -> Result<PathBuf, WebbyError> {
+        let mut dir = dir;
+
+        loop {
+            let path = dir.join("webby.toml");
+            if path.is_file() {
+                return Ok(path);
+            }
+
+            let Some(parent) = dir.parent() else {
+                return Err(WebbyError::new(
+                    ErrorKind::Config,
+                    "Failed to find webby.toml",
+                ));
+            };
+            dir = parent;
+        }
+    }
+
+    /// Reads and parses the `webby.toml` file at `path`.
+    pub fn load(path: &Path) -> Result<Self, WebbyError> {
+        let src = fs::read_to_string(path).map_err(|err| {
+            WebbyError::new(ErrorKind::Io, "Failed to read webby.toml")
+                .in_file(path)
+                .caused_by(err)
+        })?;
+
+        Self::parse(&src, path)
+    }
+
+    /// Parses the source of a `webby.toml` file. `path` is where the file is
+    /// located; paths in the config are resolved relative to its folder.
+    pub fn parse(src: &str, path: &Path) -> Result<Self, WebbyError> {
+        if let Some(offset) = front_matter::find_bare_datetime(src) {
+            return Err(WebbyError::new(
+                ErrorKind::Config,
+                "webby.toml has a date or time that isn't in quotes, which webby can't read yet; put it in quotes to use it as a string, like `updated = \"2024-01-01\"`",
+            )
+            .at(path, src, offset));
+        }
+        let toml = Toml::parse(src).map_err(|err| {
+            WebbyError::new(
+                ErrorKind::Config,
+                format!("Failed to parse webby.toml: {:?}", err.kind),
+            )
+            .at(path, src, err.start)
+        })?;
+        let root = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let config_error =
+            |message: String| WebbyError::new(ErrorKind::Config, message).in_file(path);
+
+        let output_dir = if let Ok(output) = toml.get_string("output") {
+            root.join(output)
+        } else {
+            root.join("webby")
+        };
+
+        let mut output_extensions = HashMap::from([
+            (FileType::Markdown, "html".to_string()),
+            (FileType::Gemtext, "html".to_string()),
+        ]);
+        match toml.get_table("output_extensions") {
+            Ok(table) => {
+                for (name, extension) in table.iter() {
+                    let Some(file_type) = parse_file_type(name) else {
+                        return Err(config_error(format!(
+                            "Unknown file type `{name}` in `output_extensions`"
+                        )));
+                    };
+                    let Some(extension) = extension.string() else {
+                        return Err(config_error(format!(
+                            "The output extension for `{name}` has to be a string"
+                        )));
+                    };
+                    output_extensions.insert(file_type, extension.trim_start_matches('.').into());
+                }
+            }
+            Err(TomlGetError::InvalidKey) => {}
+            Err(TomlGetError::TypeMismatch(_, _)) => {
+                return Err(config_error(
+                    "`output_extensions` has to be a table in webby.toml".into(),
+                ))
+            }
+        }
+        let clean_urls = toml.get_boolean("clean_urls").unwrap_or(false);
+        let layout = toml
+            .get_string("layout")
+            .ok()
+            .map(|layout| root.join(layout));
+        let vars = match toml.get_table("vars") {
+            Ok(table) => parse_vars(table, "`[vars]`", path)?,
+            Err(TomlGetError::InvalidKey) => BTreeMap::new(),
+            Err(TomlGetError::TypeMismatch(_, _)) => {
+                return Err(config_error(
+                    "`vars` has to be a table in webby.toml".into(),
+                ))
+            }
+        };
+
+        let gemtext = match toml.get_table("gemtext") {
+            Ok(table) => {
+                parse_gemtext_options(table, &GemtextOptions::default(), "`[gemtext]`", path)?
+            }
+            Err(TomlGetError::InvalidKey) => GemtextOptions::default(),
+            Err(TomlGetError::TypeMismatch(_, _)) => {
+                return Err(config_error(
+                    "`gemtext` has to be a table in webby.toml".into(),
+                ))
+            }
+        };
+
+        let markdown = match toml.get_table("markdown") {
+            Ok(table) => {
+                parse_markdown_options(table, &MarkdownOptions::default(), "`[markdown]`", path)?
+            }
+            Err(TomlGetError::InvalidKey) => MarkdownOptions::default(),
+            Err(TomlGetError::TypeMismatch(_, _)) => {
+                return Err(config_error(
+                    "`markdown` has to be a table in webby.toml".into(),
+                ))
+            }
+        };
+
+        // `highlight = true`, or a table with the file to write the theme to
+        let (highlight, highlight_theme) = match toml.get_boolean("highlight") {
+            Ok(highlight) => (highlight, None),
+            Err(TomlGetError::InvalidKey) => (false, None),
+            Err(TomlGetError::TypeMismatch(_, _)) => match toml.get_table("highlight") {
+                Ok(table) => (
+                    table.get_boolean("enabled").unwrap_or(true),
+                    table.get_string("theme").ok().map(PathBuf::from),
+                ),
+                Err(_) => {
+                    return Err(config_error(
+                        "`highlight` has to be true, false, or a table in webby.toml".into(),
+                    ))
+                }
+            },
+        };
+
+        let headings = match toml.get_table("headings") {
+            Ok(table) => {
+                parse_heading_options(table, &HeadingOptions::default(), "`[headings]`", path)?
+            }
+            Err(TomlGetError::InvalidKey) => HeadingOptions::default(),
+            Err(TomlGetError::TypeMismatch(_, _)) => {
+                return Err(config_error(
+                    "`headings` has to be a table in webby.toml".into(),
+                ))
+            }
+        };
+
+        let profile_tables = match toml.get_table("profile") {
+            Ok(table) => Some(table),
+            Err(TomlGetError::InvalidKey) => None,
+            Err(TomlGetError::TypeMismatch(_, _)) => {
+                return Err(config_error(
+                    "`profile` has to be a table in webby.toml".into(),
+                ))
+            }
+        };
+        if let Some((name, _)) = profile_tables
+            .iter()
+            .flat_map(|tables| tables.iter())
+            .find(|(name, _)| !Profile::ALL.iter().any(|profile| profile.name() == *name))
+        {
+            return Err(config_error(format!(
+                "Unknown profile `{name}`; profiles can only be `dev` or `release`"
+            )));
+        }
+        let mut profiles = HashMap::new();
+        for profile in Profile::ALL {
+            let name = profile.name();
+            let table = match profile_tables.map(|tables| tables.get_table(name)) {
+                None | Some(Err(TomlGetError::InvalidKey)) => None,
+                Some(Ok(table)) => Some(table),
+                Some(Err(TomlGetError::TypeMismatch(_, _))) => {
+                    return Err(config_error(format!(
+                        "`profile.{name}` has to be a table in webby.toml"
+                    )))
+                }
+            };
+            let get_bool = |key: &str| table.and_then(|table| table.get_boolean(key).ok());
+
+            profiles.insert(
+                profile,
+                ProfileSettings {
+                    output_dir: table
+                        .and_then(|table| table.get_string("output").ok())
+                        .map(|output| root.join(output))
+                        .unwrap_or_else(|| output_dir.clone()),
+                    minify: MinifyOptions {
+                        minify: get_bool("minify").unwrap_or(true),
+                        strip_comments: get_bool("strip_comments").unwrap_or(true),
+                    },
+                    vars: match table.map(|table| table.get_table("vars")) {
+                        None | Some(Err(TomlGetError::InvalidKey)) => BTreeMap::new(),
+                        Some(Ok(vars)) => {
+                            parse_vars(vars, &format!("`[profile.{name}.vars]`"), path)?
+                        }
+                        Some(Err(TomlGetError::TypeMismatch(_, _))) => {
+                            return Err(config_error(format!(
+                                "`profile.{name}.vars` has to be a table in webby.toml"
+                            )))
+                        }
+                    },
+                },
+            );
+        }
+
+        let mut outputs = BTreeMap::new();
+        match toml.get_table("outputs") {
+            Ok(table) => {
+                for (name, value) in table.iter() {
+                    if name == DEFAULT_OUTPUT {
+                        return Err(config_error(format!(
+                            "`{DEFAULT_OUTPUT}` can't be used as the name of an output, because it's the project's `output` folder"
+                        )));
+                    }
+                    let (output_path, format) = match (value.string(), value.table()) {
+                        (Some(output_path), _) => (output_path, None),
+                        (_, Some(table)) => {
+                            let Ok(output_path) = table.get_string("path") else {
+                                return Err(config_error(format!(
+                                    "The output `{name}` didn't have a path"
+                                )));
+                            };
+                            (output_path, table.get_string("format").ok())
+                        }
+                        _ => {
+                            return Err(config_error(format!(
+                                "The output `{name}` has to be a path or a table"
+                            )))
+                        }
+                    };
+                    let format = match format {
+                        None => OutputFormat::Html,
+                        Some(format) => parse_output_format(format).ok_or_else(|| {
+                            config_error(format!(
+                                "Unknown format `{format}` for the output `{name}`; it can be html or gemtext"
+                            ))
+                        })?,
+                    };
+                    outputs.insert(
+                        name.to_string(),
+                        OutputRoot {
+                            path: root.join(output_path),
+                            format,
+                        },
+                    );
+                }
+            }
+            Err(TomlGetError::InvalidKey) => {}
+            Err(TomlGetError::TypeMismatch(_, _)) => {
+                return Err(config_error(
+                    "`outputs` has to be a table in webby.toml".into(),
+                ))
+            }
+        }
+
+        let mut targets = Vec::default();
+
+        match toml.get_array("target") {
+            Ok(target_tables) => {
+                for target in target_tables {
+                    let Some(table) = target.table() else {
+                        return Err(config_error(
+                            "All target entries in webby.toml must be a TOML table.".into(),
+                        ));
+                    };
+                    let Ok(source) = table.get_string("path") else {
+                        return Err(config_error(
+                            "Target in webby.toml didn't have a path".into(),
+                        ));
+                    };
+                    // For globs, the target's path is the folder before the
+                    // first component with glob syntax in it
+                    let (base, pattern) = glob::split(source).map_err(|err| err.in_file(path))?;
+                    let target_path = root.join(base);
+                    let exclude = match table.get_array("exclude") {
+                        Ok(patterns) => patterns
+                            .iter()
+                            .map(|pattern| {
+                                let Some(pattern) = pattern.string() else {
+                                    return Err(config_error(format!(
+                                        "The `exclude` list for target {target_path:?} can only contain strings"
+                                    )));
+                                };
+                                Pattern::exclude(pattern).map_err(|err| err.in_file(path))
+                            })
+                            .collect::<Result<_, _>>()?,
+                        Err(TomlGetError::InvalidKey) => Vec::new(),
+                        Err(TomlGetError::TypeMismatch(_, _)) => {
+                            return Err(config_error(format!(
+                                "The `exclude` entry for target {target_path:?} has to be an array"
+                            )))
+                        }
+                    };
+                    let mode = if let Ok(mode) = table.get_string("mode") {
+                        match mode {
+                            "compile" => Mode::Compile,
+                            "copy" => Mode::Copy,
+                            "link" => Mode::Link,
+                            other => {
+                                return Err(config_error(format!(
+                                    "Unknown mode: {other} for target: {target_path:?}"
+                                )))
+                            }
+                        }
+                    } else {
+                        // Globs like `*.md` still have an extension
+                        match Path::new(source)
+                            .extension()
+                            .and_then(|osstr| osstr.to_str())
+                        {
+                            Some("gmi" | "html" | "svg" | "md" | "css" | "js" | "mjs") => {
+                                Mode::Compile
+                            }
+                            _ => Mode::Copy,
+                        }
+                    };
+                    let target_vars = match table.get_table("vars") {
+                        Ok(vars) => {
+                            parse_vars(vars, &format!("the vars for target {target_path:?}"), path)?
+                        }
+                        Err(TomlGetError::InvalidKey) => BTreeMap::new(),
+                        Err(TomlGetError::TypeMismatch(_, _)) => {
+                            return Err(config_error(format!(
+                                "The `vars` for target {target_path:?} have to be a table"
+                            )))
+                        }
+                    };
+                    // The target's `gemtext` table overrides the project's
+                    let target_gemtext = match table.get_table("gemtext") {
+                        Ok(options) => parse_gemtext_options(
+                            options,
+                            &gemtext,
+                            &format!("the gemtext options for target {target_path:?}"),
+                            path,
+                        )?,
+                        Err(TomlGetError::InvalidKey) => gemtext.clone(),
+                        Err(TomlGetError::TypeMismatch(_, _)) => {
+                            return Err(config_error(format!(
+                                "The `gemtext` options for target {target_path:?} have to be a table"
+                            )))
+                        }
+                    };
+                    let target_markdown = match table.get_table("markdown") {
+                        Ok(options) => parse_markdown_options(
+                            options,
+                            &markdown,
+                            &format!("the markdown options for target {target_path:?}"),
+                            path,
+                        )?,
+                        Err(TomlGetError::InvalidKey) => markdown.clone(),
+                        Err(TomlGetError::TypeMismatch(_, _)) => {
+                            return Err(config_error(format!(
+                                "The `markdown` options for target {target_path:?} have to be a table"
+                            )))
+                        }
+                    };
+                    let target_headings = match table.get_table("headings") {
+                        Ok(options) => parse_heading_options(
+                            options,
+                            &headings,
+                            &format!("the heading options for target {target_path:?}"),
+                            path,
+                        )?,
+                        Err(TomlGetError::InvalidKey) => headings.clone(),
+                        Err(TomlGetError::TypeMismatch(_, _)) => {
+                            return Err(config_error(format!(
+                                "The `headings` options for target {target_path:?} have to be a table"
+                            )))
+                        }
+                    };
+                    let keep_output_name = table.get_string("output").is_ok();
+                    // Relative to the output directory
+                    let output = if let Ok(output_name) = table.get_string("output") {
+                        PathBuf::from(output_name)
+                    } else if pattern.is_some() {
+                        // Glob targets keep their folder structure in the
+                        // output directory
+                        target_path
+                            .strip_prefix(&root)
+                            .unwrap_or(&target_path)
+                            .to_path_buf()
+                    } else {
+                        let Some(file_name) = target_path.file_name() else {
+                            return Err(config_error(format!(
+                                "Target {target_path:?} doesn't have a file name, so it needs an `output`"
+                            )));
+                        };
+                        PathBuf::from(file_name)
+                    };
+                    // The output folders to write the target to, and the
+                    // format translated files are written in for each
+                    let target_outputs = match table.get_array("outputs") {
+                        Ok(names) => names
+                            .iter()
+                            .map(|name| match name.string() {
+                                Some(DEFAULT_OUTPUT) => Ok((&output_dir, OutputFormat::Html)),
+                                Some(name) => match outputs.get(name) {
+                                    Some(OutputRoot { path, format }) => Ok((path, *format)),
+                                    None => Err(config_error(format!(
+                                        "Target {target_path:?} uses the output `{name}`, but there's no `[outputs.{name}]` in webby.toml"
+                                    ))),
+                                },
+                                None => Err(config_error(format!(
+                                    "The `outputs` for target {target_path:?} can only contain names of outputs"
+                                ))),
+                            })
+                            .collect::<Result<Vec<_>, _>>()?,
+                        Err(TomlGetError::InvalidKey) => vec![(&output_dir, OutputFormat::Html)],
+                        Err(TomlGetError::TypeMismatch(_, _)) => {
+                            return Err(config_error(format!(
+                                "The `outputs` for target {target_path:?} have to be an array"
+                            )))
+                        }
+                    };
+                    // `format` converts the target to that format in every
+                    // output it's written to, like HTML pages to Gemtext
+                    let target_format = match table.get_string("format") {
+                        Ok(format) => Some(parse_output_format(format).ok_or_else(|| {
+                            config_error(format!(
+                                "Unknown format `{format}` for target {target_path:?}; it can be html or gemtext"
+                            ))
+                        })?),
+                        Err(TomlGetError::InvalidKey) => None,
+                        Err(TomlGetError::TypeMismatch(_, _)) => {
+                            return Err(config_error(format!(
+                                "The `format` for target {target_path:?} has to be a string"
+                            )))
+                        }
+                    };
+                    let file_type = if let Ok(file_type) = table.get_string("filetype") {
+                        let Some(file_type) = parse_file_type(file_type) else {
+                            return Err(config_error(format!("Target `{target_path:?}` had an unexpected filetype: {file_type}\n`filetype` must be one of html, css, js, gemtext, or markdown")));
+                        };
+                        file_type
+                    } else {
+                        FileType::from(target_path.as_path())
+                    };
+
+                    let target = Target {
+                        path: target_path,
+                        output: PathBuf::new(),
+                        mode,
+                        file_type,
+                        pattern,
+                        exclude,
+                        output_extension: table
+                            .get_string("output_extension")
+                            .ok()
+                            .map(|extension| extension.trim_start_matches('.').into()),
+                        clean_urls: table.get_boolean("clean_urls").unwrap_or(clean_urls),
+                        keep_output_name,
+                        // `layout = false` turns off the project's layout for
+                        // this target
+                        layout: match table.get_string("layout") {
+                            Ok(layout) => Some(root.join(layout)),
+                            Err(_) if table.get_boolean("layout") == Ok(false) => None,
+                            Err(_) => layout.clone(),
+                        },
+                        vars: target_vars,
+                        sitemap: table.get_boolean("sitemap").unwrap_or(true),
+                        format: OutputFormat::Html,
+                        gemtext: target_gemtext,
+                        markdown: target_markdown,
+                        highlight: table.get_boolean("highlight").unwrap_or(highlight),
+                        headings: target_headings,
+                        feed: None,
+                    };
+                    for (output_root, format) in target_outputs {
+                        targets.push(Target {
+                            output: output_root.join(&output),
+                            format: target_format.unwrap_or(format),
+                            ..target.clone()
+                        });
+                    }
+                }
+            }
+            Err(e) => match e {
+                TomlGetError::InvalidKey => {
+                    return Err(config_error("No targets specified. See the GitHub for an example on setting up a webby project: https://github.com/bright-shard/webby".into()));
+                }
+                TomlGetError::TypeMismatch(_, _) => {
+                    return Err(config_error(
+                        "The 'target' entry has to an array in webby.toml".into(),
+                    ));
+                }
+            },
+        }
+
+        let base_url = toml
+            .get_string("base_url")
+            .ok()
+            .map(|url| url.trim_end_matches('/').to_string());
+        match toml.get_array("feed") {
+            Ok(feed_tables) => {
+                for feed in feed_tables {
+                    let Some(table) = feed.table() else {
+                        return Err(config_error(
+                            "All feed entries in webby.toml must be a TOML table.".into(),
+                        ));
+                    };
+                    let Ok(source) = table.get_string("path") else {
+                        return Err(config_error("Feed in webby.toml didn't have a path".into()));
+                    };
+                    let Some(base_url) = &base_url else {
+                        return Err(config_error(format!(
+                            "The feed for `{source}` needs a `base_url` at the top of webby.toml, like `base_url = \"https://example.com\"`, so it can link to posts"
+                        )));
+                    };
+                    let Ok(title) = table.get_string("title") else {
+                        return Err(config_error(format!(
+                            "The feed for `{source}` didn't have a title"
+                        )));
+                    };
+                    let limit = match table.get_integer("limit") {
+                        Ok(limit) => Some(usize::try_from(limit).map_err(|_| {
+                            config_error(format!(
+                                "The `limit` for the feed for `{source}` can't be negative"
+                            ))
+                        })?),
+                        Err(TomlGetError::InvalidKey) => None,
+                        Err(TomlGetError::TypeMismatch(_, _)) => {
+                            return Err(config_error(format!(
+                                "The `limit` for the feed for `{source}` has to be a number"
+                            )))
+                        }
+                    };
+                    let (base, pattern) = glob::split(source).map_err(|err| err.in_file(path))?;
+
+                    let outputs = [
+                        (FeedFormat::Atom, table.get_string("atom").ok()),
+                        (FeedFormat::Rss, table.get_string("rss").ok()),
+                    ];
+                    if outputs.iter().all(|(_, output)| output.is_none()) {
+                        return Err(config_error(format!(
+                            "The feed for `{source}` needs an `atom` or `rss` file to write to"
+                        )));
+                    }
+                    for (format, output) in outputs {
+                        let Some(output) = output else {
+                            continue;
+                        };
+                        targets.push(Target {
+                            path: root.join(base),
+                            output: output_dir.join(output),
+                            mode: Mode::Compile,
+                            file_type: FileType::Unknown,
+                            pattern: pattern.clone(),
+                            exclude: Vec::new(),
+                            output_extension: None,
+                            clean_urls: false,
+                            keep_output_name: true,
+                            layout: None,
+                            vars: BTreeMap::new(),
+                            sitemap: false,
+                            format: OutputFormat::Html,
+                            gemtext: gemtext.clone(),
+                            markdown: markdown.clone(),
+                            highlight,
+                            headings: headings.clone(),
+                            feed: Some(Feed {
+                                format,
+                                title: title.to_string(),
+                                description: table
+                                    .get_string("description")
+                                    .ok()
+                                    .map(str::to_string),
+                                author: table.get_string("author").ok().map(str::to_string),
+                                base_url: base_url.clone(),
+                                limit,
+                            }),
+                        });
+                    }
+                }
+            }
+            Err(TomlGetError::InvalidKey) => {}
+            Err(TomlGetError::TypeMismatch(_, _)) => {
+                return Err(config_error(
+                    "The 'feed' entry has to be an array in webby.toml".into(),
+                ))
+            }
+        }
+
+        let sitemap = toml.get_boolean("sitemap").unwrap_or(false);
+        if sitemap && base_url.is_none() {
+            return Err(config_error(
+                "`sitemap = true` needs a `base_url` at the top of webby.toml, like `base_url = \"https://example.com\"`, so the sitemap can link to pages".into(),
+            ));
+        }
+
+        let mut config = Self {
+            path: path.to_path_buf(),
+            root,
+            output_dir,
+            outputs,
+            base_url,
+            sitemap,
+            robots: toml.get_boolean("robots").unwrap_or(false),
+            highlight_theme,
+            targets,
+            output_extensions,
+            vars,
+            profile: Profile::default(),
+            profiles,
+            drafts: toml.get_boolean("drafts").unwrap_or(false),
+            dry_run: false,
+        };
+        config.set_profile(Profile::default());
+
+        Ok(config)
+    }
+
+    /// Switches to another profile, moving the output directory to that
+    /// profile's.
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.profile = profile;
+        let output_dir = self.profile_settings().output_dir.clone();
+        self.set_output_dir(output_dir);
+    }
+
+    /// The settings for the current profile.
+    pub fn profile_settings(&self) -> &ProfileSettings {
+        &self.profiles[&self.profile]
+    }
+
+    /// Where a compiled file should be written to. `output` is the path
+    /// mirroring its source file in the output directory; this changes the
+    /// extension of translated files and applies clean URLs.
+    pub fn output_path(&self, target: &Target, output: &Path, file_type: FileType) -> PathBuf {
+        if !matches!(target.mode, Mode::Compile) {
+            return output.to_path_buf();
+        }
+
+        let extension = match target.format {
+            OutputFormat::Html => target
+                .output_extension
+                .as_deref()
+                .filter(|_| file_type.is_translated())
+                .or_else(|| self.output_extensions.get(&file_type).map(String::as_str)),
+            // Capsules always use Gemtext's own extension, and HTML pages are
+            // translated to Gemtext for them
+            OutputFormat::Gemtext if file_type.is_translated() || file_type == FileType::Html => {
+                Some(target.output_extension.as_deref().unwrap_or("gmi"))
+            }
+            OutputFormat::Gemtext => None,
+        };
+        let output = match extension {
+            Some(extension) => output.with_extension(extension),
+            None => output.to_path_buf(),
+        };
+
+        if target.clean_urls
+            && output.extension().is_some_and(|ext| ext == "html")
+            && output
+                .file_stem()
+                .is_some_and(|stem| stem != "index" && stem != "404")
+        {
+            output.with_extension("").join("index.html")
+        } else {
+            output
+        }
+    }
+
+    /// Where the file at `source` is written to, if any target builds it. If
+    /// it's written to several outputs, the first one in `format` is
+    /// preferred.
+    pub fn output_of(&self, source: &Path, format: OutputFormat) -> Option<PathBuf> {
+        let mut outputs = self
+            .targets
+            .iter()
+            .filter_map(|target| Some((target.format, self.target_output(target, source)?)));
+        let first = outputs.next()?;
+        if first.0 == format {
+            return Some(first.1);
+        }
+
+        Some(
+            outputs
+                .find(|(target_format, _)| *target_format == format)
+                .unwrap_or(first)
+                .1,
+        )
+    }
+
+    /// Where `target` writes the file at `source` to, or `None` if `target`
+    /// doesn't build `source`.
+    pub fn target_output(&self, target: &Target, source: &Path) -> Option<PathBuf> {
+        if target.feed.is_some() {
+            return None;
+        }
+        let source = crate::normalize_path(source);
+        let target_path = crate::normalize_path(&target.path);
+        if target.pattern.is_none() && source == target_path {
+            return Some(if target.keep_output_name {
+                target.output.clone()
+            } else {
+                self.output_path(target, &target.output, target.file_type)
+            });
+        }
+
+        let relative = source.strip_prefix(&target_path).ok()?;
+        let ignored = relative
+            .components()
+            .any(|component| component.as_os_str().to_str().is_some_and(glob::is_ignored));
+        let excluded = relative.ancestors().any(|ancestor| {
+            target
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches(ancestor))
+        });
+        let matches = target
+            .pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.matches(relative));
+        if relative.as_os_str().is_empty() || ignored || excluded || !matches {
+            return None;
+        }
+
+        Some(self.output_path(
+            target,
+            &target.output.join(relative),
+            FileType::from(source.as_path()),
+        ))
+    }
+
+    /// The URL of the page built from `source`, relative to the root of the
+    /// site, like `/posts/hello/`. Returns `None` if no target builds
+    /// `source`.
+    pub fn url_of(&self, source: &Path, format: OutputFormat) -> Option<String> {
+        self.url_of_output(&self.output_of(source, format)?)
+    }
+
+    /// The URL of a file in the output directory (or one of the extra
+    /// outputs), relative to the root of the site. `index.html` files are
+    /// linked to by their folder.
+    pub fn url_of_output(&self, output: &Path) -> Option<String> {
+        // The most specific folder, in case an output is inside another
+        let output_dir = self
+            .output_dirs()
+            .filter(|output_dir| output.starts_with(output_dir))
+            .max_by_key(|output_dir| output_dir.components().count())?;
+        let relative = output.strip_prefix(output_dir).ok()?;
+        let mut url = String::new();
+        for component in relative.components() {
+            url.push('/');
+            url += &component.as_os_str().to_string_lossy();
+        }
+        if let Some(dir) = url.strip_suffix("/index.html") {
+            url.truncate(dir.len() + 1);
+        }
+
+        Some(url)
+    }
+
+    /// The output directory, then every extra output folder.
+    pub fn output_dirs(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.output_dir.as_path())
+            .chain(self.outputs.values().map(|output| output.path.as_path()))
+    }
+
+    /// Applies settings that override the ones in `webby.toml`.
+    pub fn apply(&mut self, overrides: &Overrides) {
+        if let Some(profile) = overrides.profile {
+            self.set_profile(profile);
+        }
+        if let Some(output_dir) = &overrides.output_dir {
+            self.set_output_dir(output_dir.clone());
+        }
+        self.dry_run |= overrides.dry_run;
+    }
+
+    /// Changes the output directory, moving every target's output into the
+    /// new directory.
+    pub fn set_output_dir(&mut self, output_dir: PathBuf) {
+        for target in &mut self.targets {
+            if let Ok(relative) = target.output.strip_prefix(&self.output_dir) {
+                target.output = output_dir.join(relative);
+            }
+        }
+        self.output_dir = output_dir;
+    }
+
+    /// Deletes the output directory and every extra output folder. Returns
+    /// the folders that were deleted.
+    ///
+    /// To avoid deleting anything important because of a typo in
+    /// `webby.toml`, this refuses to delete an output folder if it contains the
+    /// project root or any of the targets' source files.
+    pub fn clean(&self) -> Result<Vec<PathBuf>, WebbyError> {
+        let canonicalize = |path: &Path| {
+            path.canonicalize().map_err(|err| {
+                WebbyError::new(ErrorKind::Io, "Failed to resolve path")
+                    .in_file(path)
+                    .caused_by(err)
+            })
+        };
+        // A config path like `webby.toml` has an empty parent folder
+        let root = if self.root.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &self.root
+        };
+        let root = canonicalize(root)?;
+        let protected: Vec<PathBuf> = std::iter::once(root)
+            .chain(
+                self.targets
+                    .iter()
+                    .filter_map(|target| target.path.canonicalize().ok()),
+            )
+            .collect();
+
+        let mut output_dirs = Vec::new();
+        for output_dir in self.output_dirs().filter(|output_dir| output_dir.exists()) {
+            let output_dir = canonicalize(output_dir)?;
+            if let Some(path) = protected.iter().find(|path| path.starts_with(&output_dir)) {
+                return Err(WebbyError::new(
+                    ErrorKind::Config,
+                    format!("Refusing to delete the output directory {output_dir:?}, because it contains {path:?}"),
+                )
+                .in_file(&self.path));
+            }
+            output_dirs.push(output_dir);
+        }
+
+        let mut deleted = Vec::new();
+        for output_dir in output_dirs {
+            // An output inside another one was already deleted with it
+            if !output_dir.exists() {
+                continue;
+            }
+            fs::remove_dir_all(&output_dir).map_err(|err| {
+                WebbyError::new(ErrorKind::Io, "Failed to delete the output directory")
+                    .in_file(&output_dir)
+                    .caused_by(err)
+            })?;
+            deleted.push(output_dir);
+        }
+
+        Ok(deleted)
+    }
+}
+
+/// Reads a table of variables, like `[vars]`. `name` describes the table for
+/// error messages.
+fn parse_vars(
+    table: &Table,
+    name: &str,
+    path: &Path,
+) -> Result<BTreeMap<String, Value>, WebbyError> {
+    table
+        .iter()
+        .map(|(key, value)| match Value::from_toml(value) {
+            Some(value) => Ok((key.to_string(), value)),
+            None => Err(WebbyError::new(
+                ErrorKind::Config,
+                format!("`{key}` in {name} is a date or time, which webby can't read yet; put it in quotes to use it as a string"),
+            )
+            .in_file(path)),
+        })
+        .collect()
+}
+
+/// Parses a `[gemtext]` table. Options it doesn't set are kept from `base`.
+fn parse_gemtext_options(
+    table: &Table,
+    base: &GemtextOptions,
+    name: &str,
+    path: &Path,
+) -> Result<GemtextOptions, WebbyError> {
+    let config_error = |message: String| WebbyError::new(ErrorKind::Config, message).in_file(path);
+    let mut options = base.clone();
+
+    for (key, value) in table.iter() {
+        let string = || {
+            value
+                .string()
+                .map(str::to_string)
+                .ok_or_else(|| config_error(format!("`{key}` in {name} has to be a string")))
+        };
+        match key {
+            "wrapper" => options.wrapper = Some(string()?),
+            "gemini_proxy" => options.gemini_proxy = Some(string()?),
+            "inline_images" | "rewrite_gmi_links" => {
+                let Some(value) = value.boolean() else {
+                    return Err(config_error(format!(
+                        "`{key}` in {name} has to be true or false"
+                    )));
+                };
+                match key {
+                    "inline_images" => options.inline_images = value,
+                    _ => options.rewrite_gmi_links = value,
+                }
+            }
+            "classes" => {
+                let Some(classes) = value.table() else {
+                    return Err(config_error(format!(
+                        "`classes` in {name} has to be a table"
+                    )));
+                };
+                for (line_type, class) in classes.iter() {
+                    let classes = &mut options.classes;
+                    let field = match line_type {
+                        "text" => &mut classes.text,
+                        "link" => &mut classes.link,
+                        "image" => &mut classes.image,
+                        "h1" => &mut classes.h1,
+                        "h2" => &mut classes.h2,
+                        "h3" => &mut classes.h3,
+                        "list" => &mut classes.list,
+                        "quote" => &mut classes.quote,
+                        "preformatted" => &mut classes.preformatted,
+                        "wrapper" => &mut classes.wrapper,
+                        other => {
+                            return Err(config_error(format!(
+                                "Unknown line type `{other}` in the classes in {name}; it can be text, link, image, h1, h2, h3, list, quote, preformatted, or wrapper"
+                            )))
+                        }
+                    };
+                    let Some(class) = class.string() else {
+                        return Err(config_error(format!(
+                            "The class for `{line_type}` in {name} has to be a string"
+                        )));
+                    };
+                    *field = Some(class.to_string());
+                }
+            }
+            other => {
+                return Err(config_error(format!("Unknown option `{other}` in {name}")));
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+/// Parses a `[headings]` table. Options it doesn't set are kept from `base`.
+/// `permalink = false` turns off permalinks set by `base`.
+fn parse_heading_options(
+    table: &Table,
+    base: &HeadingOptions,
+    name: &str,
+    path: &Path,
+) -> Result<HeadingOptions, WebbyError> {
+    let config_error = |message: String| WebbyError::new(ErrorKind::Config, message).in_file(path);
+    let mut options = base.clone();
+
+    for (key, value) in table.iter() {
+        match key {
+            "ids" => {
+                let Some(ids) = value.boolean() else {
+                    return Err(config_error(format!(
+                        "`ids` in {name} has to be true or false"
+                    )));
+                };
+                options.ids = ids;
+            }
+            "permalink" => {
+                options.permalink = match (value.string(), value.boolean()) {
+                    (Some(permalink), _) => Some(permalink.to_string()),
+                    (_, Some(false)) => None,
+                    _ => {
+                        return Err(config_error(format!(
+                            "`permalink` in {name} has to be a string, like \"#\", or false"
+                        )))
+                    }
+                };
+            }
+            other => {
+                return Err(config_error(format!("Unknown option `{other}` in {name}")));
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+/// Parses a `[markdown]` table. Options it doesn't set are kept from `base`.
+///
+/// `gfm = false` and `math = true` turn groups of constructs off or on at once,
+/// and are applied before the other keys, so single constructs can still be
+/// changed after them.
+fn parse_markdown_options(
+    table: &Table,
+    base: &MarkdownOptions,
+    name: &str,
+    path: &Path,
+) -> Result<MarkdownOptions, WebbyError> {
+    let config_error = |message: String| WebbyError::new(ErrorKind::Config, message).in_file(path);
+    let mut options = base.clone();
+
+    let get_bool = |key: &str| match table.get_boolean(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(TomlGetError::InvalidKey) => Ok(None),
+        Err(TomlGetError::TypeMismatch(_, _)) => Err(config_error(format!(
+            "`{key}` in {name} has to be true or false"
+        ))),
+    };
+    if let Some(gfm) = get_bool("gfm")? {
+        let constructs = &mut options.constructs;
+        constructs.gfm_autolink_literal = gfm;
+        constructs.gfm_footnote_definition = gfm;
+        constructs.gfm_label_start_footnote = gfm;
+        constructs.gfm_strikethrough = gfm;
+        constructs.gfm_table = gfm;
+        constructs.gfm_task_list_item = gfm;
+    }
+    if let Some(math) = get_bool("math")? {
+        options.constructs.math_flow = math;
+        options.constructs.math_text = math;
+        options.math_text_single_dollar = math;
+    }
+
+    for (key, value) in table.iter() {
+        let string = || {
+            value
+                .string()
+                .map(str::to_string)
+                .ok_or_else(|| config_error(format!("`{key}` in {name} has to be a string")))
+        };
+        let compile = &mut options.compile;
+        let flag = match key {
+            "gfm" | "math" => continue,
+            "gfm_footnote_label" => {
+                compile.gfm_footnote_label = Some(string()?);
+                continue;
+            }
+            "gfm_footnote_label_tag_name" => {
+                compile.gfm_footnote_label_tag_name = Some(string()?);
+                continue;
+            }
+            "gfm_footnote_label_attributes" => {
+                compile.gfm_footnote_label_attributes = Some(string()?);
+                continue;
+            }
+            "gfm_footnote_back_label" => {
+                compile.gfm_footnote_back_label = Some(string()?);
+                continue;
+            }
+            "gfm_footnote_clobber_prefix" => {
+                compile.gfm_footnote_clobber_prefix = Some(string()?);
+                continue;
+            }
+            "allow_dangerous_html" => &mut compile.allow_dangerous_html,
+            "allow_dangerous_protocol" => &mut compile.allow_dangerous_protocol,
+            "gfm_task_list_item_checkable" => &mut compile.gfm_task_list_item_checkable,
+            "gfm_tagfilter" => &mut compile.gfm_tagfilter,
+            "gfm_strikethrough_single_tilde" => &mut options.gfm_strikethrough_single_tilde,
+            "math_text_single_dollar" => &mut options.math_text_single_dollar,
+            key => match construct(&mut options.constructs, key) {
+                Some(flag) => flag,
+                None => return Err(config_error(format!("Unknown option `{key}` in {name}"))),
+            },
+        };
+        let Some(value) = value.boolean() else {
+            return Err(config_error(format!(
+                "`{key}` in {name} has to be true or false"
+            )));
+        };
+        *flag = value;
+    }
+
+    Ok(options)
+}
+
+/// Finds a Markdown construct by its name. MDX constructs aren't included,
+/// because they can't be translated to HTML.
+fn construct<'a>(constructs: &'a mut Constructs, name: &str) -> Option<&'a mut bool> {
+    Some(match name {
+        "attention" => &mut constructs.attention,
+        "autolink" => &mut constructs.autolink,
+        "block_quote" => &mut constructs.block_quote,
+        "character_escape" => &mut constructs.character_escape,
+        "character_reference" => &mut constructs.character_reference,
+        "code_indented" => &mut constructs.code_indented,
+        "code_fenced" => &mut constructs.code_fenced,
+        "code_text" => &mut constructs.code_text,
+        "definition" => &mut constructs.definition,
+        "frontmatter" => &mut constructs.frontmatter,
+        "gfm_autolink_literal" => &mut constructs.gfm_autolink_literal,
+        "gfm_label_start_footnote" => &mut constructs.gfm_label_start_footnote,
+        "gfm_footnote_definition" => &mut constructs.gfm_footnote_definition,
+        "gfm_strikethrough" => &mut constructs.gfm_strikethrough,
+        "gfm_table" => &mut constructs.gfm_table,
+        "gfm_task_list_item" => &mut constructs.gfm_task_list_item,
+        "hard_break_escape" => &mut constructs.hard_break_escape,
+        "hard_break_trailing" => &mut constructs.hard_break_trailing,
+        "heading_atx" => &mut constructs.heading_atx,
+        "heading_setext" => &mut constructs.heading_setext,
+        "html_flow" => &mut constructs.html_flow,
+        "html_text" => &mut constructs.html_text,
+        "label_start_image" => &mut constructs.label_start_image,
+        "label_start_link" => &mut constructs.label_start_link,
+        "label_end" => &mut constructs.label_end,
+        "list_item" => &mut constructs.list_item,
+        "math_flow" => &mut constructs.math_flow,
+        "math_text" => &mut constructs.math_text,
+        "thematic_break" => &mut constructs.thematic_break,
+        _ => return None,
+    })
+}
+
+fn parse_file_type(name: &str) -> Option<FileType> {
+    match name {
+        "html" => Some(FileType::Html),
+        "css" => Some(FileType::Css),
+        "js" | "javascript" => Some(FileType::Js),
+        "gmi" | "gemtext" => Some(FileType::Gemtext),
+        "markdown" | "md" => Some(FileType::Markdown),
+        _ => None,
+    }
+}
+
+fn parse_output_format(name: &str) -> Option<OutputFormat> {
+    match name {
+        "html" => Some(OutputFormat::Html),
+        "gemtext" => Some(OutputFormat::Gemtext),
+        _ => None,
+    }
+}
@@ -0,0 +1,196 @@
+//! Dates from front matter, formatted the ways feeds and sitemaps need them.
+//!
+//! webby's TOML parser can't read TOML dates, so dates are written as strings
+//! like `"2024-06-01"` or `"2024-06-01T09:30:00+02:00"`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A date and time, with a UTC offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// The offset from UTC, in minutes.
+    pub offset: i32,
+}
+impl Date {
+    /// Parses a date like `2024-06-01`, `2024-06-01T09:30`, or
+    /// `2024-06-01T09:30:00Z`, with an optional `Z` or `+HH:MM` offset. Dates
+    /// without a time are at midnight UTC.
+    pub fn parse(src: &str) -> Option<Self> {
+        let src = src.trim();
+        let (date, time) = match src.find(['T', 't', ' ']) {
+            Some(idx) => (&src[..idx], Some(&src[idx + 1..])),
+            None => (src, None),
+        };
+
+        let mut parts = date.splitn(3, '-');
+        let year = number(parts.next()?, 4)?;
+        let month = number(parts.next()?, 2)? as u32;
+        let day = number(parts.next()?, 2)? as u32;
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        let mut this = Self {
+            year,
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            offset: 0,
+        };
+        let Some(time) = time else {
+            return Some(this);
+        };
+
+        let (time, offset) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+            (time, 0)
+        } else if let Some(idx) = time.rfind(['+', '-']) {
+            let (hours, minutes) = time[idx + 1..].split_once(':')?;
+            let offset = number(hours, 2)? * 60 + number(minutes, 2)?;
+            let sign = if time.as_bytes()[idx] == b'-' { -1 } else { 1 };
+            (&time[..idx], sign * offset as i32)
+        } else {
+            (time, 0)
+        };
+        // Fractions of a second are ignored
+        let time = time.split_once('.').map_or(time, |(time, _)| time);
+        let mut parts = time.splitn(3, ':');
+        this.hour = number(parts.next()?, 2)? as u32;
+        this.minute = number(parts.next()?, 2)? as u32;
+        this.second = parts.next().map_or(Some(0), |second| number(second, 2))? as u32;
+        this.offset = offset;
+        if this.hour > 23 || this.minute > 59 || this.second > 60 || offset.abs() >= 24 * 60 {
+            return None;
+        }
+
+        Some(this)
+    }
+
+    /// The date of a point in time, in UTC.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let seconds = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(err) => -(err.duration().as_secs() as i64),
+        };
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let seconds = seconds.rem_euclid(86400) as u32;
+
+        Self {
+            year,
+            month,
+            day,
+            hour: seconds / 3600,
+            minute: seconds / 60 % 60,
+            second: seconds % 60,
+            offset: 0,
+        }
+    }
+
+    /// Seconds since the Unix epoch, for comparing dates in different time
+    /// zones.
+    pub fn timestamp(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * 86400
+            + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+            - self.offset as i64 * 60
+    }
+
+    /// Formats the date for Atom feeds and sitemaps, like
+    /// `2024-06-01T09:30:00Z`.
+    pub fn to_rfc3339(&self) -> String {
+        let offset = if self.offset == 0 {
+            "Z".to_string()
+        } else {
+            let sign = if self.offset < 0 { '-' } else { '+' };
+            let offset = self.offset.abs();
+            format!("{sign}{:02}:{:02}", offset / 60, offset % 60)
+        };
+
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{offset}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+
+    /// Formats the date for RSS feeds, like `Sat, 01 Jun 2024 09:30:00 +0000`.
+    pub fn to_rfc822(&self) -> String {
+        let weekday = (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7);
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.abs();
+
+        format!(
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} {sign}{:02}{:02}",
+            WEEKDAYS[weekday as usize],
+            self.day,
+            MONTHS[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second,
+            offset / 60,
+            offset % 60
+        )
+    }
+}
+
+/// Parses a number with exactly `digits` digits.
+fn number(src: &str, digits: usize) -> Option<i64> {
+    if src.len() != digits || !src.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    src.parse().ok()
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days between 1970-01-01 and a date. This is Howard Hinnant's
+/// `days_from_civil` algorithm.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date a number of days after 1970-01-01. The inverse of
+/// [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
@@ -0,0 +1,143 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+/// The type of a [`WebbyError`]. Tools wrapping webby should match on this
+/// instead of on the error's message, which may change at any time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Reading, writing, copying, or linking a file failed.
+    Io,
+    /// A macro invocation was malformed - for example, it was missing a
+    /// parenthesis or had the wrong number of arguments.
+    MacroSyntax,
+    /// A macro invocation used a macro name webby doesn't know.
+    UnknownMacro,
+    /// The HTML minifier found HTML it couldn't parse.
+    HtmlSyntax,
+    /// The Gemtext translator found Gemtext it couldn't parse.
+    GemtextSyntax,
+    /// `webby.toml` was missing or invalid.
+    Config,
+    /// A file's front matter was invalid, or a macro used a front matter key
+    /// the file doesn't have.
+    FrontMatter,
+    /// A `#!VAR` macro used a variable that isn't defined.
+    UnknownVariable,
+}
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Io => "I/O error",
+            Self::MacroSyntax => "macro syntax error",
+            Self::UnknownMacro => "unknown macro",
+            Self::HtmlSyntax => "HTML syntax error",
+            Self::GemtextSyntax => "Gemtext syntax error",
+            Self::Config => "config error",
+            Self::FrontMatter => "front matter error",
+            Self::UnknownVariable => "unknown variable",
+        })
+    }
+}
+
+/// A 1-based line and column in a source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+impl Position {
+    /// Finds the line and column of a byte offset in `src`. Columns are
+    /// counted in characters, not bytes.
+    pub fn of_offset(src: &str, offset: usize) -> Self {
+        let mut offset = offset.min(src.len());
+        while !src.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &src[..offset];
+        let line_start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+
+        Self {
+            line: before.bytes().filter(|byte| *byte == b'\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// An error that occurred while building a webby project.
+///
+/// Errors store where they happened (if known) and, optionally, the error that
+/// caused them. For example, an error in a file embedded with `#!INCLUDE` will
+/// be reported at the `#!INCLUDE`, with the error in the included file as its
+/// cause. The cause chain can be walked with [`Error::source`].
+#[derive(Debug)]
+pub struct WebbyError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// The file the error occurred in.
+    pub path: Option<PathBuf>,
+    /// Where in [`WebbyError::path`] the error occurred.
+    pub position: Option<Position>,
+    pub cause: Option<Box<dyn Error + Send + Sync + 'static>>,
+}
+impl WebbyError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            path: None,
+            position: None,
+            cause: None,
+        }
+    }
+
+    /// Sets the file this error occurred in.
+    pub fn in_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the file this error occurred in, and the position in that file,
+    /// from a byte offset into that file's source.
+    pub fn at(mut self, path: impl AsRef<Path>, src: &str, offset: usize) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self.position = Some(Position::of_offset(src, offset));
+        self
+    }
+
+    /// Sets the file this error occurred in and the position in that file.
+    pub fn at_position(mut self, path: impl AsRef<Path>, position: Position) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self.position = Some(position);
+        self
+    }
+
+    /// Sets the error that caused this error.
+    pub fn caused_by(mut self, cause: impl Into<Box<dyn Error + Send + Sync + 'static>>) -> Self {
+        self.cause = Some(cause.into());
+        self
+    }
+}
+impl Display for WebbyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}", path.display())?;
+            if let Some(Position { line, column }) = self.position {
+                write!(f, ":{line}:{column}")?;
+            }
+            f.write_str(": ")?;
+        }
+
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+impl Error for WebbyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.cause
+            .as_deref()
+            .map(|cause| cause as &(dyn Error + 'static))
+    }
+}
@@ -0,0 +1,257 @@
+//! Atom and RSS feeds of a collection of posts.
+//!
+//! A feed is declared with a `[[feed]]` table in `webby.toml`, and is built
+//! like any other target: its `path` is a glob of Markdown or Gemtext posts,
+//! and it's written to its `atom` and/or `rss` output files. Each post's
+//! title, date and summary come from its front matter, and its body is
+//! compiled and translated to HTML the same way it is for the site itself.
+
+use {
+    crate::{
+        collection::{self, Item, Sort},
+        date::Date,
+        Config, Context, ErrorKind, FileType, Target, Value, WebbyError,
+    },
+    std::{fmt::Write, fs},
+};
+
+/// Which kind of feed to write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedFormat {
+    /// An Atom 1.0 feed.
+    Atom,
+    /// An RSS 2.0 feed.
+    Rss,
+}
+
+/// The settings for a feed, from a `[[feed]]` table in `webby.toml`.
+#[derive(Clone, Debug)]
+pub struct Feed {
+    pub format: FeedFormat,
+    pub title: String,
+    pub description: Option<String>,
+    /// The feed's author. Posts can override this with `author` in their
+    /// front matter. Atom feeds need an author, so they use the title if
+    /// this isn't set.
+    pub author: Option<String>,
+    /// The URL the site is published at, without a trailing `/`. Every link
+    /// in the feed is made absolute with this.
+    pub base_url: String,
+    /// The most posts the feed lists. The newest posts are kept.
+    pub limit: Option<usize>,
+}
+
+/// A post, ready to be written to a feed.
+struct Entry {
+    title: String,
+    url: String,
+    date: Date,
+    author: Option<String>,
+    summary: Option<String>,
+    content: String,
+}
+
+/// Builds the feed for `target`, returning the feed's XML.
+pub fn render(
+    config: &Config,
+    target: &Target,
+    feed: &Feed,
+    ctx: &mut Context,
+) -> Result<String, WebbyError> {
+    let mut items = collection::collect_in(&target.path, target.pattern.as_ref(), Sort::Date, ctx)?;
+    items.truncate(feed.limit.unwrap_or(items.len()));
+
+    let entries = items
+        .into_iter()
+        .map(|item| entry(feed, item, ctx))
+        .collect::<Result<Vec<_>, _>>()?;
+    let self_url = config
+        .url_of_output(&target.output)
+        .map(|url| format!("{}{url}", feed.base_url))
+        .unwrap_or_default();
+    let updated = entries
+        .iter()
+        .map(|entry| entry.date)
+        .max_by_key(Date::timestamp);
+
+    Ok(match feed.format {
+        FeedFormat::Atom => atom(feed, &self_url, updated, &entries),
+        FeedFormat::Rss => rss(feed, &self_url, updated, &entries),
+    })
+}
+
+/// Reads and compiles a post.
+fn entry(feed: &Feed, item: Item, ctx: &mut Context) -> Result<Entry, WebbyError> {
+    let Some(url) = &item.url else {
+        return Err(WebbyError::new(
+            ErrorKind::Config,
+            "This post is in a feed, but no target builds it, so it doesn't have a URL to link to",
+        )
+        .in_file(&item.path));
+    };
+    let date = match item.date() {
+        Some(date) => Date::parse(date).ok_or_else(|| {
+            WebbyError::new(
+                ErrorKind::FrontMatter,
+                format!("The post's date `{date}` isn't a valid date; write it like \"2024-06-01\" or \"2024-06-01T09:30:00Z\""),
+            )
+            .in_file(&item.path)
+        })?,
+        // Undated posts fall back to when they were last changed
+        None => fs::metadata(&item.path)
+            .and_then(|metadata| metadata.modified())
+            .map(Date::from_system_time)
+            .map_err(|err| {
+                WebbyError::new(ErrorKind::Io, "Failed to read the post's modification time")
+                    .in_file(&item.path)
+                    .caused_by(err)
+            })?,
+    };
+
+    let source = ctx.read_to_string(&item.path).map_err(|err| {
+        WebbyError::new(ErrorKind::Io, "Failed to read the post")
+            .in_file(&item.path)
+            .caused_by(err)
+    })?;
+    let (content, _) = crate::compile_file(
+        &source,
+        &item.path,
+        FileType::from(item.path.as_path()),
+        ctx,
+    )?;
+
+    Ok(Entry {
+        title: item.title(),
+        url: format!("{}{url}", feed.base_url),
+        date,
+        author: match item.front_matter.get("author") {
+            Some(Value::Table(author)) => author.get("name").map(ToString::to_string),
+            Some(author) => Some(author.to_string()),
+            None => None,
+        },
+        summary: item.front_matter.get("summary").map(ToString::to_string),
+        content: content.into_owned(),
+    })
+}
+
+fn atom(feed: &Feed, self_url: &str, updated: Option<Date>, entries: &[Entry]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\"",
+    );
+    writeln!(out, " xml:base=\"{}/\">", escape(&feed.base_url)).unwrap();
+    writeln!(out, "  <title>{}</title>", escape(&feed.title)).unwrap();
+    if let Some(description) = &feed.description {
+        writeln!(out, "  <subtitle>{}</subtitle>", escape(description)).unwrap();
+    }
+    writeln!(out, "  <link href=\"{}/\"/>", escape(&feed.base_url)).unwrap();
+    writeln!(out, "  <link rel=\"self\" href=\"{}\"/>", escape(self_url)).unwrap();
+    writeln!(out, "  <id>{}</id>", escape(self_url)).unwrap();
+    writeln!(
+        out,
+        "  <updated>{}</updated>",
+        updated.map_or("1970-01-01T00:00:00Z".into(), |date| date.to_rfc3339())
+    )
+    .unwrap();
+    // Every entry needs an author, and entries without their own use this one
+    writeln!(
+        out,
+        "  <author><name>{}</name></author>",
+        escape(feed.author.as_ref().unwrap_or(&feed.title))
+    )
+    .unwrap();
+
+    for entry in entries {
+        out += "  <entry>\n";
+        writeln!(out, "    <title>{}</title>", escape(&entry.title)).unwrap();
+        writeln!(out, "    <link href=\"{}\"/>", escape(&entry.url)).unwrap();
+        writeln!(out, "    <id>{}</id>", escape(&entry.url)).unwrap();
+        writeln!(out, "    <updated>{}</updated>", entry.date.to_rfc3339()).unwrap();
+        if let Some(author) = &entry.author {
+            writeln!(out, "    <author><name>{}</name></author>", escape(author)).unwrap();
+        }
+        if let Some(summary) = &entry.summary {
+            writeln!(out, "    <summary>{}</summary>", escape(summary)).unwrap();
+        }
+        // Relative links in the post are relative to its page
+        writeln!(
+            out,
+            "    <content type=\"html\" xml:base=\"{}\">{}</content>",
+            escape(&entry.url),
+            escape(&entry.content)
+        )
+        .unwrap();
+        out += "  </entry>\n";
+    }
+
+    out += "</feed>\n";
+    out
+}
+
+fn rss(feed: &Feed, self_url: &str, updated: Option<Date>, entries: &[Entry]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n",
+    );
+    writeln!(out, "  <title>{}</title>", escape(&feed.title)).unwrap();
+    writeln!(out, "  <link>{}/</link>", escape(&feed.base_url)).unwrap();
+    writeln!(
+        out,
+        "  <description>{}</description>",
+        escape(feed.description.as_ref().unwrap_or(&feed.title))
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>",
+        escape(self_url)
+    )
+    .unwrap();
+    if let Some(updated) = updated {
+        writeln!(
+            out,
+            "  <lastBuildDate>{}</lastBuildDate>",
+            updated.to_rfc822()
+        )
+        .unwrap();
+    }
+
+    for entry in entries {
+        out += "  <item>\n";
+        writeln!(out, "    <title>{}</title>", escape(&entry.title)).unwrap();
+        writeln!(out, "    <link>{}</link>", escape(&entry.url)).unwrap();
+        writeln!(
+            out,
+            "    <guid isPermaLink=\"true\">{}</guid>",
+            escape(&entry.url)
+        )
+        .unwrap();
+        writeln!(out, "    <pubDate>{}</pubDate>", entry.date.to_rfc822()).unwrap();
+        // RSS only has one field for the post, so it's the summary if there
+        // is one and the whole post otherwise
+        writeln!(
+            out,
+            "    <description>{}</description>",
+            escape(entry.summary.as_ref().unwrap_or(&entry.content))
+        )
+        .unwrap();
+        out += "  </item>\n";
+    }
+
+    out += "</channel>\n</rss>\n";
+    out
+}
+
+/// Escapes text for XML.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&apos;",
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
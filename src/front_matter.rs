@@ -0,0 +1,287 @@
+//! TOML front matter for Markdown and Gemtext files.
+//!
+//! Front matter is a block of TOML at the very top of a file, between two
+//! `+++` lines:
+//!
+//! ```text
+//! +++
+//! title = "My first post"
+//! date = "2024-06-01"
+//! tags = ["rust", "web"]
+//! +++
+//!
+//! # My first post
+//! ```
+//!
+//! The block is removed before the file is translated, and its values can be
+//! used with the `#!META(key)` macro in the file, anything it includes, and
+//! its layout.
+
+use {
+    crate::{ErrorKind, WebbyError},
+    boml::{types::TomlValue, Toml},
+    std::{
+        collections::BTreeMap,
+        fmt::{self, Display},
+        ops::Range,
+        path::Path,
+    },
+};
+
+/// A value from front matter or `webby.toml`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(BTreeMap<String, Value>),
+}
+impl Value {
+    /// Converts a TOML value. Dates and times aren't supported by webby's TOML
+    /// parser, so they return `None`; write them as strings instead.
+    pub fn from_toml(value: &TomlValue) -> Option<Self> {
+        Some(match value {
+            TomlValue::String(string) => Self::String(string.as_str().to_string()),
+            TomlValue::Integer(int) => Self::Integer(*int),
+            TomlValue::Float(float) => Self::Float(*float),
+            TomlValue::Boolean(bool) => Self::Boolean(*bool),
+            TomlValue::Array(array) => {
+                Self::Array(array.iter().map(Self::from_toml).collect::<Option<_>>()?)
+            }
+            TomlValue::Table(table) => Self::Table(
+                table
+                    .iter()
+                    .map(|(key, value)| Some((key.to_string(), Self::from_toml(value)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            TomlValue::OffsetDateTime
+            | TomlValue::LocalDateTime
+            | TomlValue::LocalDate
+            | TomlValue::LocalTime => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Boolean(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+}
+impl Display for Value {
+    /// Values are displayed the way they're inserted into pages: strings
+    /// without quotes, and arrays as comma-separated lists.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(string) => f.write_str(string),
+            Self::Integer(int) => write!(f, "{int}"),
+            Self::Float(float) => write!(f, "{float}"),
+            Self::Boolean(bool) => write!(f, "{bool}"),
+            Self::Array(array) => {
+                for (idx, value) in array.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                Ok(())
+            }
+            Self::Table(table) => {
+                for (idx, (key, value)) in table.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{key} = {value}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The parsed front matter of a file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrontMatter {
+    pub values: BTreeMap<String, Value>,
+}
+impl FrontMatter {
+    /// Looks up a value. Keys can be dotted to look inside tables, like
+    /// `author.name`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        lookup(&self.values, key)
+    }
+
+    /// The page's title, if it has one.
+    pub fn title(&self) -> Option<&str> {
+        self.get("title").and_then(Value::as_str)
+    }
+
+    /// If the page is a draft. Drafts aren't built unless `drafts = true` is
+    /// set in `webby.toml`.
+    pub fn is_draft(&self) -> bool {
+        self.get("draft").and_then(Value::as_bool).unwrap_or(false)
+    }
+}
+
+/// Looks up a dotted key, like `author.name`, in a table of values.
+pub fn lookup<'a>(values: &'a BTreeMap<String, Value>, key: &str) -> Option<&'a Value> {
+    let mut parts = key.split('.');
+    let mut value = values.get(parts.next()?.trim())?;
+    for part in parts {
+        let Value::Table(table) = value else {
+            return None;
+        };
+        value = table.get(part.trim())?;
+    }
+
+    Some(value)
+}
+
+/// Splits the front matter off the top of a file. Returns the parsed front
+/// matter (if there is any) and the byte range of the rest of the file.
+pub fn split(
+    source: &str,
+    source_path: &Path,
+) -> Result<(Option<FrontMatter>, Range<usize>), WebbyError> {
+    let Some(toml_start) = strip_delimiter(source) else {
+        return Ok((None, 0..source.len()));
+    };
+
+    let mut line_start = toml_start;
+    let (toml_end, body_start) = loop {
+        let Some(line_len) = source[line_start..].find('\n') else {
+            if source[line_start..].trim_end() == "+++" {
+                break (line_start, source.len());
+            }
+            return Err(WebbyError::new(
+                ErrorKind::FrontMatter,
+                "Front matter started with `+++` but was never closed",
+            )
+            .at(source_path, source, 0));
+        };
+        let line_end = line_start + line_len + 1;
+        if source[line_start..line_end].trim_end() == "+++" {
+            break (line_start, line_end);
+        }
+        line_start = line_end;
+    };
+
+    let toml_src = &source[toml_start..toml_end];
+    if let Some(offset) = find_bare_datetime(toml_src) {
+        return Err(WebbyError::new(
+            ErrorKind::FrontMatter,
+            "Front matter has a date or time that isn't in quotes, which webby can't read yet; put it in quotes to use it as a string, like `date = \"2024-06-01\"`",
+        )
+        .at(source_path, source, toml_start + offset));
+    }
+    let toml = Toml::parse(toml_src).map_err(|err| {
+        WebbyError::new(
+            ErrorKind::FrontMatter,
+            format!("Failed to parse front matter: {:?}", err.kind),
+        )
+        .at(source_path, source, toml_start + err.start)
+    })?;
+
+    let mut values = BTreeMap::new();
+    for (key, value) in toml.iter() {
+        let Some(value) = Value::from_toml(value) else {
+            return Err(WebbyError::new(
+                ErrorKind::FrontMatter,
+                format!("Front matter value `{key}` is a date or time, which webby can't read yet; put it in quotes to use it as a string"),
+            )
+            .in_file(source_path));
+        };
+        values.insert(key.to_string(), value);
+    }
+
+    Ok((Some(FrontMatter { values }), body_start..source.len()))
+}
+
+/// If `source` starts with a `+++` line, returns the index after it.
+fn strip_delimiter(source: &str) -> Option<usize> {
+    let line_end = source.find('\n')?;
+    (source[..line_end].trim_end() == "+++").then_some(line_end + 1)
+}
+
+/// Finds a date or time that isn't in quotes in some TOML, like
+/// `date = 2024-06-01`, returning where it starts. webby's TOML parser can't
+/// read these yet, and panics on them, so they have to be caught first.
+pub(crate) fn find_bare_datetime(toml: &str) -> Option<usize> {
+    let bytes = toml.as_bytes();
+    // If a value starts next, like after `=`, or `,` in an array
+    let mut expect_value = false;
+    // How many arrays deep the value is
+    let mut arrays = 0usize;
+    let mut idx = 0;
+    while let Some(&byte) = bytes.get(idx) {
+        match byte {
+            b'#' => {
+                idx = toml[idx..].find('\n').map_or(toml.len(), |end| idx + end);
+                continue;
+            }
+            b'"' | b'\'' => {
+                idx = string_end(toml, idx);
+                expect_value = false;
+                continue;
+            }
+            b'=' => expect_value = true,
+            b'[' if expect_value => arrays += 1,
+            b',' if arrays > 0 => expect_value = true,
+            b']' if arrays > 0 => arrays -= 1,
+            b'\n' if arrays == 0 => expect_value = false,
+            byte if byte.is_ascii_whitespace() => {}
+            b'0'..=b'9' if expect_value => {
+                let rest = &bytes[idx..];
+                let digits = |range: std::ops::Range<usize>| {
+                    rest.get(range)
+                        .is_some_and(|digits| digits.iter().all(u8::is_ascii_digit))
+                };
+                let date = digits(0..4) && rest.get(4) == Some(&b'-') && digits(5..7);
+                let time = digits(0..2) && rest.get(2) == Some(&b':') && digits(3..5);
+                if date || time {
+                    return Some(idx);
+                }
+                expect_value = false;
+            }
+            _ => expect_value = false,
+        }
+        idx += 1;
+    }
+
+    None
+}
+
+/// Finds the end of the TOML string starting at `start`, which may be a
+/// multi-line string.
+fn string_end(toml: &str, start: usize) -> usize {
+    let quote = &toml[start..start + 1];
+    let triple = quote.repeat(3);
+    if toml[start..].starts_with(&triple) {
+        let body = start + 3;
+        return toml[body..]
+            .find(&triple)
+            .map_or(toml.len(), |end| body + end + 3);
+    }
+
+    let bytes = toml.as_bytes();
+    let mut idx = start + 1;
+    while let Some(&byte) = bytes.get(idx) {
+        idx += 1;
+        match byte {
+            b'\\' if quote == "\"" => idx += 1,
+            b'\n' => break,
+            byte if byte == bytes[start] => break,
+            _ => {}
+        }
+    }
+    idx.min(toml.len())
+}
@@ -0,0 +1,289 @@
+//! Glob patterns for picking which files a target builds.
+//!
+//! Patterns are matched against paths relative to a target's folder, one path
+//! component at a time:
+//!
+//! - `*` matches any number of characters in a file or folder name
+//! - `?` matches a single character
+//! - `[abc]` matches one of the listed characters, `[a-z]` a range of them,
+//!   and `[!abc]` any character that isn't listed
+//! - `{md,gmi}` matches any of the comma-separated alternatives
+//! - `**` on its own matches any number of folders, including none
+
+use {
+    crate::{ErrorKind, WebbyError},
+    std::path::{Component, Path},
+};
+
+/// Returns true if `path` contains any glob syntax.
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '[', '{'])
+}
+
+/// Splits a path that may contain a glob into the folder before the first
+/// component with glob syntax in it, and a pattern for the rest. If the path
+/// doesn't contain a glob, it's returned as-is with no pattern.
+pub fn split(path: &str) -> Result<(&str, Option<Pattern>), WebbyError> {
+    if !is_glob(path) {
+        return Ok((path, None));
+    }
+
+    let split = path
+        .split('/')
+        .position(is_glob)
+        .expect("`is_glob` means a component has glob syntax");
+    let base_len: usize = path.split('/').take(split).map(|part| part.len() + 1).sum();
+    let base = &path[..base_len.saturating_sub(1)];
+
+    Ok((base, Some(Pattern::new(&path[base_len..])?)))
+}
+
+/// Returns true for files that should never be built when walking a folder:
+/// dotfiles, and the backup and swap files editors leave behind.
+pub fn is_ignored(name: &str) -> bool {
+    name.starts_with('.')
+        || name.ends_with('~')
+        || (name.starts_with('#') && name.ends_with('#'))
+        || name.ends_with(".swp")
+        || name.ends_with(".swo")
+        || name.ends_with(".swx")
+        // Vim creates this file to check if it can write to a folder
+        || name == "4913"
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    /// `**`
+    AnyFolders,
+    /// A file or folder name, possibly with wildcards.
+    Name(String),
+}
+
+/// A parsed glob pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    /// One list of segments for every alternative in `{a,b}` groups.
+    alternatives: Vec<Vec<Segment>>,
+}
+impl Pattern {
+    /// Parses a pattern that has to match a whole relative path, like
+    /// `posts/**/*.md`.
+    pub fn new(pattern: &str) -> Result<Self, WebbyError> {
+        let alternatives = expand_braces(pattern)?
+            .into_iter()
+            .map(|pattern| {
+                let segments = pattern
+                    .split('/')
+                    .filter(|segment| !segment.is_empty() && *segment != ".")
+                    .map(|segment| {
+                        if segment == "**" {
+                            Ok(Segment::AnyFolders)
+                        } else {
+                            check_classes(segment)?;
+                            Ok(Segment::Name(segment.to_string()))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, WebbyError>>()?;
+                Ok(segments)
+            })
+            .collect::<Result<_, WebbyError>>()?;
+
+        Ok(Self { alternatives })
+    }
+
+    /// Parses a pattern from an `exclude` list. These work like `.gitignore`
+    /// entries: a pattern without a `/` (like `*.psd`) matches files and
+    /// folders with that name anywhere, while a pattern with one (like
+    /// `drafts/**`) is matched against the whole relative path.
+    pub fn exclude(pattern: &str) -> Result<Self, WebbyError> {
+        let pattern = pattern.trim_end_matches('/');
+        if pattern.contains('/') {
+            Self::new(pattern)
+        } else {
+            Self::new(&format!("**/{pattern}"))
+        }
+    }
+
+    /// Checks if a relative path matches the pattern.
+    pub fn matches(&self, path: &Path) -> bool {
+        let components: Option<Vec<&str>> = path
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .map(|component| match component {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+        let Some(components) = components else {
+            return false;
+        };
+
+        self.alternatives
+            .iter()
+            .any(|segments| match_segments(segments, &components))
+    }
+}
+
+fn match_segments(segments: &[Segment], components: &[&str]) -> bool {
+    match segments.split_first() {
+        None => components.is_empty(),
+        Some((Segment::AnyFolders, rest)) => {
+            (0..=components.len()).any(|skip| match_segments(rest, &components[skip..]))
+        }
+        Some((Segment::Name(pattern), rest)) => match components.split_first() {
+            Some((name, components)) => {
+                match_name(pattern.as_bytes(), name.as_bytes()) && match_segments(rest, components)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Matches a single file or folder name against a pattern with `*`, `?`, and
+/// `[...]` wildcards.
+fn match_name(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some((b'?', rest)) => {
+            let len = char_len(name);
+            len > 0 && match_name(rest, &name[len..])
+        }
+        Some((b'[', _)) => {
+            let Some(close) = class_end(pattern) else {
+                return false;
+            };
+            let len = char_len(name);
+            len > 0
+                && match_class(&pattern[1..close], &name[..len])
+                && match_name(&pattern[close + 1..], &name[len..])
+        }
+        Some((byte, rest)) => name.first() == Some(byte) && match_name(rest, &name[1..]),
+    }
+}
+
+/// Checks if a character is in a `[...]` class. `class` is the text between the
+/// brackets.
+fn match_class(class: &[u8], ch: &[u8]) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some((b'!' | b'^', rest)) => (true, rest),
+        _ => (false, class),
+    };
+    let (Ok(class), Ok(ch)) = (std::str::from_utf8(class), std::str::from_utf8(ch)) else {
+        return false;
+    };
+    let ch = ch.chars().next().unwrap();
+
+    let mut found = false;
+    let mut chars = class.chars();
+    while let Some(start) = chars.next() {
+        let mut range = chars.clone();
+        if range.next() == Some('-') {
+            if let Some(end) = range.next() {
+                chars = range;
+                found |= (start..=end).contains(&ch);
+                continue;
+            }
+        }
+        found |= start == ch;
+    }
+
+    found != negated
+}
+
+/// The length of the UTF-8 character at the start of `bytes`.
+fn char_len(bytes: &[u8]) -> usize {
+    match bytes.first() {
+        None => 0,
+        Some(byte) if *byte < 0x80 => 1,
+        Some(byte) if *byte >= 0xF0 => 4.min(bytes.len()),
+        Some(byte) if *byte >= 0xE0 => 3.min(bytes.len()),
+        Some(_) => 2.min(bytes.len()),
+    }
+}
+
+/// Finds the `]` closing a `[` at the start of `pattern`. A `]` right after the
+/// `[` (or `[!`) is part of the class, not the end of it.
+fn class_end(pattern: &[u8]) -> Option<usize> {
+    let mut idx = 1;
+    if matches!(pattern.get(idx), Some(b'!' | b'^')) {
+        idx += 1;
+    }
+    idx += 1;
+    pattern
+        .get(idx..)?
+        .iter()
+        .position(|byte| *byte == b']')
+        .map(|offset| idx + offset)
+}
+
+fn check_classes(segment: &str) -> Result<(), WebbyError> {
+    let bytes = segment.as_bytes();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'[' {
+            let Some(close) = class_end(&bytes[idx..]) else {
+                return Err(pattern_error(format!(
+                    "Unclosed `[` in glob pattern `{segment}`"
+                )));
+            };
+            idx += close;
+        }
+        idx += 1;
+    }
+
+    Ok(())
+}
+
+/// Expands `{a,b}` groups into every pattern they could form.
+fn expand_braces(pattern: &str) -> Result<Vec<String>, WebbyError> {
+    let Some(open) = pattern.find('{') else {
+        if pattern.contains('}') {
+            return Err(pattern_error(format!(
+                "Unopened `}}` in glob pattern `{pattern}`"
+            )));
+        }
+        return Ok(vec![pattern.to_string()]);
+    };
+
+    let mut depth = 0;
+    let mut close = None;
+    let mut options = Vec::new();
+    let mut option_start = open + 1;
+    for (idx, char) in pattern[open..].char_indices() {
+        let idx = open + idx;
+        match char {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    options.push(&pattern[option_start..idx]);
+                    close = Some(idx);
+                    break;
+                }
+            }
+            ',' if depth == 1 => {
+                options.push(&pattern[option_start..idx]);
+                option_start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    let Some(close) = close else {
+        return Err(pattern_error(format!(
+            "Unclosed `{{` in glob pattern `{pattern}`"
+        )));
+    };
+
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+    let mut expanded = Vec::new();
+    for option in options {
+        expanded.extend(expand_braces(&format!("{prefix}{option}{suffix}"))?);
+    }
+
+    Ok(expanded)
+}
+
+fn pattern_error(message: String) -> WebbyError {
+    WebbyError::new(ErrorKind::Config, message)
+}
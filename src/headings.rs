@@ -0,0 +1,367 @@
+//! Heading ids, permalinks, and tables of contents.
+//!
+//! Headings in compiled HTML are given an `id` made from their text, like
+//! `getting-started`, so they can be linked to. `#!TOC()` can't list the
+//! headings when it's compiled, because the page hasn't been translated yet,
+//! so it leaves a [`toc_marker`] behind that's swapped for the table of
+//! contents once the page is finished.
+
+use {
+    crate::translator,
+    std::{collections::HashSet, fmt::Write},
+};
+
+/// Changes how headings are linked. By default, every heading is given an
+/// id, but no permalink.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeadingOptions {
+    /// Give every heading without an `id` one made from its text. Headings
+    /// still get ids with this off if the page has a table of contents, which
+    /// needs them.
+    pub ids: bool,
+    /// If set, every heading gets a link to itself, with this HTML as its
+    /// text, like `#`. This also turns on `ids`.
+    pub permalink: Option<String>,
+}
+impl Default for HeadingOptions {
+    fn default() -> Self {
+        Self {
+            ids: true,
+            permalink: None,
+        }
+    }
+}
+
+/// Marks where `#!TOC()` was used. These are Unicode private use characters,
+/// so translators pass them through untouched.
+const MARKER_START: char = '\u{E000}';
+const MARKER_END: char = '\u{E001}';
+
+/// What `#!TOC(depth)` expands to, until the page's headings are known.
+pub fn toc_marker(depth: usize) -> String {
+    format!("{MARKER_START}TOC:{depth}{MARKER_END}")
+}
+
+/// A heading found in some HTML.
+pub struct Heading {
+    /// 1 for `<h1>`, up to 6 for `<h6>`.
+    pub level: u8,
+    /// The heading's `id` attribute, if it has one.
+    pub id: Option<String>,
+    /// The heading's text, still HTML-escaped, without any tags or hidden
+    /// elements (like permalinks) inside it.
+    pub text: String,
+    /// Where the `>` of the opening tag is.
+    open_end: usize,
+    /// Where the closing tag starts.
+    close: usize,
+}
+
+/// Finds every `<h1>`-`<h6>` heading in some HTML.
+pub fn find(html: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = html[offset..].find("<h").map(|start| start + offset) {
+        offset = start + 2;
+        let bytes = &html.as_bytes()[offset..];
+        let (Some(level @ b'1'..=b'6'), Some(b'>' | b' ' | b'\t' | b'\n')) =
+            (bytes.first().copied(), bytes.get(1).copied())
+        else {
+            continue;
+        };
+        let level = level - b'0';
+        let Some(open_end) = html[offset..].find('>').map(|end| end + offset) else {
+            break;
+        };
+        let Some(close) = html[open_end..]
+            .find(&format!("</h{level}>"))
+            .map(|close| close + open_end)
+        else {
+            break;
+        };
+
+        headings.push(Heading {
+            level,
+            id: attribute(&html[offset + 1..open_end], "id").map(str::to_string),
+            text: inner_text(&html[open_end + 1..close]),
+            open_end,
+            close,
+        });
+        offset = close;
+    }
+
+    headings
+}
+
+/// Finds the value of an attribute in the inside of an opening tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(start) = rest.find(name) {
+        let before = rest[..start].chars().next_back();
+        let after = &rest[start + name.len()..];
+        rest = after;
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = after.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        return match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next(),
+            _ => value.split([' ', '\t', '\n', '>', '/']).next(),
+        };
+    }
+
+    None
+}
+
+/// Strips the tags from some HTML, along with anything inside an
+/// `aria-hidden="true"` element, and collapses its whitespace.
+fn inner_text(html: &str) -> String {
+    let mut text = String::new();
+    // How many elements deep into a hidden element the text is
+    let mut hidden = 0;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        if hidden == 0 {
+            text += &rest[..start];
+        }
+        let Some(end) = rest[start..].find('>').map(|end| end + start) else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..end];
+        if tag.starts_with('/') {
+            hidden = usize::saturating_sub(hidden, 1);
+        } else if !tag.ends_with('/')
+            && (hidden > 0 || attribute(tag, "aria-hidden") == Some("true"))
+        {
+            hidden += 1;
+        }
+        rest = &rest[end + 1..];
+    }
+    if hidden == 0 {
+        text += rest;
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Makes an `id` from a heading's text: lowercase letters and numbers, with
+/// dashes between words, like `getting-started`.
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for char in translator::decode_entities(text).chars() {
+        if char.is_alphanumeric() {
+            slug.extend(char.to_lowercase());
+        } else if (char.is_whitespace() || char == '-' || char == '_')
+            && !slug.is_empty()
+            && !slug.ends_with('-')
+        {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+
+    if slug.is_empty() {
+        "section".into()
+    } else {
+        slug.into()
+    }
+}
+
+/// Gives headings ids and permalinks, then fills in any tables of contents,
+/// following the options. Returns `None` if nothing needed to change.
+pub fn link_headings(html: &str, options: &HeadingOptions) -> Option<String> {
+    let has_toc = html.contains(MARKER_START);
+    if !options.ids && options.permalink.is_none() && !has_toc {
+        return None;
+    }
+
+    let headings = find(html);
+    // Ids that are already used anywhere in the page can't be reused
+    let mut used = HashSet::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("id=") {
+        if rest[..start].ends_with([' ', '\t', '\n']) {
+            if let Some(id) = attribute(&rest[start - 1..], "id") {
+                used.insert(id.to_string());
+            }
+        }
+        rest = &rest[start + 3..];
+    }
+
+    let mut output = String::with_capacity(html.len());
+    let mut copied = 0;
+    let mut linked = Vec::with_capacity(headings.len());
+    for heading in headings {
+        let id = match heading.id {
+            Some(id) => id,
+            None => {
+                let slug = slug(&heading.text);
+                let mut id = slug.clone();
+                let mut duplicate = 0;
+                while used.contains(&id) {
+                    duplicate += 1;
+                    id = format!("{slug}-{duplicate}");
+                }
+                used.insert(id.clone());
+
+                output += &html[copied..heading.open_end];
+                write!(output, " id=\"{id}\"").unwrap();
+                copied = heading.open_end;
+                id
+            }
+        };
+        if let Some(permalink) = &options.permalink {
+            // Headings in a layout's page were already given permalinks
+            let contents = &html[heading.open_end..heading.close];
+            if !contents.contains("class=\"permalink\"") {
+                output += &html[copied..heading.close];
+                write!(
+                    output,
+                    " <a class=\"permalink\" href=\"#{id}\" aria-hidden=\"true\">{permalink}</a>"
+                )
+                .unwrap();
+                copied = heading.close;
+            }
+        }
+        linked.push((heading.level, id, heading.text));
+    }
+    output += &html[copied..];
+
+    if has_toc {
+        output = expand_markers(&output, |depth| {
+            let mut toc = String::new();
+            write_toc(&linked, depth, &mut toc);
+            toc
+        });
+    }
+
+    Some(output)
+}
+
+/// Swaps every table of contents marker for what `toc` makes for its depth. A
+/// marker that's the only thing in a paragraph, like translated Markdown and
+/// Gemtext put it, replaces the paragraph.
+fn expand_markers(html: &str, toc: impl Fn(usize) -> String) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(MARKER_START) {
+        let Some(end) = rest[start..].find(MARKER_END).map(|end| end + start) else {
+            break;
+        };
+        let depth = rest[start + MARKER_START.len_utf8()..end]
+            .strip_prefix("TOC:")
+            .and_then(|depth| depth.parse().ok())
+            .unwrap_or(6);
+        let after = &rest[end + MARKER_END.len_utf8()..];
+
+        let before = &rest[..start];
+        let paragraph = before.rfind('<').filter(|open| {
+            let tag = &before[*open..];
+            (tag == "<p>" || (tag.starts_with("<p ") && tag.ends_with('>')))
+                && after.starts_with("</p>")
+        });
+        match paragraph {
+            Some(open) => {
+                output += &before[..open];
+                output += &toc(depth);
+                rest = &after["</p>".len()..];
+            }
+            None => {
+                output += before;
+                output += &toc(depth);
+                rest = after;
+            }
+        }
+    }
+    output += rest;
+
+    output
+}
+
+/// Writes a nested `<ul>` linking to the headings, as `(level, id, text)`.
+/// Only headings within `depth` levels of the highest one are listed.
+fn write_toc(headings: &[(u8, String, String)], depth: usize, out: &mut String) {
+    let Some(top) = headings.iter().map(|(level, _, _)| *level).min() else {
+        return;
+    };
+    // The levels of the lists that are open
+    let mut open: Vec<u8> = Vec::new();
+    for (level, id, text) in headings {
+        if usize::from(level - top) >= depth {
+            continue;
+        }
+        // Close lists of deeper headings, but keep one open for a heading
+        // that's between two levels, like an `<h3>` after an `<h4>` in an
+        // `<h2>`
+        while let Some(&last) = open.last() {
+            if *level >= last {
+                break;
+            }
+            let len = open.len();
+            if len > 1 && *level <= open[len - 2] {
+                *out += "</li></ul>";
+                open.pop();
+            } else {
+                open[len - 1] = *level;
+            }
+        }
+        match open.last() {
+            Some(last) if level == last => *out += "</li><li>",
+            _ => {
+                *out += "<ul><li>";
+                open.push(*level);
+            }
+        }
+        write!(out, "<a href=\"#{id}\">{text}</a>").unwrap();
+    }
+    for _ in open {
+        *out += "</li></ul>";
+    }
+}
+
+/// Fills in the tables of contents in a Gemtext page. Gemtext can't link to
+/// headings, so this is a flat list of their text.
+pub fn expand_gemtext_toc(gemtext: &str) -> Option<String> {
+    if !gemtext.contains(MARKER_START) {
+        return None;
+    }
+
+    let mut headings = Vec::new();
+    let mut preformatted = false;
+    for line in gemtext.lines() {
+        if line.starts_with("```") {
+            preformatted = !preformatted;
+        } else if !preformatted && line.starts_with('#') {
+            let level = line.bytes().take(3).take_while(|b| *b == b'#').count();
+            headings.push((level, line[level..].trim()));
+        }
+    }
+
+    let mut output = String::with_capacity(gemtext.len());
+    for line in gemtext.split_inclusive('\n') {
+        if line.trim_start().starts_with(MARKER_START) {
+            let newline = line.ends_with('\n');
+            let toc = expand_markers(line.trim(), |depth| {
+                let top = headings.iter().map(|(level, _)| *level).min().unwrap_or(1);
+                headings
+                    .iter()
+                    .filter(|(level, _)| level - top < depth)
+                    .map(|(_, text)| format!("* {text}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+            output += &toc;
+            if newline && !toc.is_empty() {
+                output.push('\n');
+            }
+        } else {
+            output += &expand_markers(line, |_| String::new());
+        }
+    }
+
+    Some(output)
+}
@@ -0,0 +1,745 @@
+//! Build-time syntax highlighting for code blocks.
+//!
+//! Code is split into tokens by a small lexer for its language, and each token
+//! is wrapped in a `<span>` with a class like `hl-keyword`, which [`THEME`]
+//! colours. Nothing runs in the browser, and the same code is always
+//! highlighted the same way.
+
+use {
+    crate::{translator, Config, ErrorKind, OutputFormat, WebbyError},
+    std::{fs, path::PathBuf},
+};
+
+/// The CSS that colours highlighted code. It's written to the `theme` file
+/// given in `webby.toml`, and follows the reader's light or dark mode.
+pub const THEME: &str = "\
+/* Syntax highlighting theme generated by webby */
+.hl-comment { color: #6a737d; font-style: italic; }
+.hl-string { color: #032f62; }
+.hl-number, .hl-literal { color: #005cc5; }
+.hl-keyword { color: #d73a49; }
+.hl-type { color: #6f42c1; }
+.hl-function { color: #6f42c1; }
+.hl-property, .hl-attribute { color: #005cc5; }
+.hl-tag { color: #22863a; }
+.hl-variable { color: #e36209; }
+@media (prefers-color-scheme: dark) {
+  .hl-comment { color: #8b949e; }
+  .hl-string { color: #a5d6ff; }
+  .hl-number, .hl-literal { color: #79c0ff; }
+  .hl-keyword { color: #ff7b72; }
+  .hl-type { color: #d2a8ff; }
+  .hl-function { color: #d2a8ff; }
+  .hl-property, .hl-attribute { color: #79c0ff; }
+  .hl-tag { color: #7ee787; }
+  .hl-variable { color: #ffa657; }
+}
+";
+
+/// Writes the theme to every HTML output, if `webby.toml` gives a file for
+/// it. Returns the files whose contents changed.
+pub fn write_theme(config: &Config) -> Result<Vec<PathBuf>, WebbyError> {
+    let mut written = Vec::new();
+    let Some(theme) = &config.highlight_theme else {
+        return Ok(written);
+    };
+    if config.dry_run {
+        return Ok(written);
+    }
+
+    let roots = std::iter::once(&config.output_dir).chain(
+        config
+            .outputs
+            .values()
+            .filter(|output| output.format == OutputFormat::Html)
+            .map(|output| &output.path),
+    );
+    for root in roots {
+        let path = root.join(theme);
+        if fs::read(&path).is_ok_and(|old| old == THEME.as_bytes()) {
+            continue;
+        }
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, THEME))
+            .map_err(|err| {
+                WebbyError::new(ErrorKind::Io, format!("Failed to write {path:?}"))
+                    .in_file(&config.path)
+                    .caused_by(err)
+            })?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Highlights code in a language, returning HTML to put in a `<pre>`. Returns
+/// `None` if webby can't highlight the language.
+///
+/// Languages are named like they are in Markdown code blocks: `rust`, `toml`,
+/// `sh`, `html`, `css`, `js`, and `json`, and some common aliases.
+pub fn highlight(language: &str, code: &str) -> Option<String> {
+    let mut out = String::with_capacity(code.len() * 2);
+    match language.to_ascii_lowercase().as_str() {
+        "html" | "htm" | "xml" | "svg" => highlight_html(code, &mut out),
+        name => Lexer::new(language_named(name)?, code, &mut out).run(),
+    }
+    Some(out)
+}
+
+/// Highlights the `<pre><code class="language-x">` blocks in a page. Returns
+/// `None` if there weren't any blocks it could highlight.
+pub fn highlight_code_blocks(html: &str) -> Option<String> {
+    let mut out = String::new();
+    // The end of the last block that was highlighted
+    let mut last = 0;
+    let mut search = 0;
+
+    while let Some(idx) = html[search..].find("<code") {
+        let start = search + idx;
+        search = start + "<code".len();
+
+        // The `<code>` has to be the first thing in a `<pre>`
+        let before = html[..start].trim_end();
+        let is_in_pre = before.rfind('<').is_some_and(|tag| {
+            let tag = &before[tag..];
+            tag.ends_with('>') && (tag == "<pre>" || tag.starts_with("<pre "))
+        });
+        let is_code_tag = html[search..].starts_with(|c: char| c == '>' || c.is_whitespace());
+        let Some(tag_len) = html[start..].find('>') else {
+            break;
+        };
+        let tag = &html[start..start + tag_len];
+        let content_start = start + tag_len + 1;
+        let Some(content_len) = html[content_start..].find("</code>") else {
+            break;
+        };
+        let content = &html[content_start..content_start + content_len];
+        // Code with tags in it is already highlighted, or has other markup
+        if !is_in_pre || !is_code_tag || content.contains('<') {
+            continue;
+        }
+        let Some(language) = class_attribute(tag).and_then(|class| {
+            class
+                .split_whitespace()
+                .find_map(|class| class.strip_prefix("language-"))
+        }) else {
+            continue;
+        };
+        let Some(highlighted) = highlight(language, &translator::decode_entities(content)) else {
+            continue;
+        };
+
+        out += &html[last..content_start];
+        out += &highlighted;
+        last = content_start + content_len;
+        search = last;
+    }
+
+    if last == 0 {
+        return None;
+    }
+    out += &html[last..];
+    Some(out)
+}
+
+/// Finds the value of the `class` attribute in an opening tag.
+fn class_attribute(tag: &str) -> Option<&str> {
+    let value = &tag[tag.find("class=")? + "class=".len()..];
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &value[1..];
+    Some(&value[..value.find(quote)?])
+}
+
+/// What a token is highlighted as.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Literal,
+    Type,
+    Function,
+    Property,
+    Tag,
+    Attribute,
+    Variable,
+}
+impl Class {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Comment => "hl-comment",
+            Self::String => "hl-string",
+            Self::Number => "hl-number",
+            Self::Keyword => "hl-keyword",
+            Self::Literal => "hl-literal",
+            Self::Type => "hl-type",
+            Self::Function => "hl-function",
+            Self::Property => "hl-property",
+            Self::Tag => "hl-tag",
+            Self::Attribute => "hl-attribute",
+            Self::Variable => "hl-variable",
+        }
+    }
+}
+
+/// Languages with rules that don't fit in [`Language`]'s lists.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Rust,
+    Toml,
+    Shell,
+    Css,
+    Js,
+    Json,
+}
+
+struct Language {
+    syntax: Syntax,
+    keywords: &'static [&'static str],
+    literals: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Characters identifiers can have after their first character, besides
+    /// letters, numbers and `_`.
+    ident_chars: &'static [char],
+}
+
+const RUST: Language = Language {
+    syntax: Syntax::Rust,
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+        "union", "unsafe", "use", "where", "while",
+    ],
+    literals: &["true", "false", "None", "Some", "Ok", "Err"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    ident_chars: &[],
+};
+const TOML: Language = Language {
+    syntax: Syntax::Toml,
+    keywords: &[],
+    literals: &["true", "false", "inf", "nan"],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    ident_chars: &['-'],
+};
+const SHELL: Language = Language {
+    syntax: Syntax::Shell,
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "in", "do", "done", "while", "until", "case",
+        "esac", "function", "return", "export", "local", "readonly", "set", "unset", "exit",
+    ],
+    literals: &["true", "false"],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    ident_chars: &['-', '.'],
+};
+const CSS: Language = Language {
+    syntax: Syntax::Css,
+    keywords: &[],
+    literals: &["inherit", "initial", "unset", "none", "auto"],
+    line_comments: &[],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    ident_chars: &['-'],
+};
+const JS: Language = Language {
+    syntax: Syntax::Js,
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "let",
+        "new",
+        "of",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "try",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+    literals: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    ident_chars: &['$'],
+};
+const JSON: Language = Language {
+    syntax: Syntax::Json,
+    keywords: &[],
+    literals: &["true", "false", "null"],
+    line_comments: &[],
+    block_comment: None,
+    quotes: &['"'],
+    ident_chars: &[],
+};
+
+fn language_named(name: &str) -> Option<&'static Language> {
+    Some(match name {
+        "rust" | "rs" => &RUST,
+        "toml" => &TOML,
+        "sh" | "bash" | "shell" | "zsh" | "console" => &SHELL,
+        "css" => &CSS,
+        "js" | "javascript" | "mjs" | "cjs" | "jsx" => &JS,
+        "json" | "jsonc" => &JSON,
+        _ => return None,
+    })
+}
+
+struct Lexer<'a> {
+    language: &'static Language,
+    code: &'a str,
+    out: &'a mut String,
+    pos: usize,
+}
+impl<'a> Lexer<'a> {
+    fn new(language: &'static Language, code: &'a str, out: &'a mut String) -> Self {
+        Self {
+            language,
+            code,
+            out,
+            pos: 0,
+        }
+    }
+
+    fn run(mut self) {
+        let language = self.language;
+        let syntax = language.syntax;
+
+        while let Some(c) = self.code[self.pos..].chars().next() {
+            let rest = &self.code[self.pos..];
+
+            if let Some((open, close)) = language.block_comment {
+                if let Some(comment) = rest.strip_prefix(open) {
+                    let len = comment
+                        .find(close)
+                        .map_or(rest.len(), |end| open.len() + end + close.len());
+                    self.emit(Some(Class::Comment), len);
+                    continue;
+                }
+            }
+            // In shells, `#` only starts a comment at the start of a word
+            if language.line_comments.iter().any(|c| rest.starts_with(c))
+                && (syntax != Syntax::Shell
+                    || self.code[..self.pos]
+                        .chars()
+                        .next_back()
+                        .is_none_or(char::is_whitespace))
+            {
+                self.emit(Some(Class::Comment), rest.find('\n').unwrap_or(rest.len()));
+                continue;
+            }
+
+            if language.quotes.contains(&c) {
+                if syntax == Syntax::Rust && c == '\'' && !is_char_literal(rest) {
+                    // A lifetime, like `'a`
+                    let len = 1 + self.ident_len(&rest[1..]);
+                    self.emit(Some(Class::Variable), len);
+                    continue;
+                }
+                let len = self.string_len(rest, c);
+                // Object keys in JSON are strings followed by a `:`
+                let class = if syntax == Syntax::Json && next_char(&rest[len..]) == Some(':') {
+                    Class::Property
+                } else {
+                    Class::String
+                };
+                self.emit(Some(class), len);
+                continue;
+            }
+
+            if c.is_ascii_digit() {
+                let len = rest
+                    .char_indices()
+                    .find(|(idx, c)| {
+                        !(c.is_alphanumeric()
+                            || *c == '_'
+                            || (*c == '.'
+                                && rest[idx + 1..].starts_with(|c: char| c.is_ascii_digit())))
+                    })
+                    .map_or(rest.len(), |(idx, _)| idx);
+                self.emit(Some(Class::Number), len);
+                continue;
+            }
+
+            match (syntax, c) {
+                (Syntax::Shell, '$') => {
+                    let len = if rest[1..].starts_with('{') {
+                        rest.find('}').map_or(rest.len(), |end| end + 1)
+                    } else {
+                        match word_len(&rest[1..]) {
+                            // Special variables, like `$1` and `$?`
+                            0 => rest[1..].chars().next().map_or(1, |c| 1 + c.len_utf8()),
+                            len => 1 + len,
+                        }
+                    };
+                    self.emit(Some(Class::Variable), len);
+                    continue;
+                }
+                (Syntax::Css, '@') => {
+                    let len = 1 + self.ident_len(&rest[1..]);
+                    self.emit(Some(Class::Keyword), len);
+                    continue;
+                }
+                // Colours, like `#fff`
+                (Syntax::Css, '#') if !in_css_selector(rest) => {
+                    let len = 1 + self.ident_len(&rest[1..]);
+                    self.emit(Some(Class::Number), len);
+                    continue;
+                }
+                (Syntax::Css, '!') if rest.starts_with("!important") => {
+                    self.emit(Some(Class::Keyword), "!important".len());
+                    continue;
+                }
+                // Table headers, like `[table]` and `[[array]]`
+                (Syntax::Toml, '[') if self.at_line_start() => {
+                    let len = rest.find(['\n', '#']).unwrap_or(rest.len());
+                    let len = rest[..len].rfind(']').map_or(len, |end| end + 1);
+                    self.emit(Some(Class::Type), len);
+                    continue;
+                }
+                _ => {}
+            }
+
+            if c.is_alphabetic() || c == '_' {
+                let len = self.ident_len(rest);
+                let word = &rest[..len];
+                let after = &rest[len..];
+
+                // Byte and raw strings, like `b"..."` and `r#"..."#`
+                if syntax == Syntax::Rust {
+                    let string_len = match (word, after.chars().next()) {
+                        ("b", Some(quote @ ('"' | '\''))) => Some(self.string_len(after, quote)),
+                        ("r" | "br", _) => raw_string_len(after),
+                        _ => None,
+                    };
+                    if let Some(string_len) = string_len {
+                        self.emit(Some(Class::String), len + string_len);
+                        continue;
+                    }
+                }
+
+                let class = self.classify(word, after);
+                self.emit(class, len);
+                continue;
+            }
+
+            self.emit(None, c.len_utf8());
+        }
+    }
+
+    fn classify(&self, word: &str, after: &str) -> Option<Class> {
+        let language = self.language;
+        if language.keywords.contains(&word) {
+            return Some(Class::Keyword);
+        }
+        if language.literals.contains(&word) {
+            return Some(Class::Literal);
+        }
+
+        let next = next_char(after);
+        match language.syntax {
+            Syntax::Rust | Syntax::Js if next == Some('(') => Some(Class::Function),
+            // Macros, like `println!`
+            Syntax::Rust if after.starts_with('!') && !after.starts_with("!=") => {
+                Some(Class::Function)
+            }
+            Syntax::Rust | Syntax::Js if word.starts_with(char::is_uppercase) => Some(Class::Type),
+            // Keys come before the line's `=`, or start an inline table's entry
+            Syntax::Toml if matches!(next, Some('=' | '.')) && self.at_key() => {
+                Some(Class::Property)
+            }
+            Syntax::Css if next == Some('(') => Some(Class::Function),
+            // Media features, like `(min-width: 40em)`
+            Syntax::Css if self.in_parens() => (next == Some(':')).then_some(Class::Property),
+            // Words in selectors are element names, unless they're class names
+            // or pseudo-classes
+            Syntax::Css if in_css_selector(after) => {
+                (!self.after_selector_punctuation()).then_some(Class::Tag)
+            }
+            Syntax::Css if next == Some(':') => Some(Class::Property),
+            _ => None,
+        }
+    }
+
+    /// If the last thing before the current token was a `.`, `#` or `:`,
+    /// making the token a class name, ID or pseudo-class in a CSS selector.
+    fn after_selector_punctuation(&self) -> bool {
+        self.code[..self.pos].ends_with(['.', ':', '-', '#'])
+    }
+
+    /// The text between the start of the line and the lexer.
+    fn line_before(&self) -> &str {
+        self.code[..self.pos]
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+    }
+
+    /// If the lexer is inside parentheses opened on the current line.
+    fn in_parens(&self) -> bool {
+        let line = self.line_before();
+        line.matches('(').count() > line.matches(')').count()
+    }
+
+    /// If there's only whitespace between the start of the line and the lexer.
+    fn at_line_start(&self) -> bool {
+        self.line_before().trim().is_empty()
+    }
+
+    /// If a TOML key can go here: before the line's `=`, after the `.` in a
+    /// dotted key, or at the start of an entry in an inline table.
+    fn at_key(&self) -> bool {
+        let line = self.line_before();
+        !line.contains('=') || line.trim_end().ends_with(['{', ',', '.'])
+    }
+
+    fn ident_len(&self, text: &str) -> usize {
+        text.char_indices()
+            .find(|(_, c)| {
+                !(c.is_alphanumeric() || *c == '_' || self.language.ident_chars.contains(c))
+            })
+            .map_or(text.len(), |(idx, _)| idx)
+    }
+
+    /// Finds the length of the string at the start of `text`, including its
+    /// quotes.
+    fn string_len(&self, text: &str, quote: char) -> usize {
+        let syntax = self.language.syntax;
+        // TOML has multi-line strings with three quotes
+        if syntax == Syntax::Toml {
+            let triple = if quote == '"' { "\"\"\"" } else { "'''" };
+            if let Some(rest) = text.strip_prefix(triple) {
+                return rest.find(triple).map_or(text.len(), |end| end + 6);
+            }
+        }
+        let escapes = !(quote == '\'' && matches!(syntax, Syntax::Toml | Syntax::Shell));
+        let multiline = matches!(
+            (syntax, quote),
+            (Syntax::Rust, '"') | (Syntax::Js, '`') | (Syntax::Shell, _)
+        );
+
+        let mut chars = text.char_indices().skip(1);
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '\\' if escapes => {
+                    chars.next();
+                }
+                '\n' if !multiline => return idx,
+                c if c == quote => return idx + c.len_utf8(),
+                _ => {}
+            }
+        }
+        text.len()
+    }
+
+    /// Writes the next `len` bytes of code, in a span if they have a class.
+    fn emit(&mut self, class: Option<Class>, len: usize) {
+        let text = &self.code[self.pos..self.pos + len];
+        push(self.out, class, text);
+        self.pos += len;
+    }
+}
+
+/// If CSS is in a selector, because the next `{`, `;`, or `}` in it opens a
+/// rule.
+fn in_css_selector(text: &str) -> bool {
+    text.find(['{', ';', '}'])
+        .is_some_and(|idx| text[idx..].starts_with('{'))
+}
+
+/// If a `'` in Rust starts a character literal, rather than a lifetime.
+fn is_char_literal(text: &str) -> bool {
+    let mut chars = text.chars().skip(1);
+    match chars.next() {
+        Some('\\') => true,
+        Some(_) => chars.next() == Some('\''),
+        None => false,
+    }
+}
+
+/// Finds the length of a raw string in Rust, after its `r`, like `#"..."#`.
+/// Returns `None` if `text` doesn't start with one.
+fn raw_string_len(text: &str) -> Option<usize> {
+    let hashes = text.len() - text.trim_start_matches('#').len();
+    if !text[hashes..].starts_with('"') {
+        return None;
+    }
+    let close = format!("\"{}", "#".repeat(hashes));
+    let start = hashes + 1;
+    Some(
+        text[start..]
+            .find(&close)
+            .map_or(text.len(), |end| start + end + close.len()),
+    )
+}
+
+/// Finds the length of the word at the start of `text`, made of letters,
+/// numbers and `_`.
+fn word_len(text: &str) -> usize {
+    text.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len())
+}
+
+/// The next character in `text` that isn't a space or tab.
+fn next_char(text: &str) -> Option<char> {
+    text.chars().find(|c| *c != ' ' && *c != '\t')
+}
+
+fn highlight_html(code: &str, out: &mut String) {
+    let mut pos = 0;
+    while pos < code.len() {
+        let rest = &code[pos..];
+
+        if rest.starts_with("<!--") {
+            let len = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            push(out, Some(Class::Comment), &rest[..len]);
+            pos += len;
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            // Doctypes and XML declarations
+            let len = rest.find('>').map_or(rest.len(), |end| end + 1);
+            push(out, Some(Class::Keyword), &rest[..len]);
+            pos += len;
+        } else if rest.starts_with('<')
+            && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/')
+        {
+            pos += highlight_tag(rest, out);
+        } else if let Some(entity) = rest.strip_prefix('&') {
+            // Entities, like `&amp;`
+            let len = entity
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '#')
+                .filter(|end| entity[*end..].starts_with(';'))
+                .map_or(1, |end| end + 2);
+            push(out, (len > 1).then_some(Class::Literal), &rest[..len]);
+            pos += len;
+        } else {
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let len = rest[first..]
+                .find(['<', '&'])
+                .map_or(rest.len(), |end| first + end);
+            push(out, None, &rest[..len]);
+            pos += len;
+        }
+    }
+}
+
+/// Highlights the tag at the start of `text`, and the contents of `<script>`
+/// and `<style>` tags. Returns how much of `text` it highlighted.
+fn highlight_tag(text: &str, out: &mut String) -> usize {
+    let closing = text.starts_with("</");
+    let mut pos = if closing { 2 } else { 1 };
+    push(out, None, &text[..pos]);
+
+    let name_len = text[pos..]
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(text.len() - pos);
+    let name = &text[pos..pos + name_len];
+    push(out, Some(Class::Tag), name);
+    pos += name_len;
+
+    while let Some(c) = text[pos..].chars().next() {
+        let rest = &text[pos..];
+        let len = match c {
+            '>' => {
+                push(out, None, ">");
+                pos += 1;
+                break;
+            }
+            '"' | '\'' => {
+                let len = rest[1..].find(c).map_or(rest.len(), |end| end + 2);
+                push(out, Some(Class::String), &rest[..len]);
+                len
+            }
+            c if c.is_whitespace() || c == '=' || c == '/' => {
+                push(out, None, &rest[..c.len_utf8()]);
+                c.len_utf8()
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/'))
+                    .unwrap_or(rest.len());
+                // Unquoted values come right after an `=`
+                let class = if text[..pos].ends_with('=') {
+                    Class::String
+                } else {
+                    Class::Attribute
+                };
+                push(out, Some(class), &rest[..len]);
+                len
+            }
+        };
+        pos += len;
+    }
+
+    // Scripts and styles are highlighted as JS and CSS
+    if text[..pos].ends_with("/>") {
+        return pos;
+    }
+    let language = match name.to_ascii_lowercase().as_str() {
+        "script" if !closing => &JS,
+        "style" if !closing => &CSS,
+        _ => return pos,
+    };
+    let end = text[pos..]
+        .to_ascii_lowercase()
+        .find(&format!("</{}", name.to_ascii_lowercase()))
+        .map_or(text.len(), |end| pos + end);
+    Lexer::new(language, &text[pos..end], out).run();
+
+    end
+}
+
+/// Writes escaped text, in a span if it has a class.
+fn push(out: &mut String, class: Option<Class>, text: &str) {
+    if let Some(class) = class {
+        *out += "<span class=\"";
+        *out += class.name();
+        *out += "\">";
+    }
+    for c in text.chars() {
+        match c {
+            '&' => *out += "&amp;",
+            '<' => *out += "&lt;",
+            '>' => *out += "&gt;",
+            '"' => *out += "&quot;",
+            c => out.push(c),
+        }
+    }
+    if class.is_some() {
+        *out += "</span>";
+    }
+}
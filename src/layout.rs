@@ -0,0 +1,65 @@
+//! Layouts wrap translated pages (like Markdown blog posts) in a full HTML
+//! document.
+//!
+//! A layout is an ordinary HTML file that uses `#!CONTENT()` where the page
+//! should go and, optionally, `#!TITLE()` where the page's title should go.
+//! The title is taken from the page's front matter, or if it doesn't have one,
+//! its first heading. The page's front matter can also be used with
+//! `#!META(key)`.
+
+use {
+    crate::{feed::escape, headings, Context, ErrorKind, FileType, WebbyError},
+    std::path::Path,
+};
+
+/// A translated page that's being placed in a layout.
+pub struct Page {
+    /// The translated HTML of the page.
+    pub content: String,
+    /// The page's title, as HTML.
+    pub title: String,
+}
+
+/// Places the translated HTML in `content` into the layout at `layout_path`.
+/// `source_path` is the file `content` was translated from.
+pub fn apply_layout(
+    layout_path: &Path,
+    source_path: &Path,
+    content: String,
+    ctx: &mut Context,
+) -> Result<String, WebbyError> {
+    let layout = ctx.read_to_string(layout_path).map_err(|err| {
+        WebbyError::new(
+            ErrorKind::Io,
+            format!("Failed to read layout {layout_path:?}"),
+        )
+        .in_file(source_path)
+        .caused_by(err)
+    })?;
+
+    // The heading's text is already HTML, so the other titles are escaped to
+    // match it
+    let title = ctx
+        .front_matter
+        .as_ref()
+        .and_then(|front_matter| front_matter.title())
+        .map(escape)
+        .or_else(|| {
+            headings::find(&content)
+                .into_iter()
+                .next()
+                .map(|heading| heading.text)
+        })
+        .unwrap_or_else(|| {
+            source_path
+                .file_stem()
+                .map(|stem| escape(&stem.to_string_lossy()))
+                .unwrap_or_default()
+        });
+    let outer_page = ctx.page.replace(Page { content, title });
+    let result = crate::compile_file(&layout, layout_path, FileType::from(layout_path), ctx)
+        .map(|(compiled, _)| compiled.into_owned());
+    ctx.page = outer_page;
+
+    result
+}
@@ -1,25 +1,54 @@
+pub mod cache;
+pub mod collection;
 pub mod compiler;
+pub mod config;
+pub mod date;
+pub mod error;
+pub mod feed;
+pub mod front_matter;
+pub mod glob;
+pub mod headings;
+pub mod highlight;
+pub mod layout;
 pub mod minifier;
+pub mod serve;
+pub mod sitemap;
 pub mod translator;
+pub mod watch;
+
+pub use {
+    cache::{Cache, Hash},
+    config::{Config, Profile},
+    error::{ErrorKind, Position, WebbyError},
+    feed::{Feed, FeedFormat},
+    front_matter::{FrontMatter, Value},
+    glob::Pattern,
+    headings::HeadingOptions,
+    minifier::MinifyOptions,
+    translator::{GemtextOptions, MarkdownOptions},
+};
 
 use std::{
-    fs::{self, File},
-    path::{Path, PathBuf},
+    collections::BTreeMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    thread,
 };
 
 type Cow<'a> = std::borrow::Cow<'a, str>;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum Mode {
     Compile,
     Copy,
     Link,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum FileType {
     Html,
     Css,
+    Js,
     Gemtext,
     Markdown,
     Unknown,
@@ -29,112 +58,639 @@ impl From<&Path> for FileType {
         match value.extension().and_then(|str| str.to_str()) {
             Some("html") => FileType::Html,
             Some("css") => FileType::Css,
+            Some("js") | Some("mjs") => FileType::Js,
             Some("gmi") | Some("gemtext") => FileType::Gemtext,
             Some("md") | Some("markdown") => FileType::Markdown,
             _ => FileType::Unknown,
         }
     }
 }
+impl FileType {
+    /// Returns true for file types that are translated to another language
+    /// when they're compiled, and so need a new file extension.
+    pub fn is_translated(self) -> bool {
+        matches!(self, Self::Gemtext | Self::Markdown)
+    }
+}
 
+/// The format translated files (Markdown and Gemtext) are written in. HTML
+/// pages written to Gemtext outputs are translated too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Translate them to HTML, for websites.
+    #[default]
+    Html,
+    /// Translate them to Gemtext, for Gemini capsules.
+    Gemtext,
+}
+
+#[derive(Clone, Debug)]
 pub struct Target {
+    /// The file or folder to build. For glob targets, this is the folder the
+    /// glob is matched in.
     pub path: PathBuf,
     pub output: PathBuf,
     pub mode: Mode,
     pub file_type: FileType,
+    /// If set, only files in `path` whose relative paths match this pattern
+    /// are built.
+    pub pattern: Option<Pattern>,
+    /// Files and folders in `path` that shouldn't be built.
+    pub exclude: Vec<Pattern>,
+    /// The extension translated files in this target are given, overriding
+    /// [`Config::output_extensions`].
+    pub output_extension: Option<String>,
+    /// If set, HTML files are written to `name/index.html` instead of
+    /// `name.html`.
+    pub clean_urls: bool,
+    /// If set and `path` is a file, it's written to exactly `output`, without
+    /// changing its extension or applying clean URLs. This is set when the
+    /// target's `output` is given in `webby.toml`.
+    pub keep_output_name: bool,
+    /// The layout translated files in this target are placed in.
+    pub layout: Option<PathBuf>,
+    /// Variables that can be used with `#!VAR(name)` in this target. These
+    /// override the ones in [`Config::vars`].
+    pub vars: BTreeMap<String, Value>,
+    /// If set, the HTML pages this target builds are listed in the sitemap.
+    pub sitemap: bool,
+    /// The format translated files in this target are written in.
+    pub format: OutputFormat,
+    /// If set, this target writes a feed of the pages it matches to `output`,
+    /// instead of building them.
+    pub feed: Option<Feed>,
+    /// How Gemtext files in this target are translated to HTML.
+    pub gemtext: GemtextOptions,
+    /// How Markdown files in this target are parsed and translated.
+    pub markdown: MarkdownOptions,
+    /// If set, code blocks in this target's pages are syntax highlighted. See
+    /// [`highlight`].
+    pub highlight: bool,
+    /// How headings in this target's pages are given ids and permalinks.
+    pub headings: HeadingOptions,
 }
 
-pub fn build_target(target: Target) -> Result<(), Cow<'static>> {
-    let op: fn(&Path, &Path, &Path, FileType) -> Result<(), String> = match target.mode {
-        Mode::Copy => |target_path, path, output, _| {
-            fs::copy(path, output).map_err(|err| {
-                    panic!("Failed to copy batch target {target_path:?}. Couldn't copy file at {path:?} because: {err}");
-                }).map(|_| {})
-        },
-        Mode::Link => |target_path, path, output, _| {
-            fs::hard_link(path, output)
-                .map_err(|err| format!("Failed to link target {target_path:?}: {err}"))
-        },
-        Mode::Compile => |target_path, path, output, file_type| {
-            let original = fs::read_to_string(path).map_err(|err| {
-                    format!(
-                        "Failed to compile target {target_path:?}: Error occurred while reading the source file: {err}"
-                    )
-                })?;
+/// State for compiling a single file, shared with any files it includes.
+#[derive(Default)]
+pub struct Context<'a> {
+    /// The project the file is being built in. `#!FOREACH(...)` uses this to
+    /// find the URLs of the pages it lists.
+    pub config: Option<&'a Config>,
+    /// Every file or folder read while compiling the file, and a hash of its
+    /// contents (or `None` if it couldn't be read).
+    pub dependencies: Vec<(PathBuf, Option<Hash>)>,
+    /// The page being placed in a layout, if a layout is being compiled. This
+    /// is what `#!CONTENT()` and `#!TITLE()` expand to.
+    pub page: Option<layout::Page>,
+    /// The front matter of the file being compiled, if it has any. This is
+    /// what `#!META(key)` reads from.
+    pub front_matter: Option<FrontMatter>,
+    /// The variables `#!VAR(name)` can read, besides the ones in the front
+    /// matter.
+    pub vars: BTreeMap<String, Value>,
+    /// The profile the project is being built with.
+    pub profile: Profile,
+    /// How compiled HTML and CSS are cleaned up.
+    pub minify: MinifyOptions,
+    /// The format translated files are written in.
+    pub format: OutputFormat,
+    /// How Gemtext is translated to HTML.
+    pub gemtext: GemtextOptions,
+    /// How Markdown is parsed and translated.
+    pub markdown: MarkdownOptions,
+    /// If set, code blocks in compiled HTML are syntax highlighted.
+    pub highlight: bool,
+    /// How headings in compiled HTML are given ids and permalinks.
+    pub headings: HeadingOptions,
+}
+impl Context<'_> {
+    /// Looks up a variable for `#!VAR(name)`: first in the front matter, then
+    /// in the target's and project's variables.
+    pub fn var(&self, name: &str) -> Option<&Value> {
+        self.front_matter
+            .as_ref()
+            .and_then(|front_matter| front_matter.get(name))
+            .or_else(|| front_matter::lookup(&self.vars, name))
+    }
+
+    /// Reads a file, recording it as a dependency of the file being compiled.
+    pub fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        let bytes = fs::read(path);
+        self.dependencies.push((
+            path.to_path_buf(),
+            bytes.as_ref().ok().map(|bytes| cache::hash(bytes)),
+        ));
 
-            let compiled = compile_file(&original, path, file_type)?;
+        bytes
+    }
+
+    /// Reads a UTF-8 file, recording it as a dependency of the file being
+    /// compiled.
+    pub fn read_to_string(&mut self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Lists the entries in a folder, sorted by name, recording the folder as
+    /// a dependency of the file being compiled.
+    pub fn read_dir(&mut self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.dependencies
+            .push((path.to_path_buf(), cache::hash_path(path).ok()));
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+
+        Ok(entries)
+    }
+}
+
+/// The outcome of building a single [`Target`].
+pub struct BuildResult {
+    /// Every file and folder that was read while building the target. If any
+    /// of them change, the target needs to be rebuilt.
+    pub dependencies: Vec<PathBuf>,
+    /// Every output file whose contents changed.
+    pub written: Vec<PathBuf>,
+    pub result: Result<(), WebbyError>,
+}
+
+/// Builds each target on its own thread. The results are in the same order as
+/// the targets.
+pub fn build_targets<'a>(
+    config: &Config,
+    targets: impl IntoIterator<Item = &'a Target>,
+    cache: &Cache,
+) -> Vec<BuildResult> {
+    thread::scope(|scope| {
+        let tasks: Vec<_> = targets
+            .into_iter()
+            .map(|target| (target, scope.spawn(|| build_target(config, target, cache))))
+            .collect();
+
+        tasks
+            .into_iter()
+            .map(|(target, task)| {
+                task.join().unwrap_or_else(|_| BuildResult {
+                    dependencies: vec![target.path.clone()],
+                    written: Vec::new(),
+                    result: Err(WebbyError::new(
+                        ErrorKind::Io,
+                        "The thread building this target panicked",
+                    )
+                    .in_file(&target.path)),
+                })
+            })
+            .collect()
+    })
+}
 
-            fs::write(output, compiled.as_ref())
-                    .map_err(|err| format!("Failed to compile target {target_path:?}: Error occured while writing the compiled file: {err}"))
-        },
+/// Builds a target. Files that haven't changed since they were last built,
+/// according to `cache`, are skipped.
+pub fn build_target(config: &Config, target: &Target, cache: &Cache) -> BuildResult {
+    let mut vars = config.vars.clone();
+    vars.extend(config.profile_settings().vars.clone());
+    vars.extend(target.vars.clone());
+    // Pages can link to pages from any target, so the settings that decide
+    // where every target's files are written are part of each file's settings
+    let urls = cache::hash(
+        format!(
+            "{:?} {:?} {:?} {:?}",
+            config.targets,
+            config.outputs,
+            config.output_extensions.iter().collect::<BTreeMap<_, _>>(),
+            config.base_url
+        )
+        .as_bytes(),
+    );
+    let mut build = TargetBuild {
+        config,
+        cache,
+        target,
+        vars,
+        urls,
+        dependencies: Vec::new(),
+        written: Vec::new(),
     };
+    let result = build.build_path(&target.path, &target.output);
 
-    if target.path.is_file() | target.path.is_symlink() {
-        op(&target.path, &target.path, &target.output, target.file_type)?;
-    } else {
-        if !target.output.exists() {
-            fs::create_dir_all(&target.output).map_err(|err| {
-                            format!("Failed to copy batch target {:?}. Couldn't create its output folder at {:?} because: {err}", &target.path, &target.output)
-                        })?;
+    BuildResult {
+        dependencies: build.dependencies,
+        written: build.written,
+        result,
+    }
+}
+
+/// A target that's being built.
+struct TargetBuild<'a> {
+    config: &'a Config,
+    cache: &'a Cache,
+    target: &'a Target,
+    /// The project's variables, merged with the profile's and the target's.
+    vars: BTreeMap<String, Value>,
+    /// A hash of the settings that decide the URLs of every target's pages.
+    urls: Hash,
+    dependencies: Vec<PathBuf>,
+    written: Vec<PathBuf>,
+}
+impl TargetBuild<'_> {
+    fn build_path(&mut self, path: &Path, output: &Path) -> Result<(), WebbyError> {
+        self.dependencies.push(path.to_path_buf());
+
+        if self.target.feed.is_some() {
+            if let Some(parent) = output.parent() {
+                self.create_dir(path, parent)?;
+            }
+            return self.build_file(path, output, FileType::Unknown);
         }
+        if self.target.pattern.is_none() && (path.is_file() | path.is_symlink()) {
+            let file_type = self.target.file_type;
+            let output = if self.target.keep_output_name {
+                output.to_path_buf()
+            } else {
+                self.config.output_path(self.target, output, file_type)
+            };
+            if let Some(parent) = output.parent() {
+                self.create_dir(path, parent)?;
+            }
+            self.build_file(path, &output, file_type)
+        } else {
+            self.build_dir(path, output, Path::new(""))
+        }
+    }
+
+    /// Builds every file in a folder that matches the target's pattern and
+    /// isn't excluded. `relative` is the folder's path relative to the
+    /// target's path.
+    fn build_dir(&mut self, dir: &Path, output: &Path, relative: &Path) -> Result<(), WebbyError> {
+        let src = dir.read_dir().map_err(|err| {
+            WebbyError::new(ErrorKind::Io, "Failed to open the target's source folder")
+                .in_file(dir)
+                .caused_by(err)
+        })?;
+
+        for dir_entry in src.filter_map(|dir_entry| dir_entry.ok()) {
+            let name = dir_entry.file_name();
+            if name.to_str().is_some_and(glob::is_ignored) {
+                continue;
+            }
+            let relative = relative.join(&name);
+            if self
+                .target
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches(&relative))
+            {
+                continue;
+            }
+            let path = dir_entry.path();
+            let output = output.join(&name);
+
+            if path.is_dir() {
+                self.dependencies.push(path.clone());
+                self.build_dir(&path, &output, &relative)?;
+            } else if self
+                .target
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.matches(&relative))
+            {
+                let file_type = FileType::from(path.as_path());
+                let output = self.config.output_path(self.target, &output, file_type);
+                if let Some(parent) = output.parent() {
+                    self.create_dir(&path, parent)?;
+                }
+                self.build_file(&path, &output, file_type)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds a single file, unless the cache says it's already up to date.
+    fn build_file(
+        &mut self,
+        path: &Path,
+        output: &Path,
+        file_type: FileType,
+    ) -> Result<(), WebbyError> {
+        let settings = cache::hash(
+            format!(
+                "{} {file_type:?} {:?} profile={:?} {:?} drafts={} vars={:?} urls={:x}",
+                env!("CARGO_PKG_VERSION"),
+                self.target,
+                self.config.profile,
+                self.config.profile_settings().minify,
+                self.config.drafts,
+                self.vars,
+                self.urls
+            )
+            .as_bytes(),
+        );
+        if !self.config.dry_run {
+            if let Some(files) = self.cache.fresh(output, settings) {
+                self.dependencies.extend(files);
+                return Ok(());
+            }
+        }
+
+        let mut ctx = Context {
+            config: Some(self.config),
+            vars: self.vars.clone(),
+            profile: self.config.profile,
+            minify: self.config.profile_settings().minify,
+            format: self.target.format,
+            gemtext: GemtextOptions {
+                highlight: self.target.highlight,
+                ..self.target.gemtext.clone()
+            },
+            markdown: self.target.markdown.clone(),
+            highlight: self.target.highlight,
+            headings: self.target.headings.clone(),
+            ..Default::default()
+        };
+        let result = self.write_file(path, output, file_type, &mut ctx);
+        self.dependencies
+            .extend(ctx.dependencies.iter().map(|(path, _)| path.clone()));
+        let output_hash = result?;
 
-        let src = target.path.read_dir().map_err(|err| {
-                    format!(
-                        "Failed to copy batch target {:?}. Couldn't open its source directory because: {err}", &target.path
+        if self.config.dry_run {
+            return Ok(());
+        }
+        let Some(output_hash) = output_hash else {
+            // A draft; remove it if it was published before it became one
+            if output.is_file() {
+                let _ = fs::remove_file(output);
+            }
+            return Ok(());
+        };
+        if let Some(files) = ctx
+            .dependencies
+            .into_iter()
+            .map(|(path, hash)| hash.map(|hash| (path, hash)))
+            .collect()
+        {
+            self.cache.record(
+                output,
+                cache::Entry {
+                    settings,
+                    dependencies: files,
+                    output: output_hash,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Copies, links, or compiles a file to `output`. Returns the hash of the
+    /// output file, or `None` if the file is a draft and wasn't written.
+    fn write_file(
+        &mut self,
+        path: &Path,
+        output: &Path,
+        file_type: FileType,
+        ctx: &mut Context,
+    ) -> Result<Option<Hash>, WebbyError> {
+        let read_error = |err| {
+            WebbyError::new(ErrorKind::Io, "Failed to read the source file")
+                .in_file(path)
+                .caused_by(err)
+        };
+
+        if let Some(feed) = &self.target.feed {
+            let xml = feed::render(self.config, self.target, feed, ctx)?;
+            return self
+                .write_if_changed(path, output, xml.as_bytes())
+                .map(Some);
+        }
+
+        match self.target.mode {
+            Mode::Copy => {
+                let bytes = ctx.read(path).map_err(read_error)?;
+                self.write_if_changed(path, output, &bytes).map(Some)
+            }
+            Mode::Link => {
+                let hash = cache::hash(&ctx.read(path).map_err(read_error)?);
+                if self.config.dry_run
+                    || fs::read(output).is_ok_and(|bytes| cache::hash(&bytes) == hash)
+                {
+                    return Ok(Some(hash));
+                }
+
+                if output.exists() {
+                    // Rebuilding the target; remove the old link so it can be
+                    // recreated
+                    let _ = fs::remove_file(output);
+                }
+                fs::hard_link(path, output).map_err(|err| {
+                    WebbyError::new(
+                        ErrorKind::Io,
+                        format!("Failed to hard link file to {output:?}"),
                     )
+                    .in_file(path)
+                    .caused_by(err)
                 })?;
+                self.written.push(output.to_path_buf());
 
-        for dir_entry in src.filter_map(|dir_entry| dir_entry.ok()) {
-            let dir_entry = dir_entry.path();
-
-            if dir_entry.is_file() {
-                let output = target.output.join(dir_entry.file_name().unwrap());
-                op(
-                    &target.path,
-                    &dir_entry,
-                    &output,
-                    FileType::from(dir_entry.as_path()),
-                )?;
-            } else {
-                let subdir = dir_entry.file_name().unwrap();
-                let subtarget = Target {
-                    path: target.path.join(subdir),
-                    output: target.output.join(subdir),
-                    mode: target.mode,
-                    file_type: target.file_type,
-                };
-                build_target(subtarget)?;
+                Ok(Some(hash))
             }
+            Mode::Compile => {
+                let original = ctx.read_to_string(path).map_err(read_error)?;
+                let (mut compiled, front_matter) = compile_file(&original, path, file_type, ctx)?;
+                if front_matter.as_ref().is_some_and(FrontMatter::is_draft) && !self.config.drafts {
+                    return Ok(None);
+                }
+                if let Some(layout) = self.target.layout.as_ref().filter(|_| {
+                    file_type.is_translated() && self.target.format == OutputFormat::Html
+                }) {
+                    ctx.front_matter = front_matter;
+                    compiled = Cow::Owned(layout::apply_layout(
+                        layout,
+                        path,
+                        compiled.into_owned(),
+                        ctx,
+                    )?);
+                }
+                self.write_if_changed(path, output, compiled.as_bytes())
+                    .map(Some)
+            }
+        }
+    }
+
+    /// Writes `bytes` to `output`, unless `output` already has those exact
+    /// contents. This keeps the modification times of unchanged files stable.
+    /// Returns the hash of `bytes`.
+    fn write_if_changed(
+        &mut self,
+        source_path: &Path,
+        output: &Path,
+        bytes: &[u8],
+    ) -> Result<Hash, WebbyError> {
+        let hash = cache::hash(bytes);
+        if self.config.dry_run || fs::read(output).is_ok_and(|old| old == bytes) {
+            return Ok(hash);
+        }
+
+        fs::write(output, bytes).map_err(|err| {
+            WebbyError::new(
+                ErrorKind::Io,
+                format!("Failed to write the output file to {output:?}"),
+            )
+            .in_file(source_path)
+            .caused_by(err)
+        })?;
+        self.written.push(output.to_path_buf());
+
+        Ok(hash)
+    }
+
+    /// Creates a folder in the output directory, if it doesn't already exist.
+    fn create_dir(&self, source_path: &Path, dir: &Path) -> Result<(), WebbyError> {
+        if self.config.dry_run || dir.exists() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(dir).map_err(|err| {
+            WebbyError::new(
+                ErrorKind::Io,
+                format!("Failed to create the output folder at {dir:?}"),
+            )
+            .in_file(source_path)
+            .caused_by(err)
+        })
+    }
+}
+
+/// Writes the files made from the whole project after its targets are built:
+/// the sitemap, `robots.txt`, and the syntax highlighting theme. Returns the
+/// files whose contents changed, and the errors from any that couldn't be
+/// written.
+pub fn write_generated(config: &Config) -> (Vec<PathBuf>, Vec<WebbyError>) {
+    let mut written = Vec::new();
+    let mut errors = Vec::new();
+    for write in [sitemap::write, highlight::write_theme] {
+        match write(config) {
+            Ok(mut files) => written.append(&mut files),
+            Err(err) => errors.push(err),
         }
     }
 
-    Ok(())
+    (written, errors)
 }
 
+/// Compiles a file's macros, then minifies or translates it. Translated files
+/// can start with front matter, which is returned separately.
 fn compile_file<'a>(
     input: &'a str,
     source_path: &'a Path,
     file_type: FileType,
-) -> Result<Cow<'a>, String> {
-    let compiled_macros = compiler::compile_macros(input, source_path)?;
+    ctx: &mut Context,
+) -> Result<(Cow<'a>, Option<FrontMatter>), WebbyError> {
+    // A hashbang at the start of a script isn't a macro, so it's kept as it is
+    let hashbang = match file_type {
+        FileType::Js if input.starts_with("#!") => {
+            &input[..input.find('\n').unwrap_or(input.len())]
+        }
+        _ => "",
+    };
+    let (front_matter, body) = if file_type.is_translated() {
+        front_matter::split(input, source_path)?
+    } else {
+        (None, hashbang.len()..input.len())
+    };
+
+    // Included files without front matter can still read the front matter of
+    // the file including them
+    let outer_front_matter = match &front_matter {
+        Some(front_matter) => ctx.front_matter.replace(front_matter.clone()),
+        None => ctx.front_matter.clone(),
+    };
+    let compiled_macros = compiler::compile_macros_in(input, body, source_path, ctx);
+    ctx.front_matter = outer_front_matter;
+    let compiled_macros = if hashbang.is_empty() {
+        compiled_macros?
+    } else {
+        Cow::Owned(format!("{hashbang}{}", compiled_macros?))
+    };
 
     let output = match file_type {
-        FileType::Gemtext => Cow::Owned(translator::translate_gemtext(
+        // Capsules keep Gemtext as it is
+        FileType::Gemtext if ctx.format == OutputFormat::Gemtext => compiled_macros,
+        FileType::Markdown if ctx.format == OutputFormat::Gemtext => Cow::Owned(
+            translator::translate_markdown_to_gemtext_with_options(&compiled_macros, &ctx.markdown),
+        ),
+        // HTML pages are rewritten for capsules
+        FileType::Html if ctx.format == OutputFormat::Gemtext => Cow::Owned(
+            translator::translate_html_to_gemtext(source_path, &compiled_macros)?,
+        ),
+        FileType::Gemtext => Cow::Owned(translator::translate_gemtext_with_options(
             source_path,
             compiled_macros.as_ref(),
+            &ctx.gemtext,
         )?),
-        FileType::Html => Cow::Owned(minifier::minify_html(
-            source_path.to_str().unwrap(),
+        FileType::Html if ctx.minify.minify => {
+            Cow::Owned(minifier::minify_html(source_path, &compiled_macros, input)?)
+        }
+        FileType::Css if ctx.minify.minify => Cow::Owned(minifier::minify_css(&compiled_macros)),
+        FileType::Js if ctx.minify.minify => Cow::Owned(minifier::minify_js(&compiled_macros)),
+        FileType::Html if ctx.minify.strip_comments => {
+            Cow::Owned(minifier::strip_html_comments(&compiled_macros))
+        }
+        FileType::Css if ctx.minify.strip_comments => {
+            Cow::Owned(minifier::strip_css_comments(&compiled_macros))
+        }
+        FileType::Js if ctx.minify.strip_comments => {
+            Cow::Owned(minifier::strip_js_comments(&compiled_macros))
+        }
+        FileType::Html | FileType::Css | FileType::Js => compiled_macros,
+        FileType::Markdown => Cow::Owned(translator::translate_markdown_with_options(
             &compiled_macros,
-            input,
-        )?),
-        FileType::Css => Cow::Owned(minifier::minify_css(&compiled_macros)),
-        FileType::Markdown => Cow::Owned(translator::translate_markdown(&compiled_macros)),
+            &ctx.markdown,
+        )),
         FileType::Unknown => compiled_macros,
     };
+    // Gemtext highlights its own preformatted blocks while it's translated
+    let output = match file_type {
+        FileType::Html | FileType::Markdown
+            if ctx.highlight && ctx.format == OutputFormat::Html =>
+        {
+            highlight::highlight_code_blocks(&output).map_or(output, Cow::Owned)
+        }
+        _ => output,
+    };
+    // Heading ids have to be added after translating, and `#!TOC()` needs
+    // them
+    let output = match file_type {
+        FileType::Html | FileType::Markdown | FileType::Gemtext => match ctx.format {
+            OutputFormat::Html => headings::link_headings(&output, &ctx.headings),
+            OutputFormat::Gemtext => headings::expand_gemtext_toc(&output),
+        }
+        .map_or(output, Cow::Owned),
+        _ => output,
+    };
 
-    Ok(output)
+    Ok((output, front_matter))
 }
 
-fn line_number_of_offset(src: &str, offset: usize) -> usize {
-    src[..offset].bytes().filter(|byte| *byte == b'\n').count()
+/// Lexically removes `.` and `..` components from a path, so paths to the same
+/// file written different ways can be compared.
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    normalized
 }
@@ -1,132 +1,404 @@
 use {
-    boml::{table::TomlGetError, Toml},
     std::{
-        borrow::Cow,
-        env, fs,
-        path::Path,
-        thread::{self, JoinHandle},
+        env,
+        error::Error,
+        fs,
+        path::{Path, PathBuf},
+        process::ExitCode,
+        thread,
+    },
+    webby::{
+        build_targets,
+        config::{Overrides, Profile},
+        serve,
+        watch::{self, Rebuild, Watcher},
+        write_generated, Cache, Config, ErrorKind, WebbyError,
     },
-    webby::{build_target, FileType, Mode, Target},
 };
 
-type ErrorMsg = Cow<'static, str>;
-type Tasks = Vec<JoinHandle<Result<(), ErrorMsg>>>;
-
-pub fn main() -> Result<(), ErrorMsg> {
-    let cwd = env::current_dir().expect("Failed to find current directory");
-    let mut root = cwd.as_path();
-
-    while !root
-        .read_dir()
-        .expect("Failed to list files in current folder")
-        .any(|file| {
-            if let Ok(ref file) = file {
-                if let Some(name) = file.file_name().to_str() {
-                    if name == "webby.toml" && file.path().is_file() {
-                        return true;
-                    }
+const USAGE: &str = "\
+webby - the smol web compiler
+
+Usage: webby [options] [command]
+
+Commands:
+  build         Build the project (the default)
+  check         Compile the project without writing anything, to find errors
+  clean         Delete the output directory
+  init [dir]    Create a new webby project in `dir` (or the current directory)
+  watch         Build the project, then rebuild it whenever a file changes
+  serve         Watch the project and serve the output directory on localhost
+
+Options:
+  -c, --config <path>   Use this webby.toml instead of searching for one
+  -o, --output <dir>    Write output files to this directory
+  -p, --port <port>     The port for `webby serve` to use (default: 8080)
+  -r, --release         Build with the release profile instead of the dev one
+  -q, --quiet           Only print errors
+  -v, --verbose         Print every file that gets written
+  -h, --help            Print this message
+  -V, --version         Print webby's version
+";
+
+/// The port `webby serve` uses if one isn't given.
+const DEFAULT_PORT: u16 = 8080;
+
+/// The `webby.toml` created by `webby init`.
+const INIT_CONFIG: &str = r#"# See https://github.com/bright-shard/webby for everything you can put here
+
+# The folder to put compiled files in
+output = "webby"
+
+[[target]]
+path = "index.html"
+"#;
+/// The `index.html` created by `webby init`.
+const INIT_INDEX: &str = r#"<!DOCTYPE html>
+<html>
+    <head>
+        <meta charset="utf-8">
+        <title>My webby site</title>
+    </head>
+    <body>
+        <!-- Minified builds leave out comments and extra whitespace -->
+        <h1>Hello from webby!</h1>
+    </body>
+</html>
+"#;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+enum Command {
+    Build,
+    Check,
+    Clean,
+    Init(Option<PathBuf>),
+    Watch,
+    Serve,
+    Help,
+    Version,
+}
+
+struct Args {
+    command: Command,
+    config: Option<PathBuf>,
+    port: u16,
+    verbosity: Verbosity,
+    overrides: Overrides,
+}
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, WebbyError> {
+        let mut command = None;
+        let mut this = Self {
+            command: Command::Build,
+            config: None,
+            port: DEFAULT_PORT,
+            verbosity: Verbosity::Normal,
+            overrides: Overrides::default(),
+        };
+        let usage_error = |message: String| {
+            WebbyError::new(
+                ErrorKind::Config,
+                format!("{message}\nRun `webby --help` for usage."),
+            )
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| usage_error(format!("Expected a value after `{name}`")))
+            };
+
+            match arg.as_str() {
+                "-c" | "--config" => this.config = Some(PathBuf::from(value(&arg)?)),
+                "-o" | "--output" => this.overrides.output_dir = Some(PathBuf::from(value(&arg)?)),
+                "-p" | "--port" => {
+                    let port = value(&arg)?;
+                    this.port = port
+                        .parse()
+                        .map_err(|_| usage_error(format!("Invalid port `{port}`")))?;
+                }
+                "-r" | "--release" => this.overrides.profile = Some(Profile::Release),
+                "-q" | "--quiet" => this.verbosity = Verbosity::Quiet,
+                "-v" | "--verbose" => this.verbosity = Verbosity::Verbose,
+                "-h" | "--help" => command = Some(Command::Help),
+                "-V" | "--version" => command = Some(Command::Version),
+                flag if flag.starts_with('-') => {
+                    return Err(usage_error(format!("Unknown option `{flag}`")))
+                }
+                other if command.is_none() => {
+                    command = Some(match other {
+                        "build" => Command::Build,
+                        "check" => Command::Check,
+                        "clean" => Command::Clean,
+                        "init" => Command::Init(None),
+                        "watch" => Command::Watch,
+                        "serve" => Command::Serve,
+                        "help" => Command::Help,
+                        other => return Err(usage_error(format!("Unknown command `{other}`"))),
+                    })
                 }
+                other => match &mut command {
+                    Some(Command::Init(dir @ None)) => *dir = Some(PathBuf::from(other)),
+                    _ => return Err(usage_error(format!("Unexpected argument `{other}`"))),
+                },
             }
+        }
 
-            false
-        })
-    {
-        let Some(parent) = root.parent() else {
-            return Err("Failed to find webby.toml".into());
-        };
-        root = parent;
+        if let Some(command) = command {
+            this.command = command;
+        }
+        Ok(this)
     }
 
-    let cfg = fs::read_to_string(root.join("webby.toml")).expect("Failed to read webby.toml");
-    let toml = Toml::parse(&cfg).unwrap();
+    /// Finds and loads the project's config.
+    fn load_config(&self) -> Result<Config, WebbyError> {
+        let mut config = Config::load(&self.config_path()?)?;
+        config.apply(&self.overrides);
+        Ok(config)
+    }
 
-    let tasks = parse_cfg(toml, root)?;
-    for task in tasks {
-        match task.join().unwrap() {
-            Ok(()) => {}
-            Err(err) => println!("{err}"),
+    /// The path to the project's `webby.toml`.
+    fn config_path(&self) -> Result<PathBuf, WebbyError> {
+        if let Some(path) = &self.config {
+            return Ok(path.clone());
         }
+
+        let cwd = env::current_dir().map_err(|err| {
+            WebbyError::new(ErrorKind::Io, "Failed to find current directory").caused_by(err)
+        })?;
+        Config::find(&cwd)
     }
+}
 
-    Ok(())
+pub fn main() -> ExitCode {
+    let result = Args::parse(env::args().skip(1)).and_then(|mut args| match args.command {
+        Command::Build => build(&args),
+        Command::Check => {
+            args.overrides.dry_run = true;
+            build(&args)
+        }
+        Command::Clean => clean(&args),
+        Command::Init(ref dir) => init(&args, dir.as_deref()),
+        Command::Watch => watch(&args),
+        Command::Serve => serve(&args),
+        Command::Help => {
+            print!("{USAGE}");
+            Ok(true)
+        }
+        Command::Version => {
+            println!("webby {}", env!("CARGO_PKG_VERSION"));
+            Ok(true)
+        }
+    });
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            report(&err);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-fn parse_cfg(toml: Toml, root: &Path) -> Result<Tasks, ErrorMsg> {
-    let output_dir = if let Ok(output) = toml.get_string("output") {
-        root.join(output)
+/// Builds the project, or just compiles it if this is a dry run. Returns
+/// `Ok(false)` if any targets failed to build.
+fn build(args: &Args) -> Result<bool, WebbyError> {
+    let config = args.load_config()?;
+    let cache = Cache::load(&config.output_dir);
+
+    let mut failed = 0;
+    let mut written = 0;
+    for result in build_targets(&config, &config.targets, &cache) {
+        if args.verbosity == Verbosity::Verbose {
+            for path in &result.written {
+                println!("Wrote {}", path.display());
+            }
+        }
+        written += result.written.len();
+
+        if let Err(err) = result.result {
+            report(&err);
+            failed += 1;
+        }
+    }
+
+    let (files, errors) = write_generated(&config);
+    if args.verbosity == Verbosity::Verbose {
+        for path in &files {
+            println!("Wrote {}", path.display());
+        }
+    }
+    written += files.len();
+    for err in &errors {
+        report(err);
+    }
+    failed += errors.len();
+
+    if config.dry_run {
+        if args.verbosity > Verbosity::Quiet {
+            println!(
+                "Checked {} target(s), {failed} failed",
+                config.targets.len()
+            );
+        }
     } else {
-        root.join("webby")
+        cache.save()?;
+        if args.verbosity > Verbosity::Quiet {
+            println!(
+                "Built {} target(s), {failed} failed, {written} file(s) changed",
+                config.targets.len()
+            );
+        }
+    }
+
+    Ok(failed == 0)
+}
+
+/// Deletes the output directories.
+fn clean(args: &Args) -> Result<bool, WebbyError> {
+    let config = args.load_config()?;
+    let deleted = config.clean()?;
+
+    if args.verbosity > Verbosity::Quiet {
+        for output_dir in deleted {
+            println!("Deleted {}", output_dir.display());
+        }
+    }
+    Ok(true)
+}
+
+/// Creates a new project with a `webby.toml` and an example `index.html`.
+fn init(args: &Args, dir: Option<&Path>) -> Result<bool, WebbyError> {
+    let dir = dir.unwrap_or(Path::new("."));
+    let io_error = |message: &str, path: &Path, err| {
+        WebbyError::new(ErrorKind::Io, message)
+            .in_file(path)
+            .caused_by(err)
     };
 
-    if !output_dir.exists() {
-        let Ok(()) = fs::create_dir(&output_dir) else {
-            return Err("Failed to create output directory".into());
-        };
+    fs::create_dir_all(dir)
+        .map_err(|err| io_error("Failed to create the project folder", dir, err))?;
+
+    let config_path = dir.join("webby.toml");
+    if config_path.exists() {
+        return Err(WebbyError::new(
+            ErrorKind::Config,
+            "There's already a webby.toml here; not overwriting it",
+        )
+        .in_file(&config_path));
     }
+    fs::write(&config_path, INIT_CONFIG)
+        .map_err(|err| io_error("Failed to write webby.toml", &config_path, err))?;
+    let mut created = vec![config_path];
 
-    let mut tasks = Vec::default();
-
-    match toml.get_array("target") {
-        Ok(targets) => {
-            for target in targets {
-                let Some(table) = target.table() else {
-                    return Err("All target entries in webby.toml must be a TOML table.".into());
-                };
-                let Ok(path) = table.get_string("path") else {
-                    return Err("Target in webby.toml didn't have a path".into());
-                };
-                let path = root.join(path);
-                let mode = if let Ok(mode) = table.get_string("mode") {
-                    match mode {
-                        "compile" => Mode::Compile,
-                        "copy" => Mode::Copy,
-                        "link" => Mode::Link,
-                        other => {
-                            return Err(format!("Unknown mode: {other} for target: {path:?}").into())
-                        }
-                    }
-                } else {
-                    match path.extension().and_then(|osstr| osstr.to_str()) {
-                        Some("gmi" | "html" | "svg" | "md" | "css") => Mode::Compile,
-                        _ => Mode::Copy,
-                    }
-                };
-                let output = if let Ok(output_name) = table.get_string("output") {
-                    output_dir.join(output_name)
-                } else {
-                    output_dir.join(path.file_name().unwrap())
-                };
-                let file_type = if let Ok(file_type) = table.get_string("filetype") {
-                    match file_type {
-                    "html" => FileType::Html,
-                    "css" => FileType::Css,
-                    "gmi" | "gemtext" => FileType::Gemtext,
-                    "markdown" | "md" => FileType::Markdown,
-                    _ => return Err(format!("Target `{path:?}` had an unexpected filetype: {file_type}\n`filetype` must be one of html, css, or gemtext").into())
-                }
-                } else {
-                    FileType::from(path.as_path())
-                };
-
-                let target = Target {
-                    path,
-                    output,
-                    mode,
-                    file_type,
-                };
-                let worker = thread::spawn(move || build_target(target));
-                tasks.push(worker);
-            }
+    let index_path = dir.join("index.html");
+    if !index_path.exists() {
+        fs::write(&index_path, INIT_INDEX)
+            .map_err(|err| io_error("Failed to write index.html", &index_path, err))?;
+        created.push(index_path);
+    }
+
+    if args.verbosity == Verbosity::Verbose {
+        for path in created {
+            println!("Wrote {}", path.display());
         }
-        Err(e) => match e {
-            TomlGetError::InvalidKey => {
-                return Err("No targets specified. See the GitHub for an example on setting up a webby project: https://github.com/bright-shard/webby".into());
-            }
-            TomlGetError::TypeMismatch(_, _) => {
-                return Err("The 'target' entry has to an array in webby.toml".into());
+    }
+    if args.verbosity > Verbosity::Quiet {
+        println!("Created a new webby project in {}", dir.display());
+    }
+    Ok(true)
+}
+
+/// Builds the project, then rebuilds it whenever a file changes.
+fn watch(args: &Args) -> Result<bool, WebbyError> {
+    let config_path = args.config_path()?;
+
+    if args.verbosity > Verbosity::Quiet {
+        println!(
+            "Watching {} for changes...",
+            root_of(&config_path).display()
+        );
+    }
+    watch::watch(&config_path, args.overrides.clone(), |rebuild| {
+        print_rebuild(args, rebuild)
+    })
+}
+
+/// Serves the output directory on localhost, rebuilding the project and
+/// reloading open pages whenever a file changes.
+fn serve(args: &Args) -> Result<bool, WebbyError> {
+    let config_path = args.config_path()?;
+
+    let (mut watcher, rebuild) = Watcher::new(&config_path, args.overrides.clone());
+    print_rebuild(args, &rebuild);
+    let output_dir = watcher
+        .config()
+        .map(|config| config.output_dir.clone())
+        .unwrap_or_else(|| root_of(&config_path).join("webby"));
+
+    let reloader = serve::serve(("127.0.0.1", args.port), &output_dir)?;
+    if args.verbosity > Verbosity::Quiet {
+        println!(
+            "Serving {} at http://127.0.0.1:{}/",
+            output_dir.display(),
+            args.port
+        );
+    }
+
+    loop {
+        thread::sleep(watch::POLL_INTERVAL);
+
+        if let Some(rebuild) = watcher.poll() {
+            print_rebuild(args, &rebuild);
+            if let Some(config) = watcher.config() {
+                reloader.set_output_dir(&config.output_dir);
             }
-        },
+            reloader.reload();
+        }
     }
+}
 
-    Ok(tasks)
+fn print_rebuild(args: &Args, rebuild: &Rebuild) {
+    for err in &rebuild.errors {
+        report(err);
+    }
+    if args.verbosity == Verbosity::Quiet {
+        return;
+    }
+
+    if rebuild.config_reloaded {
+        println!("Loaded webby.toml");
+    }
+    if args.verbosity == Verbosity::Verbose {
+        for path in &rebuild.written {
+            println!("Wrote {}", path.display());
+        }
+    }
+    println!(
+        "Rebuilt {} target(s) with {} error(s)",
+        rebuild.rebuilt.len(),
+        rebuild.errors.len()
+    );
+}
+
+/// The folder a `webby.toml` is in.
+fn root_of(config_path: &Path) -> &Path {
+    config_path.parent().unwrap_or(Path::new("."))
+}
+
+/// Prints an error and everything that caused it.
+fn report(err: &WebbyError) {
+    eprintln!("error: {err}");
+    let mut cause = err.source();
+    while let Some(err) = cause {
+        eprintln!("  caused by: {err}");
+        cause = err.source();
+    }
 }
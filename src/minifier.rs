@@ -1,5 +1,28 @@
 mod css;
 mod html;
+mod js;
 
-pub use css::minify_css;
-pub use html::minify_html;
+pub use css::{minify_css, strip_css_comments};
+pub use html::{minify_html, strip_html_comments};
+pub(crate) use html::{tokenize, Token};
+pub use js::{minify_js, strip_js_comments};
+
+/// How compiled HTML, CSS and JavaScript files are cleaned up. `#!MINIFY`
+/// ignores these and always minifies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MinifyOptions {
+    /// Run HTML, CSS and JavaScript through the minifiers. Minified files
+    /// never have comments.
+    pub minify: bool,
+    /// Remove comments from HTML, CSS and JavaScript, even if they aren't
+    /// minified.
+    pub strip_comments: bool,
+}
+impl Default for MinifyOptions {
+    fn default() -> Self {
+        Self {
+            minify: true,
+            strip_comments: true,
+        }
+    }
+}
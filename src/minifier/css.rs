@@ -77,3 +77,41 @@ pub fn minify_css(source: &str) -> String {
 
     out
 }
+
+/// Removes comments from CSS without minifying it.
+pub fn strip_css_comments(source: &str) -> String {
+    let mut out = String::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '/' if chars.peek().copied() == Some('*') => {
+                chars.next();
+
+                while let Some(char) = chars.next() {
+                    if char == '*' && chars.peek().copied() == Some('/') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            '\'' | '"' => {
+                out.push(char);
+
+                while let Some(subchar) = chars.next() {
+                    out.push(subchar);
+                    if subchar == char {
+                        break;
+                    } else if subchar == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            out.push(escaped);
+                        }
+                    }
+                }
+            }
+            _ => out.push(char),
+        }
+    }
+
+    out
+}
@@ -1,4 +1,7 @@
-use crate::{line_number_of_offset, minifier, Cow};
+use {
+    crate::{minifier, Cow, ErrorKind, WebbyError},
+    std::path::Path,
+};
 
 macro_rules! log {
     ($($t:tt)*) => {
@@ -7,11 +10,7 @@ macro_rules! log {
     };
 }
 
-pub fn minify_html(
-    source_path: &str,
-    source: &str,
-    original: &str,
-) -> Result<String, Cow<'static>> {
+pub fn minify_html(source_path: &Path, source: &str, original: &str) -> Result<String, WebbyError> {
     let mut result = String::new();
     let mut handled_bytes = 0;
 
@@ -51,7 +50,8 @@ pub fn minify_html(
 ///    `a`, `h1`, etc), while content tags store other HTMl elements (`head`,
 ///    `body`, etc). If the tag is not closed, this function just returns the
 ///    tag. If the tag is a style tag, it will be run through the CSS minifier.
-///    Script tags will only be minified with the `js-minify` feature enabled.
+///    Script tags are run through the JS minifier, unless their `type` isn't
+///    JavaScript or JSON (like a `text/html` template).
 /// 6. If the tag is a text tag, the only minification that will occur is
 ///    removing newlines around tags inside it. Any content tags inside the text
 ///    tag will be minified as normal for a content tag.
@@ -61,28 +61,45 @@ pub fn minify_html(
 ///    minifier.
 /// 8. If the tag is a <![CDATA[]]> tag, it will not be minimised. Content
 ///    should be explicitly minimised with the #!MINIMISE macro.
-///
-/// # TODO
-/// - Find a decent JS minifier, add it as a dep, and feature flag it. JS is
-///   too complicated to write a minifier for, when I don't even use it.
 fn handle_tag<'a>(
-    source_path: &'a str,
+    source_path: &'a Path,
     source: &'a str,
     error_meta: (&'a str, usize),
-) -> Result<(Cow<'a>, usize), Cow<'static>> {
-    if source
-        .chars()
-        .next()
-        .map(|char| char.is_whitespace())
-        .unwrap_or(false)
-    {
-        return Ok((Cow::Borrowed(""), 0));
+) -> Result<(Cow<'a>, usize), WebbyError> {
+    if !source.starts_with('<') {
+        // Text outside of any tag, like the text in an included partial.
+        // Whitespace is collapsed, and whitespace with a newline in it is
+        // dropped if it's next to a tag.
+        let end = source.find('<').unwrap_or(source.len());
+        let text = &source[..end];
+        let mut output = String::new();
+        let leading = &text[..text.len() - text.trim_start().len()];
+        if !leading.is_empty() && !leading.contains('\n') && !text.trim().is_empty() {
+            output.push(' ');
+        }
+        for (idx, word) in text.split_whitespace().enumerate() {
+            if idx > 0 {
+                output.push(' ');
+            }
+            output += word;
+        }
+        let trailing = &text[text.trim_end().len()..];
+        if !trailing.is_empty()
+            && !trailing.contains('\n')
+            && !text.trim().is_empty()
+            && end < source.len()
+        {
+            output.push(' ');
+        }
+        return Ok((Cow::Owned(output), end - 1));
     } else if source.starts_with("<!--") {
         let Some(ending) = source.find("-->") else {
-            return Err(Cow::Owned(format!(
-                "HTML error: Unclosed HTML comment at {source_path}:{}",
-                line_number_of_offset(error_meta.0, error_meta.1)
-            )));
+            return Err(html_error(
+                "Unclosed HTML comment",
+                source_path,
+                error_meta.0,
+                error_meta.1,
+            ));
         };
         return Ok((Cow::Borrowed(""), ending + 2));
     }
@@ -141,10 +158,12 @@ fn handle_tag<'a>(
 
     if tag_name == "![CDATA[" {
         let Some(end) = source[tag_name_end..].find("]]>") else {
-            return Err(Cow::Owned(format!(
-                "Unclosed CDATA tag at {source_path:?}{}",
-                line_number_of_offset(error_meta.0, error_meta.1 + tag_name_end)
-            )));
+            return Err(html_error(
+                "Unclosed CDATA tag",
+                source_path,
+                error_meta.0,
+                error_meta.1 + tag_name_end,
+            ));
         };
         return Ok((Cow::Borrowed(&source[..end + "]]>".len()]), end));
     }
@@ -267,10 +286,12 @@ fn handle_tag<'a>(
                     output.push(char);
                     loop {
                         let Some((_, next)) = chars.next() else {
-                            return Err(Cow::Owned(format!(
-                                "Unclosed quotation in HTML property at {source_path}:{}",
-                                line_number_of_offset(error_meta.0, error_meta.1 + idx)
-                            )));
+                            return Err(html_error(
+                                "Unclosed quotation in HTML property",
+                                source_path,
+                                error_meta.0,
+                                error_meta.1 + idx,
+                            ));
                         };
                         if next == '\\' {
                             if let Some((_, next)) = chars.next() {
@@ -290,10 +311,12 @@ fn handle_tag<'a>(
                     output.push(char);
                     loop {
                         let Some((idx, char)) = chars.next() else {
-                            return Err(Cow::Owned(format!(
-                                "Unclosed quotation in HTML property at {source_path}:{}",
-                                line_number_of_offset(error_meta.0, error_meta.1 + idx)
-                            )));
+                            return Err(html_error(
+                                "Unclosed quotation in HTML property",
+                                source_path,
+                                error_meta.0,
+                                error_meta.1 + idx,
+                            ));
                         };
 
                         if char == '/' && chars.peek().map(|(_, char)| *char) == Some('>') {
@@ -350,7 +373,6 @@ fn handle_tag<'a>(
     }
 
     if tag_name == "script" || tag_name == "style" {
-        // TODO: Actually parse and minify JS
         let closing_tag = if tag_name == "script" {
             "</script>"
         } else {
@@ -361,15 +383,21 @@ fn handle_tag<'a>(
         };
 
         let Some(closing) = source[search_start_idx..].find(closing_tag) else {
-            return Err(Cow::Owned(format!(
-                "Unclosed style or script tag at {source_path}:{}",
-                line_number_of_offset(error_meta.0, error_meta.1 + search_start_idx)
-            )));
+            return Err(html_error(
+                "Unclosed style or script tag",
+                source_path,
+                error_meta.0,
+                error_meta.1 + search_start_idx,
+            ));
         };
         let closing = search_start_idx + closing + closing_tag.len();
 
         if tag_name == "style" {
             output += &minifier::minify_css(&source[search_start_idx..closing]);
+        } else if is_javascript(&output) {
+            let body = &source[search_start_idx..closing - closing_tag.len()];
+            output += &minifier::minify_js(body);
+            output += closing_tag;
         } else {
             output += &source[search_start_idx..closing];
         }
@@ -470,10 +498,12 @@ fn handle_tag<'a>(
 
                 loop {
                     let Some((idx, char)) = chars.next() else {
-                        return Err(Cow::Owned(format!(
-                            "Unclosed HTML closing tag at {source_path}:{}",
-                            line_number_of_offset(error_meta.0, error_meta.1 + next_idx)
-                        )));
+                        return Err(html_error(
+                            "Unclosed HTML closing tag",
+                            source_path,
+                            error_meta.0,
+                            error_meta.1 + next_idx,
+                        ));
                     };
 
                     if char == '>' {
@@ -502,7 +532,11 @@ fn handle_tag<'a>(
                     }
                 }
             } else if !next_char.is_whitespace() {
-                let (subtag, used) = handle_tag(source_path, &source[byte_idx..], error_meta)?;
+                let (subtag, used) = handle_tag(
+                    source_path,
+                    &source[byte_idx..],
+                    (error_meta.0, error_meta.1 + byte_idx),
+                )?;
                 log!("  Found subtag `{subtag}`. Ends at {used}, current char is {next_idx}.");
                 let used = used + byte_idx;
 
@@ -542,9 +576,261 @@ fn handle_tag<'a>(
     Ok((Cow::Owned(output), source.len() - 1))
 }
 
+/// Removes comments from HTML without minifying it. CSS and JavaScript
+/// comments in `<style>` and `<script>` tags are removed too.
+pub fn strip_html_comments(source: &str) -> String {
+    let mut out = String::new();
+    let mut rest = source;
+
+    while let Some(idx) = rest.find('<') {
+        out += &rest[..idx];
+        rest = &rest[idx..];
+
+        if rest.starts_with("<!--") {
+            match rest.find("-->") {
+                Some(end) => rest = &rest[end + "-->".len()..],
+                None => rest = "",
+            }
+        } else if let Some(tag) = ["script", "style"]
+            .into_iter()
+            .find(|tag| rest[1..].starts_with(tag))
+        {
+            let closing = format!("</{tag}>");
+            let Some(open_end) = rest.find('>') else {
+                break;
+            };
+            let close = rest[open_end..]
+                .find(&closing)
+                .map(|close| open_end + close)
+                .unwrap_or(rest.len());
+            out += &rest[..open_end + 1];
+            if tag == "style" {
+                out += &minifier::strip_css_comments(&rest[open_end + 1..close]);
+            } else if is_javascript(&rest[..open_end + 1]) {
+                out += &minifier::strip_js_comments(&rest[open_end + 1..close]);
+            } else {
+                out += &rest[open_end + 1..close];
+            }
+            rest = &rest[close..];
+            if rest.starts_with(&closing) {
+                out += &closing;
+                rest = &rest[closing.len()..];
+            }
+        } else {
+            out.push('<');
+            rest = &rest[1..];
+        }
+    }
+    out += rest;
+
+    out
+}
+
+/// A piece of HTML, from [`tokenize`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Token<'a> {
+    /// Text between tags. Entities like `&amp;` aren't decoded. The contents of
+    /// `<script>`, `<style>` and CDATA sections are a single text token.
+    Text(&'a str),
+    /// An opening tag, like `<a href="/">`. Tag names are lowercase, and
+    /// attribute values have their quotes removed.
+    Open {
+        name: String,
+        attributes: Vec<(&'a str, &'a str)>,
+        self_closing: bool,
+    },
+    /// A closing tag, like `</a>`.
+    Close(String),
+}
+
+/// Splits HTML into tags and text, following the same rules as the minifier.
+/// Comments and doctypes are dropped.
+pub(crate) fn tokenize<'a>(
+    source_path: &Path,
+    source: &'a str,
+) -> Result<Vec<Token<'a>>, WebbyError> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+
+    while offset < source.len() {
+        let rest = &source[offset..];
+        // A `<` followed by whitespace isn't a tag
+        let is_tag = rest.starts_with('<')
+            && rest[1..]
+                .chars()
+                .next()
+                .is_some_and(|char| !char.is_whitespace());
+        if !is_tag {
+            // The text's first character may be a `<` that isn't a tag, so
+            // the search for the next one starts after it
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let end = rest[first..]
+                .find('<')
+                .map_or(rest.len(), |idx| idx + first);
+            tokens.push(Token::Text(&rest[..end]));
+            offset += end;
+            continue;
+        }
+
+        if rest.starts_with("<!--") {
+            let Some(end) = rest.find("-->") else {
+                return Err(html_error(
+                    "Unclosed HTML comment",
+                    source_path,
+                    source,
+                    offset,
+                ));
+            };
+            offset += end + "-->".len();
+            continue;
+        } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let Some(end) = cdata.find("]]>") else {
+                return Err(html_error(
+                    "Unclosed CDATA tag",
+                    source_path,
+                    source,
+                    offset,
+                ));
+            };
+            tokens.push(Token::Text(&cdata[..end]));
+            offset += "<![CDATA[".len() + end + "]]>".len();
+            continue;
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            offset += rest.find('>').map_or(rest.len(), |end| end + 1);
+            continue;
+        }
+
+        let Some(tag_end) = tag_end(rest) else {
+            return Err(html_error("Unclosed HTML tag", source_path, source, offset));
+        };
+        let tag = &rest[1..tag_end];
+        offset += tag_end + 1;
+
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(Token::Close(name.trim().to_ascii_lowercase()));
+            continue;
+        }
+        let (tag, self_closing) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        let attributes = parse_attributes(&tag[name_end..]);
+
+        // Scripts and styles aren't HTML, so they're kept as one piece of
+        // text
+        let raw = !self_closing && (name == "script" || name == "style");
+        tokens.push(Token::Open {
+            name: name.clone(),
+            attributes,
+            self_closing,
+        });
+        if raw {
+            let closing = format!("</{name}");
+            let rest = &source[offset..];
+            let end = rest
+                .to_ascii_lowercase()
+                .find(&closing)
+                .unwrap_or(rest.len());
+            if end > 0 {
+                tokens.push(Token::Text(&rest[..end]));
+            }
+            offset += end;
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Finds the `>` that ends the tag at the start of `source`, skipping over
+/// quoted attribute values.
+fn tag_end(source: &str) -> Option<usize> {
+    let mut quote = None;
+    for (idx, char) in source.char_indices() {
+        match (char, quote) {
+            ('"' | '\'', None) => quote = Some(char),
+            (char, Some(open)) if char == open => quote = None,
+            ('>', None) => return Some(idx),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses the attributes in a tag, like `href="/" hidden`. Attributes without
+/// a value have an empty one.
+fn parse_attributes(mut source: &str) -> Vec<(&str, &str)> {
+    let mut attributes = Vec::new();
+    loop {
+        source = source.trim_start();
+        if source.is_empty() {
+            break;
+        }
+
+        let name_end = source
+            .find(|char: char| char.is_whitespace() || char == '=')
+            .unwrap_or(source.len());
+        let name = &source[..name_end];
+        source = source[name_end..].trim_start();
+        let Some(value) = source.strip_prefix('=') else {
+            attributes.push((name, ""));
+            continue;
+        };
+
+        let value = value.trim_start();
+        let (value, rest) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => match value[1..].find(quote) {
+                Some(end) => (&value[1..end + 1], &value[end + 2..]),
+                None => (&value[1..], ""),
+            },
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        attributes.push((name, value));
+        source = rest;
+    }
+
+    attributes
+}
+
+/// If a `<script>` tag holds JavaScript or JSON, going by its `type`, rather
+/// than something like a template that shouldn't be minified.
+fn is_javascript(open_tag: &str) -> bool {
+    let tag = open_tag
+        .trim_start_matches('<')
+        .trim_end_matches(['>', '/']);
+    // The attributes start after the tag's name
+    let attributes = tag.find(char::is_whitespace).map_or("", |end| &tag[end..]);
+    let Some((_, value)) = parse_attributes(attributes)
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("type"))
+    else {
+        return true;
+    };
+
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "" | "module"
+            | "importmap"
+            | "text/javascript"
+            | "application/javascript"
+            | "text/ecmascript"
+            | "application/ecmascript"
+            | "application/json"
+            | "application/ld+json"
+    )
+}
+
+fn html_error(message: &str, source_path: &Path, original: &str, offset: usize) -> WebbyError {
+    WebbyError::new(ErrorKind::HtmlSyntax, message).at(source_path, original, offset)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::minifier::minify_html;
+    use {crate::minifier::minify_html, std::path::Path};
 
     struct Tester {
         name: &'static str,
@@ -554,7 +840,7 @@ mod tests {
     impl Tester {
         fn test(self) {
             log!("\nSTARTING TEST '{}'", self.name);
-            let result = minify_html("test/path", self.source, self.source).unwrap();
+            let result = minify_html(Path::new("test/path"), self.source, self.source).unwrap();
             assert_eq!(&result, self.expected, "Test name: {}", self.name);
         }
     }
@@ -597,6 +883,21 @@ mod tests {
                 source: "<p string='string1 string2n\'t'>hewwo</p>",
                 expected: "<p string='string1 string2n\'t'>hewwo</p>",
             },
+            Tester {
+                name: "Text outside of tags",
+                source: "some  text\n<b>bold</b> more <i>words</i>\n",
+                expected: "some text<b>bold</b> more <i>words</i>",
+            },
+            Tester {
+                name: "Text outside of tags keeps spaces next to tags",
+                source: "Hello, <b>world</b>!  Bye <i>now</i>",
+                expected: "Hello, <b>world</b>! Bye <i>now</i>",
+            },
+            Tester {
+                name: "Scripts are minified by their type attribute",
+                source: "<script data-type=\"text/html\">let a = 1;</script>\n<script x-type=module type='text/html'>  <p>kept</p>  </script>",
+                expected: "<script data-type=\"text/html\">let a=1;</script><script x-type=module type='text/html'>  <p>kept</p>  </script>",
+            },
             Tester {
                 name: "Unclosed Elements",
                 source: "<body>  <br/><img src='https://example.com/img.png'><p>hello</p>\n<br/></body>",
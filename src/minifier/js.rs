@@ -0,0 +1,391 @@
+//! A JavaScript minifier. Source is split into tokens, then joined back
+//! together with only the whitespace that changes what the code means: spaces
+//! between words, and newlines that automatic semicolon insertion relies on.
+//! Strings, template literals and regexes are copied as they are.
+
+/// Keywords a regex can follow. After any other word, `/` is division.
+const BEFORE_REGEX: &[&str] = &[
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "of",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+    "await",
+];
+/// Keywords that can't have a line break after them without ending the
+/// statement, like `return`.
+const RESTRICTED: &[&str] = &["return", "break", "continue", "throw", "yield", "async"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// An identifier, keyword or number.
+    Word,
+    /// An operator or other punctuation.
+    Punct,
+    /// A string, template literal, regex or hashbang, which is copied as it
+    /// is.
+    Literal,
+}
+
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    /// The whitespace and comments before the token.
+    gap: &'a str,
+}
+
+pub fn minify_js(source: &str) -> String {
+    let (tokens, _) = tokenize(source);
+    let mut out = String::with_capacity(source.len());
+
+    for (idx, token) in tokens.iter().enumerate() {
+        if idx > 0 && !token.gap.is_empty() {
+            let prev = &tokens[idx - 1];
+            if has_line_break(token.gap) && needs_line_break(prev, token) {
+                out.push('\n');
+            } else if needs_space(prev, token) {
+                out.push(' ');
+            }
+        }
+        out += token.text;
+    }
+
+    out
+}
+
+/// Removes comments from JavaScript without minifying it.
+pub fn strip_js_comments(source: &str) -> String {
+    let (tokens, end) = tokenize(source);
+    let mut out = String::with_capacity(source.len());
+
+    for token in &tokens {
+        strip_gap(token.gap, &mut out);
+        out += token.text;
+    }
+    strip_gap(end, &mut out);
+
+    out
+}
+
+/// Splits JavaScript into tokens. Also returns the whitespace and comments
+/// after the last token.
+fn tokenize(source: &str) -> (Vec<Token<'_>>, &str) {
+    let bytes = source.as_bytes();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut pos = 0;
+
+    // A `#!` line at the very start is a hashbang, not code
+    if source.starts_with("#!") {
+        pos = source.find('\n').unwrap_or(source.len());
+        tokens.push(Token {
+            kind: Kind::Literal,
+            text: &source[..pos],
+            gap: "",
+        });
+    }
+
+    loop {
+        let gap_start = pos;
+        pos = skip_gap(source, pos);
+        let gap = &source[gap_start..pos];
+        let Some(&byte) = bytes.get(pos) else {
+            return (tokens, gap);
+        };
+
+        let start = pos;
+        let kind = match byte {
+            b'"' | b'\'' => {
+                pos = skip_string(bytes, pos);
+                Kind::Literal
+            }
+            b'`' => {
+                pos = skip_template(bytes, pos);
+                Kind::Literal
+            }
+            b'/' if regex_allowed(tokens.last()) => {
+                pos = skip_regex(bytes, pos);
+                Kind::Literal
+            }
+            byte if is_word_byte(byte) => {
+                while bytes.get(pos).is_some_and(|byte| is_word_byte(*byte)) {
+                    pos += 1;
+                }
+                Kind::Word
+            }
+            b'+' | b'-' if bytes.get(pos + 1) == Some(&byte) => {
+                pos += 2;
+                Kind::Punct
+            }
+            _ => {
+                pos += 1;
+                Kind::Punct
+            }
+        };
+        tokens.push(Token {
+            kind,
+            text: &source[start..pos],
+            gap,
+        });
+    }
+}
+
+/// Letters, digits, `_`, `$`, `\` (for escapes like `\u0061`), and anything
+/// that isn't ASCII, which can only be part of an identifier.
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'$' | b'\\') || byte >= 0x80
+}
+
+/// Skips whitespace and comments, returning where they end.
+fn skip_gap(source: &str, mut pos: usize) -> usize {
+    loop {
+        let rest = &source[pos..];
+        if let Some(len) = comment_len(rest) {
+            pos += len;
+        } else if let Some(char) = rest.chars().next().filter(|char| char.is_whitespace()) {
+            pos += char.len_utf8();
+        } else {
+            return pos;
+        }
+    }
+}
+
+/// The length of the comment at the start of `source`, if it starts with one.
+/// Line comments end before their newline.
+fn comment_len(source: &str) -> Option<usize> {
+    if source.starts_with("//") {
+        Some(source.find(['\n', '\r']).unwrap_or(source.len()))
+    } else {
+        let body = source.strip_prefix("/*")?;
+        Some(body.find("*/").map_or(source.len(), |end| end + 4))
+    }
+}
+
+/// Skips a string starting at `pos`, returning where it ends.
+fn skip_string(bytes: &[u8], mut pos: usize) -> usize {
+    let quote = bytes[pos];
+    pos += 1;
+    while let Some(&byte) = bytes.get(pos) {
+        pos += 1;
+        match byte {
+            b'\\' => pos += 1,
+            byte if byte == quote => break,
+            _ => {}
+        }
+    }
+    pos.min(bytes.len())
+}
+
+/// Skips a template literal starting at `pos`, including the expressions in
+/// it, returning where it ends.
+fn skip_template(bytes: &[u8], mut pos: usize) -> usize {
+    pos += 1;
+    while let Some(&byte) = bytes.get(pos) {
+        pos += 1;
+        match byte {
+            b'\\' => pos += 1,
+            b'`' => break,
+            b'$' if bytes.get(pos) == Some(&b'{') => {
+                pos += 1;
+                // How many braces deep into the expression the template is
+                let mut depth = 1;
+                while let Some(&byte) = bytes.get(pos) {
+                    match byte {
+                        b'"' | b'\'' => pos = skip_string(bytes, pos),
+                        b'`' => pos = skip_template(bytes, pos),
+                        b'{' => {
+                            depth += 1;
+                            pos += 1;
+                        }
+                        b'}' => {
+                            depth -= 1;
+                            pos += 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => pos += 1,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    pos.min(bytes.len())
+}
+
+/// Skips a regex starting at `pos`, including its flags, returning where it
+/// ends.
+fn skip_regex(bytes: &[u8], mut pos: usize) -> usize {
+    pos += 1;
+    // `/` doesn't end the regex inside a character class, like `[/]`
+    let mut in_class = false;
+    while let Some(&byte) = bytes.get(pos) {
+        pos += 1;
+        match byte {
+            b'\\' => pos += 1,
+            b'[' => in_class = true,
+            b']' => in_class = false,
+            b'/' if !in_class => break,
+            b'\n' => break,
+            _ => {}
+        }
+    }
+    while bytes.get(pos).is_some_and(u8::is_ascii_alphabetic) {
+        pos += 1;
+    }
+    pos.min(bytes.len())
+}
+
+/// If a `/` after `prev` starts a regex, instead of being division.
+fn regex_allowed(prev: Option<&Token>) -> bool {
+    match prev {
+        None => true,
+        Some(prev) => match prev.kind {
+            Kind::Word => BEFORE_REGEX.contains(&prev.text),
+            Kind::Punct => !matches!(prev.text, ")" | "]" | "}" | "++" | "--"),
+            Kind::Literal => false,
+        },
+    }
+}
+
+fn has_line_break(gap: &str) -> bool {
+    gap.contains(['\n', '\r', '\u{2028}', '\u{2029}'])
+}
+
+/// If removing the line break between two tokens could change where
+/// automatic semicolon insertion ends a statement.
+fn needs_line_break(prev: &Token, next: &Token) -> bool {
+    if prev.kind == Kind::Word && RESTRICTED.contains(&prev.text) {
+        return true;
+    }
+    let ends_statement = match prev.kind {
+        Kind::Word | Kind::Literal => true,
+        Kind::Punct => matches!(prev.text, ")" | "]" | "}" | "++" | "--"),
+    };
+    // Tokens that always continue the statement before them
+    let continues_statement = next.kind == Kind::Punct
+        && matches!(
+            next.text,
+            "." | ","
+                | ";"
+                | ":"
+                | "?"
+                | ")"
+                | "]"
+                | "}"
+                | "("
+                | "["
+                | "="
+                | "*"
+                | "%"
+                | "&"
+                | "|"
+                | "^"
+                | "<"
+                | ">"
+                | "/"
+                | "+"
+                | "-"
+        );
+
+    ends_statement && !continues_statement
+}
+
+/// If two tokens would run together without a space between them.
+fn needs_space(prev: &Token, next: &Token) -> bool {
+    let (last, first) = (
+        prev.text.as_bytes()[prev.text.len() - 1],
+        next.text.as_bytes()[0],
+    );
+
+    (is_word_byte(last) && is_word_byte(first))
+        // Regex flags
+        || (prev.kind == Kind::Literal && prev.text.starts_with('/') && is_word_byte(first))
+        // `a + +b`, `a - --b` and `a / /re/`
+        || (last == first && matches!(last, b'+' | b'-' | b'/'))
+        // `<!--` and `-->` start HTML comments in scripts
+        || (last == b'<' && first == b'!')
+        || (last == b'-' && first == b'>')
+        // `1 .toString()`
+        || (prev.kind == Kind::Word && prev.text.bytes().all(|byte| byte.is_ascii_digit()) && first == b'.')
+}
+
+/// Writes whitespace and comments without the comments. Comments become a
+/// space, or a newline if they had one in them.
+fn strip_gap(gap: &str, out: &mut String) {
+    let mut pos = 0;
+    while pos < gap.len() {
+        let rest = &gap[pos..];
+        let Some(len) = comment_len(rest) else {
+            let char = rest.chars().next().unwrap();
+            out.push(char);
+            pos += char.len_utf8();
+            continue;
+        };
+
+        let comment = &rest[..len];
+        if comment.starts_with("/*") {
+            out.push(if has_line_break(comment) { '\n' } else { ' ' });
+        }
+        pos += len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{minify_js, strip_js_comments};
+
+    #[test]
+    fn test() {
+        let cases = [
+            (
+                "Whitespace and comments",
+                "// comment\nconst  x = 1 ; /* block */ let y=x  +  2;",
+                "const x=1;let y=x+2;",
+            ),
+            (
+                "Automatic semicolon insertion",
+                "let a = 1\nlet b = a\n++b\nfoo()\n[1, 2].map(f)\nreturn\nx",
+                "let a=1\nlet b=a\n++b\nfoo()[1,2].map(f)\nreturn\nx",
+            ),
+            (
+                "Operators that would run together",
+                "a + +b; c - --d; e = f / /re/g.source; 1 .toString()",
+                "a+ +b;c- --d;e=f/ /re/g.source;1 .toString()",
+            ),
+            (
+                "Regexes and division",
+                "let r = /[/]\\/ x/g; let d = a / b / c; return /x y/.test(s)",
+                "let r=/[/]\\/ x/g;let d=a/b/c;return/x y/.test(s)",
+            ),
+            (
+                "Strings and templates",
+                "s = 'a  // b' + \"c /* d */\" + `e  ${ f({ g: `h  ${i}` }) }  j`",
+                "s='a  // b'+\"c /* d */\"+`e  ${ f({ g: `h  ${i}` }) }  j`",
+            ),
+            ("Regex flags before a word", "x = /a/ in y", "x=/a/ in y"),
+            (
+                "Hashbang",
+                "#!/usr/bin/env node\nconsole.log(1)",
+                "#!/usr/bin/env node\nconsole.log(1)",
+            ),
+        ];
+
+        for (name, source, expected) in cases {
+            assert_eq!(minify_js(source), expected, "Test name: {name}");
+        }
+
+        assert_eq!(
+            strip_js_comments("let a = 1; // one\n/* two\n */ let b = '//';\n"),
+            "let a = 1; \n\n let b = '//';\n"
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! A small HTTP server for previewing a site while working on it.
+//!
+//! This is only meant for local development. It serves files from the output
+//! directory, and injects a short script into HTML pages that reloads them
+//! whenever [`Reloader::reload`] is called (usually after a rebuild). The
+//! script is only added to responses - files on disk are never changed.
+
+use {
+    crate::{cache::CACHE_FILE, ErrorKind, WebbyError},
+    std::{
+        fs,
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream, ToSocketAddrs},
+        path::{Component, Path, PathBuf},
+        sync::{Arc, Condvar, Mutex, RwLock},
+        thread,
+        time::Duration,
+    },
+};
+
+/// The URL pages listen to for reload events.
+const EVENTS_URL: &str = "/__webby/live-reload";
+/// The script injected into HTML pages to make them reload. Listens to
+/// [`EVENTS_URL`].
+const RELOAD_SCRIPT: &str =
+    "<script>new EventSource(\"/__webby/live-reload\").onmessage=()=>location.reload()</script>";
+/// How often to ping pages waiting for a reload, so closed connections are
+/// noticed and cleaned up.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+struct State {
+    output_dir: RwLock<PathBuf>,
+    /// Incremented every time pages should reload.
+    generation: Mutex<u64>,
+    reloaded: Condvar,
+}
+
+/// Controls a running server.
+#[derive(Clone)]
+pub struct Reloader {
+    state: Arc<State>,
+}
+impl Reloader {
+    /// Tells every open page to reload.
+    pub fn reload(&self) {
+        *self.state.generation.lock().unwrap() += 1;
+        self.state.reloaded.notify_all();
+    }
+
+    /// Changes the folder files are served from.
+    pub fn set_output_dir(&self, output_dir: &Path) {
+        *self.state.output_dir.write().unwrap() = output_dir.to_path_buf();
+    }
+}
+
+/// Starts serving files from `output_dir` at `addr` on a background thread.
+pub fn serve(addr: impl ToSocketAddrs, output_dir: &Path) -> Result<Reloader, WebbyError> {
+    let listener = TcpListener::bind(addr).map_err(|err| {
+        WebbyError::new(ErrorKind::Io, "Failed to start the web server").caused_by(err)
+    })?;
+    let state = Arc::new(State {
+        output_dir: RwLock::new(output_dir.to_path_buf()),
+        generation: Mutex::new(0),
+        reloaded: Condvar::new(),
+    });
+
+    let server_state = state.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|stream| stream.ok()) {
+            let state = server_state.clone();
+            thread::spawn(move || handle_connection(stream, &state));
+        }
+    });
+
+    Ok(Reloader { state })
+}
+
+fn handle_connection(mut stream: TcpStream, state: &State) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Skip the headers; nothing in them changes the response
+    let mut header = String::new();
+    loop {
+        header.clear();
+        match reader.read_line(&mut header) {
+            Ok(0) | Err(_) => return,
+            Ok(_) if header.trim().is_empty() => break,
+            Ok(_) => {}
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        let _ = respond(&mut stream, "400 Bad Request", "text/plain", b"Bad request");
+        return;
+    };
+    if method != "GET" && method != "HEAD" {
+        let _ = respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Method not allowed",
+        );
+        return;
+    }
+
+    let url_path = target.split(['?', '#']).next().unwrap_or("/");
+    if url_path == EVENTS_URL {
+        stream_reloads(stream, state);
+        return;
+    }
+
+    let output_dir = state.output_dir.read().unwrap().clone();
+    let Some(relative) = decode_path(url_path) else {
+        let _ = respond(&mut stream, "400 Bad Request", "text/plain", b"Bad request");
+        return;
+    };
+    // The build cache is in the output directory, but isn't part of the site
+    let is_cache = relative == Path::new(CACHE_FILE);
+    let mut path = output_dir.join(relative);
+
+    if path.is_dir() {
+        if !url_path.ends_with('/') {
+            let _ = write!(
+                stream,
+                "HTTP/1.1 301 Moved Permanently\r\nLocation: {url_path}/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+            return;
+        }
+        path = path.join("index.html");
+    } else if !path.exists() && path.with_extension("html").is_file() {
+        path = path.with_extension("html");
+    }
+
+    let (status, path) = if path.is_file() && !is_cache {
+        ("200 OK", path)
+    } else {
+        ("404 Not Found", output_dir.join("404.html"))
+    };
+    let Ok(mut body) = fs::read(&path) else {
+        let _ = respond(&mut stream, status, "text/plain", b"Not found");
+        return;
+    };
+
+    let mime = mime_type(&path);
+    if mime.starts_with("text/html") {
+        body = inject_reload_script(body);
+    }
+    if method == "HEAD" {
+        let _ = write_head(&mut stream, status, mime, body.len());
+    } else {
+        let _ = respond(&mut stream, status, mime, &body);
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, mime: &str, body: &[u8]) -> std::io::Result<()> {
+    write_head(stream, status, mime, body.len())?;
+    stream.write_all(body)
+}
+
+fn write_head(stream: &mut TcpStream, status: &str, mime: &str, len: usize) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {mime}\r\nContent-Length: {len}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+    )
+}
+
+/// Keeps a connection open as a server-sent event stream, sending an event
+/// every time the site is rebuilt.
+fn stream_reloads(mut stream: TcpStream, state: &State) {
+    if write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\nConnection: keep-alive\r\n\r\n"
+    )
+    .is_err()
+    {
+        return;
+    }
+
+    let mut last_seen = *state.generation.lock().unwrap();
+    loop {
+        let generation = state.generation.lock().unwrap();
+        let (generation, _) = state
+            .reloaded
+            .wait_timeout_while(generation, KEEP_ALIVE, |generation| {
+                *generation == last_seen
+            })
+            .unwrap();
+        let current = *generation;
+        drop(generation);
+
+        let message = if current != last_seen {
+            last_seen = current;
+            "data: reload\n\n"
+        } else {
+            ": keep-alive\n\n"
+        };
+        if stream.write_all(message.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Adds the live reload script to the end of an HTML page's body.
+fn inject_reload_script(mut html: Vec<u8>) -> Vec<u8> {
+    let idx = html
+        .windows("</body>".len())
+        .rposition(|window| window.eq_ignore_ascii_case(b"</body>"))
+        .unwrap_or(html.len());
+    html.splice(idx..idx, RELOAD_SCRIPT.bytes());
+    html
+}
+
+/// Percent-decodes a URL path and converts it into a relative file path.
+/// Returns `None` if the path is invalid or tries to leave the output
+/// directory.
+fn decode_path(url_path: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(url_path.len());
+    let mut iter = url_path.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    let decoded = String::from_utf8(bytes).ok()?;
+
+    let path = PathBuf::from(decoded.trim_start_matches('/'));
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then_some(path)
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("rss") => "application/rss+xml",
+        Some("atom") => "application/atom+xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("md" | "markdown") => "text/markdown; charset=utf-8",
+        Some("gmi" | "gemtext") => "text/gemini; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("otf") => "font/otf",
+        Some("wasm") => "application/wasm",
+        Some("pdf") => "application/pdf",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{decode_path, inject_reload_script, serve, CACHE_FILE, RELOAD_SCRIPT},
+        std::{
+            env, fs,
+            io::{Read, Write},
+            net::{TcpListener, TcpStream},
+            path::PathBuf,
+            process,
+        },
+    };
+
+    #[test]
+    fn paths() {
+        let cases = [
+            ("/", Some("")),
+            ("/index.html", Some("index.html")),
+            ("/posts/", Some("posts")),
+            ("/posts/hello%20world.html", Some("posts/hello world.html")),
+            // Absolute paths stay inside the output directory
+            ("//etc/passwd", Some("etc/passwd")),
+            ("/%2Fetc/passwd", Some("etc/passwd")),
+            ("/../secret", None),
+            ("/posts/../../secret", None),
+            ("/%2e%2e/secret", None),
+            ("/posts/%2E%2E/%2e%2e/secret", None),
+            ("/./index.html", None),
+            ("/bad%2", None),
+            ("/bad%zz", None),
+            ("/%ff", None),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(decode_path(url), expected.map(PathBuf::from), "{url}");
+        }
+    }
+
+    #[test]
+    fn reload_script() {
+        let cases = [
+            (
+                "<html><body><p>hi</p></body></html>",
+                format!("<html><body><p>hi</p>{RELOAD_SCRIPT}</body></html>"),
+            ),
+            ("<p>hi</p>", format!("<p>hi</p>{RELOAD_SCRIPT}")),
+            (
+                "<BODY><p>hi</p></BODY>",
+                format!("<BODY><p>hi</p>{RELOAD_SCRIPT}</BODY>"),
+            ),
+            // Only the last `</body>` counts
+            (
+                "<body><pre>&lt;/body></pre></body>",
+                format!("<body><pre>&lt;/body></pre>{RELOAD_SCRIPT}</body>"),
+            ),
+        ];
+
+        for (html, expected) in cases {
+            assert_eq!(
+                String::from_utf8(inject_reload_script(html.into())).unwrap(),
+                expected,
+                "{html}"
+            );
+        }
+    }
+
+    /// Sends a GET request for `url` and returns the response's status line,
+    /// `Content-Type` and body.
+    fn get(port: u16, url: &str) -> (String, Option<String>, String) {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "GET {url} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap().to_string();
+        let mime = lines
+            .find_map(|line| line.strip_prefix("Content-Type: "))
+            .map(str::to_string);
+        (status, mime, body.to_string())
+    }
+
+    #[test]
+    fn serves_files() {
+        let dir = env::temp_dir().join(format!("webby-test-serve-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("posts")).unwrap();
+        fs::write(dir.join("index.html"), "<body>home</body>").unwrap();
+        fs::write(dir.join("posts/hello.html"), "hello").unwrap();
+        fs::write(dir.join("style.css"), "p{}").unwrap();
+        fs::write(dir.join(CACHE_FILE), "cache").unwrap();
+
+        // Find a free port to serve on
+        let port = TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        serve(("127.0.0.1", port), &dir).unwrap();
+
+        let (status, mime, body) = get(port, "/");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(mime.as_deref(), Some("text/html; charset=utf-8"));
+        assert_eq!(body, format!("<body>home{RELOAD_SCRIPT}</body>"));
+
+        let (status, mime, body) = get(port, "/style.css");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(mime.as_deref(), Some("text/css; charset=utf-8"));
+        assert_eq!(body, "p{}");
+
+        // Pages can be requested without their extension
+        let (_, _, body) = get(port, "/posts/hello");
+        assert!(body.starts_with("hello"));
+
+        let (status, _, _) = get(port, "/posts");
+        assert_eq!(status, "HTTP/1.1 301 Moved Permanently");
+        let (status, _, _) = get(port, "/../secret");
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        let (status, _, _) = get(port, "/missing.html");
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        let (status, _, body) = get(port, &format!("/{CACHE_FILE}"));
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        assert!(!body.contains("cache"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,140 @@
+//! `sitemap.xml` and `robots.txt` generation.
+//!
+//! After every target is built, the sitemap lists every HTML page the targets
+//! wrote, with absolute URLs from `base_url` and the time each page's source
+//! file was last changed. Targets with `sitemap = false`, like error pages,
+//! are left out, and so are pages that weren't written, like drafts.
+
+use {
+    crate::{date::Date, feed::escape, glob, Config, ErrorKind, WebbyError},
+    std::{
+        collections::BTreeMap,
+        fmt::Write,
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// The file the sitemap is written to, in the output directory.
+pub const SITEMAP_FILE: &str = "sitemap.xml";
+/// The file the generated `robots.txt` is written to, in the output directory.
+pub const ROBOTS_FILE: &str = "robots.txt";
+
+/// Writes the sitemap and `robots.txt`, if they're turned on in `webby.toml`.
+/// Returns the files whose contents changed.
+pub fn write(config: &Config) -> Result<Vec<PathBuf>, WebbyError> {
+    let mut written = Vec::new();
+    if config.dry_run {
+        return Ok(written);
+    }
+
+    let mut files = Vec::new();
+    let sitemap_url = match (config.sitemap, &config.base_url) {
+        (true, Some(base_url)) => {
+            files.push((
+                config.output_dir.join(SITEMAP_FILE),
+                render(config, base_url),
+            ));
+            Some(format!("{base_url}/{SITEMAP_FILE}"))
+        }
+        _ => None,
+    };
+    if config.robots {
+        files.push((config.output_dir.join(ROBOTS_FILE), robots(sitemap_url)));
+    }
+
+    for (path, contents) in files {
+        if fs::read(&path).is_ok_and(|old| old == contents.as_bytes()) {
+            continue;
+        }
+        fs::create_dir_all(&config.output_dir)
+            .and_then(|_| fs::write(&path, contents))
+            .map_err(|err| {
+                WebbyError::new(ErrorKind::Io, format!("Failed to write {path:?}"))
+                    .in_file(&config.path)
+                    .caused_by(err)
+            })?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Builds the sitemap's XML.
+pub fn render(config: &Config, base_url: &str) -> String {
+    // Keyed by URL, so pages are listed once and in a stable order
+    let mut pages = BTreeMap::new();
+    for target in config.targets.iter().filter(|target| target.sitemap) {
+        let mut sources = Vec::new();
+        find_sources(&target.path, &mut sources);
+
+        for source in sources {
+            let Some(output) = config
+                .target_output(target, &source)
+                .filter(|output| output.extension().is_some_and(|ext| ext == "html"))
+                .filter(|output| output.is_file())
+            else {
+                continue;
+            };
+            let Some(url) = config.url_of_output(&output) else {
+                continue;
+            };
+            let modified = fs::metadata(&source)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .map(Date::from_system_time);
+            pages.entry(url).or_insert(modified);
+        }
+    }
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for (url, modified) in pages {
+        out += "  <url>\n";
+        writeln!(
+            out,
+            "    <loc>{}</loc>",
+            escape(&format!("{base_url}{url}"))
+        )
+        .unwrap();
+        if let Some(modified) = modified {
+            writeln!(out, "    <lastmod>{}</lastmod>", modified.to_rfc3339()).unwrap();
+        }
+        out += "  </url>\n";
+    }
+    out += "</urlset>\n";
+
+    out
+}
+
+/// Builds a `robots.txt` that allows every page, and points to the sitemap if
+/// there is one.
+fn robots(sitemap_url: Option<String>) -> String {
+    let mut out = String::from("User-agent: *\nAllow: /\n");
+    if let Some(sitemap_url) = sitemap_url {
+        writeln!(out, "\nSitemap: {sitemap_url}").unwrap();
+    }
+
+    out
+}
+
+/// Finds every file a target might build: its path if it's a file, or every
+/// file in it if it's a folder. [`Config::target_output`] decides which of
+/// them the target actually builds.
+fn find_sources(path: &Path, sources: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        sources.push(path.to_path_buf());
+        return;
+    }
+    let Ok(entries) = path.read_dir() else {
+        return;
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if entry.file_name().to_str().is_some_and(glob::is_ignored) {
+            continue;
+        }
+        find_sources(&entry.path(), sources);
+    }
+}
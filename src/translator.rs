@@ -1,5 +1,19 @@
 mod gemtext;
+mod html_to_gemtext;
+mod latex;
 mod markdown;
+mod markdown_to_gemtext;
 
-pub use gemtext::translate_gemtext;
-pub use markdown::translate_markdown;
+pub use gemtext::{
+    translate_gemtext, translate_gemtext_with_options, GemtextClasses, GemtextOptions,
+};
+pub(crate) use html_to_gemtext::decode_entities;
+pub use html_to_gemtext::translate_html_to_gemtext;
+pub use latex::translate_latex;
+pub use markdown::{
+    translate_markdown, translate_markdown_with_options, CompileOptions, Constructs,
+    MarkdownOptions,
+};
+pub use markdown_to_gemtext::{
+    translate_markdown_to_gemtext, translate_markdown_to_gemtext_with_options,
+};
@@ -1,5 +1,5 @@
 use {
-    crate::Cow,
+    crate::{highlight, Cow, ErrorKind, Position, WebbyError},
     std::{fmt::Write, path::Path},
 };
 
@@ -7,6 +7,7 @@ use {
 enum ParserState {
     Text,
     List,
+    Quote,
     Preformatted,
 }
 
@@ -38,82 +39,261 @@ fn html_escape_into(input: &str, out: &mut String) {
     }
 }
 
-pub fn translate_gemtext(source_path: &Path, source: &str) -> Result<String, Cow<'static>> {
+/// Changes how Gemtext is translated to HTML. The defaults give plain HTML
+/// with no classes or wrapper, and leave links as they are.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GemtextOptions {
+    /// CSS classes added to the element each line type becomes.
+    pub classes: GemtextClasses,
+    /// If set, the translated page is wrapped in this element, like `article`.
+    pub wrapper: Option<String>,
+    /// Show links to images (`.png`, `.jpg`, `.jpeg`, `.gif` and `.webp` URLs)
+    /// as `<img>`s, with the link's label as the alt text.
+    pub inline_images: bool,
+    /// Rewrite relative links to `.gmi` files to link to `.html` files, so
+    /// they go to the website's copy of the page.
+    pub rewrite_gmi_links: bool,
+    /// If set, `gemini://` links are rewritten to go through this HTTP proxy.
+    /// The URL, without `gemini://`, is added to the end of it, so a proxy of
+    /// `https://portal.mozz.us/gemini/` links `gemini://example.com/` to
+    /// `https://portal.mozz.us/gemini/example.com/`.
+    pub gemini_proxy: Option<String>,
+    /// Highlight preformatted blocks whose alt text starts with a language
+    /// webby can highlight, like `rust`. When building, this is set by the
+    /// target's `highlight` setting.
+    pub highlight: bool,
+}
+
+/// CSS classes for the elements Gemtext lines are translated to. Line types
+/// without a class get none.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GemtextClasses {
+    pub text: Option<String>,
+    pub link: Option<String>,
+    pub image: Option<String>,
+    pub h1: Option<String>,
+    pub h2: Option<String>,
+    pub h3: Option<String>,
+    pub list: Option<String>,
+    pub quote: Option<String>,
+    pub preformatted: Option<String>,
+    pub wrapper: Option<String>,
+}
+
+/// Writes an opening tag, with a class if there is one.
+fn open_tag(tag: &str, class: &Option<String>, out: &mut String) {
+    out.push('<');
+    *out += tag;
+    if let Some(class) = class {
+        *out += " class=\"";
+        html_escape_into(class, out);
+        out.push('"');
+    }
+    out.push('>');
+}
+
+/// Rewrites a link's URL for the web, following the options.
+fn rewrite_url<'a>(url: &'a str, options: &GemtextOptions) -> Cow<'a> {
+    if let (Some(proxy), Some(rest)) = (&options.gemini_proxy, url.strip_prefix("gemini://")) {
+        return Cow::Owned(format!("{proxy}{rest}"));
+    }
+    if options.rewrite_gmi_links && !has_scheme(url) {
+        let end = url.find(['?', '#']).unwrap_or(url.len());
+        if let Some(page) = url[..end].strip_suffix(".gmi") {
+            return Cow::Owned(format!("{page}.html{}", &url[end..]));
+        }
+    }
+
+    Cow::Borrowed(url)
+}
+
+/// If a URL starts with a scheme, like `https:`, and so isn't relative.
+fn has_scheme(url: &str) -> bool {
+    url.find(':')
+        .is_some_and(|colon| !url[..colon].is_empty() && !url[..colon].contains(['/', '?', '#']))
+}
+
+/// If a URL links to an image, going by its extension.
+fn is_image(url: &str) -> bool {
+    let path = &url[..url.find(['?', '#']).unwrap_or(url.len())];
+    let Some((_, extension)) = path.rsplit_once('.') else {
+        return false;
+    };
+    ["png", "jpg", "jpeg", "gif", "webp"]
+        .iter()
+        .any(|image| extension.eq_ignore_ascii_case(image))
+}
+
+/// Writes the contents of a preformatted block, then closes it.
+fn write_preformatted(text: &str, language: &str, options: &GemtextOptions, out: &mut String) {
+    // There's no newline after the last line of the block
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    match options
+        .highlight
+        .then(|| highlight::highlight(language, text))
+        .flatten()
+    {
+        Some(highlighted) => *out += &highlighted,
+        None => html_escape_into(text, out),
+    }
+    *out += "</pre>\n";
+}
+
+/// Translates Gemtext to HTML with the default [`GemtextOptions`].
+pub fn translate_gemtext(source_path: &Path, source: &str) -> Result<String, WebbyError> {
+    translate_gemtext_with_options(source_path, source, &GemtextOptions::default())
+}
+
+/// Translates Gemtext to HTML, following the Gemtext spec: every line is its
+/// own block, consecutive list items and quote lines are grouped into one list
+/// or quote, and blank lines become `<br>`s.
+pub fn translate_gemtext_with_options(
+    source_path: &Path,
+    source: &str,
+    options: &GemtextOptions,
+) -> Result<String, WebbyError> {
+    let classes = &options.classes;
     let mut output = String::new();
     let mut state = ParserState::Text;
-    output += "<p>";
+    // The contents of the preformatted block being read, and the language
+    // from its alt text
+    let mut pre = String::new();
+    let mut pre_language = "";
+
+    if let Some(wrapper) = &options.wrapper {
+        open_tag(wrapper, &classes.wrapper, &mut output);
+        output.push('\n');
+    }
 
     for (line_num, line) in source.lines().enumerate() {
         if state == ParserState::Preformatted {
             if line.starts_with("```") {
                 state = ParserState::Text;
-                output += "</pre>";
-                continue;
+                write_preformatted(&pre, pre_language, options, &mut output);
+                pre.clear();
+            } else {
+                pre += line;
+                pre.push('\n');
             }
-
-            html_escape_into(line, &mut output);
             continue;
         }
 
-        if let Some(list_line) = line.strip_prefix("* ") {
-            if state != ParserState::List {
-                state = ParserState::List;
-                output += "<ul>";
+        // Lists and quotes end at the first line that isn't part of them
+        let next_state = if line.starts_with("* ") {
+            ParserState::List
+        } else if line.starts_with('>') {
+            ParserState::Quote
+        } else {
+            ParserState::Text
+        };
+        if next_state != state {
+            match state {
+                ParserState::List => output += "</ul>\n",
+                ParserState::Quote => output += "</blockquote>\n",
+                _ => {}
             }
-            output += "<li>";
-            html_escape_into(list_line, &mut output);
-            output += "</li>";
-            continue;
-        } else if state == ParserState::List {
-            state = ParserState::Text;
-            output += "</ul>";
+            match next_state {
+                ParserState::List => open_tag("ul", &classes.list, &mut output),
+                ParserState::Quote => open_tag("blockquote", &classes.quote, &mut output),
+                _ => {}
+            }
+            if next_state != ParserState::Text {
+                output.push('\n');
+            }
+            state = next_state;
         }
 
-        if let Some(link_line) = line.strip_prefix("=>") {
-            let mut line = link_line.split_whitespace();
-            let link = line.next().ok_or(Cow::Owned(format!(
-                "Expected URL in link at {source_path:?}:{line_num}"
-            )))?;
-
-            output += "<a href=\"";
-            html_escape_into(link, &mut output);
-            output += "\">";
+        if let Some(item) = line.strip_prefix("* ") {
+            output += "<li>";
+            html_escape_into(item, &mut output);
+            output += "</li>\n";
+        } else if let Some(quote) = line.strip_prefix('>') {
+            output += "<p>";
+            html_escape_into(quote.trim_start(), &mut output);
+            output += "</p>\n";
+        } else if let Some(link) = line.strip_prefix("=>") {
+            // The URL and label can be separated by any amount of whitespace
+            let link = link.trim();
+            let (url, label) = match link.split_once(char::is_whitespace) {
+                Some((url, label)) => (url, Some(label.trim_start())),
+                None => (link, None),
+            };
+            if url.is_empty() {
+                return Err(
+                    WebbyError::new(ErrorKind::GemtextSyntax, "Expected URL in link").at_position(
+                        source_path,
+                        Position {
+                            line: line_num + 1,
+                            column: 1,
+                        },
+                    ),
+                );
+            }
+            let href = rewrite_url(url, options);
 
-            if let Some(link_text) = line.next() {
-                html_escape_into(link_text, &mut output);
+            if options.inline_images && is_image(url) {
+                open_tag("p", &classes.image, &mut output);
+                output += "<img src=\"";
+                html_escape_into(&href, &mut output);
+                output += "\" alt=\"";
+                html_escape_into(label.unwrap_or_default(), &mut output);
+                output += "\"></p>\n";
             } else {
-                html_escape_into(link, &mut output);
+                open_tag("p", &classes.link, &mut output);
+                output += "<a href=\"";
+                html_escape_into(&href, &mut output);
+                output += "\">";
+                html_escape_into(label.unwrap_or(url), &mut output);
+                output += "</a></p>\n";
             }
-
-            output += "</a><br>";
         } else if let Some(alt) = line.strip_prefix("```") {
-            output += "<pre alt=\"";
-            html_escape_into(alt, &mut output);
-            output += "\">";
+            // Alt text describes the preformatted block, like its language
+            let alt = alt.trim();
+            output += "<pre";
+            if let Some(class) = &classes.preformatted {
+                output += " class=\"";
+                html_escape_into(class, &mut output);
+                output.push('"');
+            }
+            if !alt.is_empty() {
+                output += " aria-label=\"";
+                html_escape_into(alt, &mut output);
+                output.push('"');
+            }
+            output.push('>');
+            pre_language = alt.split_whitespace().next().unwrap_or_default();
             state = ParserState::Preformatted;
-        } else if let Some(quote) = line.strip_prefix("> ") {
-            output += "<blockquote><p>";
-            html_escape_into(quote, &mut output);
-            output += "</p></blockquote>";
         } else if line.starts_with('#') {
-            let mut chars = line.bytes();
-            let mut level = 0;
-            while chars.next() == Some(b'#') {
-                level += 1;
-            }
-
-            write!(output, "<h{level}>").unwrap();
+            // Gemtext only has three levels of headings, so any more `#`s are
+            // part of the heading's text
+            let level = line.bytes().take(3).take_while(|b| *b == b'#').count();
+            let class = match level {
+                1 => &classes.h1,
+                2 => &classes.h2,
+                _ => &classes.h3,
+            };
+            open_tag(&format!("h{level}"), class, &mut output);
             html_escape_into(line[level..].trim_start(), &mut output);
-            write!(output, "</h{level}>").unwrap();
+            writeln!(output, "</h{level}>").unwrap();
+        } else if line.trim().is_empty() {
+            output += "<br>\n";
         } else {
-            output += line;
+            open_tag("p", &classes.text, &mut output);
+            html_escape_into(line, &mut output);
+            output += "</p>\n";
         }
     }
 
-    if state == ParserState::List {
-        output += "</ul>";
+    // Close anything still open at the end of the file
+    match state {
+        ParserState::List => output += "</ul>\n",
+        ParserState::Quote => output += "</blockquote>\n",
+        ParserState::Preformatted => write_preformatted(&pre, pre_language, options, &mut output),
+        ParserState::Text => {}
+    }
+    if let Some(wrapper) = &options.wrapper {
+        writeln!(output, "</{wrapper}>").unwrap();
     }
 
-    output += "</p>";
     Ok(output)
 }
@@ -0,0 +1,354 @@
+//! Translates HTML pages to Gemtext, so hand-written HTML pages can be
+//! published to a Gemini capsule too.
+//!
+//! Headings, paragraphs, lists, quotes and preformatted blocks are kept, and
+//! every other element just contributes its text. Links and images in each
+//! block are listed as `=>` lines after it. Scripts, styles, the `<head>` and
+//! navigation are dropped.
+
+use {
+    crate::{
+        minifier::{tokenize, Token},
+        WebbyError,
+    },
+    std::path::Path,
+};
+
+/// Elements whose contents are dropped entirely.
+const DROPPED: &[&str] = &[
+    "head", "script", "style", "nav", "noscript", "template", "svg", "form", "button", "iframe",
+    "select",
+];
+/// Elements that start a new block, like a paragraph.
+const BLOCKS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "header",
+    "footer",
+    "aside",
+    "figure",
+    "figcaption",
+    "details",
+    "summary",
+    "table",
+    "dl",
+    "address",
+    "hr",
+    "body",
+];
+/// Elements that start a new line, but not a new block.
+const LINES: &[&str] = &["br", "tr", "dt", "dd"];
+/// Elements that never have a closing tag.
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+pub fn translate_html_to_gemtext(source_path: &Path, source: &str) -> Result<String, WebbyError> {
+    let mut translator = Translator::default();
+    // The element being dropped, and how many elements with the same name
+    // deep into it the tokens are. Other elements inside it are ignored, so
+    // ones without closing tags, like `<li>`, can't stop it from ending.
+    let mut dropped: Option<(String, usize)> = None;
+
+    for token in tokenize(source_path, source)? {
+        match token {
+            Token::Open {
+                name,
+                attributes,
+                self_closing,
+            } => {
+                let is_void = self_closing || VOID.contains(&name.as_str());
+                if let Some((dropped_name, depth)) = &mut dropped {
+                    if !is_void && name == *dropped_name {
+                        *depth += 1;
+                    }
+                    continue;
+                }
+                if is_dropped(&name, &attributes) {
+                    if !is_void {
+                        dropped = Some((name, 1));
+                    }
+                    continue;
+                }
+                let attribute = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                        .map(|(_, value)| decode_entities(value))
+                };
+                translator.open(&name, attribute);
+                if is_void {
+                    translator.close(&name);
+                }
+            }
+            Token::Close(name) => {
+                if let Some((dropped_name, depth)) = &mut dropped {
+                    if name == *dropped_name {
+                        *depth -= 1;
+                        if *depth == 0 {
+                            dropped = None;
+                        }
+                    }
+                    continue;
+                }
+                translator.close(&name);
+            }
+            Token::Text(text) if dropped.is_none() => translator.text(&decode_entities(text)),
+            Token::Text(_) => {}
+        }
+    }
+    translator.end_block();
+
+    let mut output = translator.blocks.join("\n\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// If an element is navigation or something else that doesn't belong in a
+/// capsule.
+fn is_dropped(name: &str, attributes: &[(&str, &str)]) -> bool {
+    DROPPED.contains(&name)
+        || attributes.iter().any(|(key, value)| {
+            (key.eq_ignore_ascii_case("role") && *value == "navigation")
+                || (key.eq_ignore_ascii_case("aria-hidden") && *value == "true")
+                || key.eq_ignore_ascii_case("hidden")
+        })
+}
+
+#[derive(Default)]
+struct Translator {
+    /// The finished blocks of Gemtext.
+    blocks: Vec<String>,
+    /// The lines of the current block.
+    lines: Vec<String>,
+    /// The text of the current line.
+    text: String,
+    /// Links and images in the current block, as `(url, label)`.
+    links: Vec<(String, String)>,
+    /// Links that are open, and where their label starts in `text`.
+    open_links: Vec<(Option<String>, usize)>,
+    heading: Option<usize>,
+    list_depth: usize,
+    item_depth: usize,
+    quote_depth: usize,
+    /// The contents and alt text of the `<pre>` being read, if there is one.
+    pre: Option<(String, String)>,
+}
+impl Translator {
+    fn open(&mut self, name: &str, attribute: impl Fn(&str) -> Option<String>) {
+        if let Some((pre, alt)) = &mut self.pre {
+            // `<pre><code class="language-rust">` gives the block's language
+            if name == "code" && pre.trim().is_empty() {
+                if let Some(language) = attribute("class").as_deref().and_then(|class| {
+                    class
+                        .split_whitespace()
+                        .find_map(|class| class.strip_prefix("language-"))
+                        .map(str::to_string)
+                }) {
+                    *alt = language;
+                }
+            }
+            return;
+        }
+
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.break_block();
+                self.heading = Some(name[1..].parse().unwrap());
+            }
+            "ul" | "ol" | "menu" => {
+                self.break_block();
+                self.list_depth += 1;
+            }
+            "li" => {
+                self.end_line();
+                self.item_depth += 1;
+            }
+            "blockquote" => {
+                self.break_block();
+                self.quote_depth += 1;
+            }
+            "pre" => {
+                self.break_block();
+                self.pre = Some((String::new(), String::new()));
+            }
+            "a" => self.open_links.push((attribute("href"), self.text.len())),
+            "img" => {
+                if let Some(src) = attribute("src") {
+                    self.push_link(src, attribute("alt").unwrap_or_default());
+                }
+            }
+            "td" | "th" if !self.text.trim().is_empty() => self.text += " | ",
+            name if BLOCKS.contains(&name) => self.break_block(),
+            name if LINES.contains(&name) => self.end_line(),
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        if self.pre.is_some() {
+            if name == "pre" {
+                let (pre, alt) = self.pre.take().unwrap();
+                self.lines.push(format!("```{alt}"));
+                let pre = pre.strip_prefix('\n').unwrap_or(&pre);
+                self.lines
+                    .extend(pre.trim_end().lines().map(str::to_string));
+                self.lines.push("```".into());
+                self.break_block();
+            }
+            return;
+        }
+
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.end_line();
+                self.heading = None;
+                self.break_block();
+            }
+            "ul" | "ol" | "menu" => {
+                self.list_depth = self.list_depth.saturating_sub(1);
+                self.break_block();
+            }
+            "li" => {
+                self.end_line();
+                self.item_depth = self.item_depth.saturating_sub(1);
+            }
+            "blockquote" => {
+                self.end_line();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                self.break_block();
+            }
+            "a" => {
+                if let Some((Some(href), start)) = self.open_links.pop() {
+                    let label = self.text[start.min(self.text.len())..].trim().to_string();
+                    // Links within the page don't mean anything in a capsule
+                    if !href.starts_with('#') && !href.starts_with("javascript:") {
+                        self.push_link(href, label);
+                    }
+                }
+            }
+            name if BLOCKS.contains(&name) => self.break_block(),
+            name if LINES.contains(&name) => self.end_line(),
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some((pre, _)) = &mut self.pre {
+            *pre += text;
+            return;
+        }
+
+        // Whitespace is collapsed, like a browser would
+        for (idx, word) in text.split(char::is_whitespace).enumerate() {
+            if idx > 0 && !self.text.is_empty() && !self.text.ends_with(' ') {
+                self.text.push(' ');
+            }
+            self.text += word;
+        }
+    }
+
+    fn push_link(&mut self, url: String, label: String) {
+        if !self.links.iter().any(|(existing, _)| *existing == url) {
+            self.links.push((url, label));
+        }
+    }
+
+    /// Finishes the current line, adding the Gemtext for the heading, list
+    /// item or quote it's in.
+    fn end_line(&mut self) {
+        let text = self.text.trim();
+        if !text.is_empty() {
+            let mut line = String::new();
+            if self.quote_depth > 0 {
+                line += "> ";
+            }
+            if let Some(level) = self.heading {
+                // Gemtext only has three levels of headings
+                line += &"#".repeat(level.clamp(1, 3));
+                line.push(' ');
+            } else if self.item_depth > 0 {
+                line += "* ";
+            }
+            line += text;
+            self.lines.push(line);
+        }
+        self.text.clear();
+        for (_, start) in &mut self.open_links {
+            *start = 0;
+        }
+    }
+
+    /// Ends the current block, unless it's inside a list or quote, which are
+    /// kept together as one block.
+    fn break_block(&mut self) {
+        if self.list_depth > 0 || self.quote_depth > 0 {
+            self.end_line();
+        } else {
+            self.end_block();
+        }
+    }
+
+    /// Ends the current block, then lists the links that were in it.
+    fn end_block(&mut self) {
+        self.end_line();
+        for (url, label) in self.links.drain(..) {
+            if label.is_empty() || label == url {
+                self.lines.push(format!("=> {url}"));
+            } else {
+                self.lines.push(format!("=> {url} {label}"));
+            }
+        }
+        if !self.lines.is_empty() {
+            self.blocks.push(self.lines.join("\n"));
+            self.lines.clear();
+        }
+    }
+}
+
+/// Decodes the HTML entities in some text, like `&amp;`.
+pub(crate) fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded += &rest[..start];
+        rest = &rest[start..];
+
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let char = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (char, entity) {
+            (Some(char), Some(entity)) => {
+                decoded.push(char);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded += rest;
+
+    decoded
+}
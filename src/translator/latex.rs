@@ -0,0 +1,844 @@
+//! Translates a subset of LaTeX math to MathML, so pages with math can be
+//! shown without sending KaTeX or MathJax to readers.
+//!
+//! Letters, numbers and operators, `_` and `^` scripts, `{}` groups, Greek
+//! letters and common symbols, `\frac`, `\sqrt`, `\binom`, `\text`, fonts like
+//! `\mathbb`, accents like `\hat`, `\left` and `\right`, and the `matrix`,
+//! `pmatrix`, `bmatrix`, `cases` and `aligned` environments are supported.
+//! Unknown commands are shown in an `<merror>`, rather than failing the build.
+
+use {super::decode_entities, crate::feed::escape, std::fmt::Write};
+
+/// Translates LaTeX math to a `<math>` element. Display math is shown as its
+/// own block, like `$$...$$` on its own lines.
+pub fn translate_latex(latex: &str, display: bool) -> String {
+    let mut parser = Parser {
+        src: latex,
+        pos: 0,
+        font: None,
+    };
+    let row = parser.row(Until::End);
+
+    if display {
+        format!("<math display=\"block\">{row}</math>")
+    } else {
+        format!("<math>{row}</math>")
+    }
+}
+
+/// Translates the math the `markdown` crate leaves in
+/// `<code class="language-math">` elements to MathML. Returns `None` if there
+/// isn't any.
+pub(super) fn translate_math_in(html: &str) -> Option<String> {
+    const OPEN: &str = "<code class=\"language-math";
+    if !html.contains(OPEN) {
+        return None;
+    }
+
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(OPEN) {
+        let Some(content_start) = rest[start..].find('>').map(|end| start + end + 1) else {
+            break;
+        };
+        let Some(content_end) = rest[content_start..]
+            .find("</code>")
+            .map(|end| content_start + end)
+        else {
+            break;
+        };
+        let mut end = content_end + "</code>".len();
+
+        // Math blocks are in a `<pre>`, and are replaced along with it
+        let mut before = &rest[..start];
+        let display = before.ends_with("<pre>") && rest[end..].starts_with("</pre>");
+        if display {
+            before = &before[..before.len() - "<pre>".len()];
+            end += "</pre>".len();
+        }
+
+        output += before;
+        output += &translate_latex(
+            decode_entities(&rest[content_start..content_end]).trim(),
+            display,
+        );
+        rest = &rest[end..];
+    }
+    output += rest;
+
+    Some(output)
+}
+
+/// Where a row of math ends.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Until {
+    /// The end of the LaTeX.
+    End,
+    /// A `}`, closing a group.
+    Brace,
+    /// A `]`, closing the index of a root.
+    Bracket,
+    /// A `\right`, closing a `\left`.
+    Right,
+    /// A `&`, `\\` or `\end`, ending a cell in a table.
+    Cell,
+}
+
+/// Fonts set by commands like `\mathbb`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Font {
+    Roman,
+    Bold,
+    Italic,
+    BoldItalic,
+    Script,
+    Fraktur,
+    DoubleStruck,
+    SansSerif,
+    Monospace,
+}
+
+/// A piece of math that scripts can be attached to.
+struct Atom {
+    mathml: String,
+    /// If scripts go above and below it, like the limits of `\sum`, instead
+    /// of to the side.
+    limits: bool,
+}
+impl From<String> for Atom {
+    fn from(mathml: String) -> Self {
+        Self {
+            mathml,
+            limits: false,
+        }
+    }
+}
+
+/// What a command like `\alpha` or `\sum` stands for.
+enum Symbol {
+    Identifier(&'static str),
+    /// An identifier that isn't italic, like `\Gamma`.
+    Upright(&'static str),
+    Operator(&'static str),
+    /// A big operator like `\sum`, and if it has limits.
+    Big(&'static str, bool),
+    /// A function name like `\sin`, and if it has limits, like `\lim`.
+    Function(&'static str, bool),
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// The font letters are written in, if it's been changed.
+    font: Option<Font>,
+}
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// The name of the command at the current position, without its `\`.
+    /// Commands are either letters, like `\alpha`, or one other character,
+    /// like `\{`.
+    fn peek_command(&self) -> Option<&'a str> {
+        let name = self.src[self.pos..].strip_prefix('\\')?;
+        let end = match name.find(|char: char| !char.is_ascii_alphabetic()) {
+            Some(0) => name.chars().next().map_or(0, char::len_utf8),
+            Some(end) => end,
+            None => name.len(),
+        };
+        Some(&name[..end])
+    }
+
+    /// Reads the command at the current position.
+    fn command(&mut self) -> &'a str {
+        let name = self.peek_command().unwrap_or_default();
+        self.pos += 1 + name.len();
+        name
+    }
+
+    fn at_end(&self, until: Until) -> bool {
+        let Some(char) = self.peek() else {
+            return true;
+        };
+        let command = self.peek_command();
+        let ends_cell = char == '&' || matches!(command, Some("\\" | "end"));
+        match until {
+            Until::End => false,
+            Until::Brace => char == '}',
+            Until::Bracket => char == ']',
+            Until::Right => command == Some("right") || ends_cell,
+            Until::Cell => ends_cell,
+        }
+    }
+
+    /// Reads math until `until`, returning it as one element.
+    fn row(&mut self, until: Until) -> String {
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.at_end(until) {
+                break;
+            }
+            if let Some(atom) = self.scripted_atom() {
+                items.push(atom);
+            }
+        }
+        if matches!(until, Until::Brace | Until::Bracket) && self.peek().is_some() {
+            self.pos += 1;
+        }
+
+        if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            format!("<mrow>{}</mrow>", items.concat())
+        }
+    }
+
+    /// Reads an atom and any scripts attached to it.
+    fn scripted_atom(&mut self) -> Option<String> {
+        let base = self.atom()?;
+        let mut sub = None;
+        let mut sup: Option<String> = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('_') => {
+                    self.pos += 1;
+                    sub = Some(self.argument());
+                }
+                Some('^') => {
+                    self.pos += 1;
+                    sup = Some(self.argument());
+                }
+                Some('\'') => {
+                    self.pos += 1;
+                    sup = Some(match sup {
+                        Some(sup) => format!("<mrow>{sup}<mo>′</mo></mrow>"),
+                        None => "<mo>′</mo>".into(),
+                    });
+                }
+                _ => break,
+            }
+        }
+
+        let (under, over, underover) = if base.limits {
+            ("munder", "mover", "munderover")
+        } else {
+            ("msub", "msup", "msubsup")
+        };
+        let base = base.mathml;
+        Some(match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => format!("<{under}>{base}{sub}</{under}>"),
+            (None, Some(sup)) => format!("<{over}>{base}{sup}</{over}>"),
+            (Some(sub), Some(sup)) => format!("<{underover}>{base}{sub}{sup}</{underover}>"),
+        })
+    }
+
+    /// Reads the argument of a command or script: a group, or one token.
+    fn argument(&mut self) -> String {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                self.row(Until::Brace)
+            }
+            Some('\\') => self
+                .atom()
+                .map(|atom| atom.mathml)
+                .unwrap_or_else(|| "<mrow></mrow>".into()),
+            Some(char) => {
+                self.pos += char.len_utf8();
+                self.token(char)
+            }
+            None => "<mrow></mrow>".into(),
+        }
+    }
+
+    /// Reads the text of a group without translating it, like the text in
+    /// `\text{...}`.
+    fn raw_argument(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let rest = &self.src[self.pos..];
+        if !rest.starts_with('{') {
+            let len = rest.chars().next().map_or(0, char::len_utf8);
+            self.pos += len;
+            return &rest[..len];
+        }
+
+        let mut depth = 0;
+        for (idx, char) in rest.char_indices() {
+            match char {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.pos += idx + 1;
+                        return &rest[1..idx];
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.pos = self.src.len();
+        &rest[1..]
+    }
+
+    /// Reads one atom, like a number, a letter, a group or a command.
+    fn atom(&mut self) -> Option<Atom> {
+        let char = self.peek()?;
+        match char {
+            '{' => {
+                self.pos += 1;
+                Some(self.row(Until::Brace).into())
+            }
+            // Scripts without anything before them
+            '_' | '^' | '\'' => Some(String::from("<mrow></mrow>").into()),
+            '\\' => self.command_atom(),
+            // Stray closing braces, and `&`s outside of tables
+            '}' | '&' => {
+                self.pos += 1;
+                None
+            }
+            '0'..='9' | '.' => {
+                let rest = &self.src[self.pos..];
+                let mut len = 0;
+                for (idx, char) in rest.char_indices() {
+                    let decimal_point = char == '.'
+                        && rest[idx + 1..].starts_with(|char: char| char.is_ascii_digit());
+                    if !char.is_ascii_digit() && !decimal_point {
+                        break;
+                    }
+                    len = idx + 1;
+                }
+                if len == 0 {
+                    self.pos += 1;
+                    return Some(operator(".").into());
+                }
+                self.pos += len;
+                Some(self.number(&rest[..len]).into())
+            }
+            char => {
+                self.pos += char.len_utf8();
+                Some(self.token(char).into())
+            }
+        }
+    }
+
+    /// Translates a single character.
+    fn token(&self, char: char) -> String {
+        match char {
+            '0'..='9' => self.number(char.encode_utf8(&mut [0; 4])),
+            '~' => space("0.2778em"),
+            char if char.is_alphabetic() => self.identifier(char),
+            char => operator(char.encode_utf8(&mut [0; 4])),
+        }
+    }
+
+    fn identifier(&self, char: char) -> String {
+        match self.font {
+            Some(Font::Roman) => format!("<mi mathvariant=\"normal\">{}</mi>", escape_char(char)),
+            Some(font) => format!("<mi>{}</mi>", styled(char, font).unwrap_or(char)),
+            None => format!("<mi>{}</mi>", escape_char(char)),
+        }
+    }
+
+    fn number(&self, number: &str) -> String {
+        let number: String = match self.font {
+            Some(font) => number
+                .chars()
+                .map(|char| styled(char, font).unwrap_or(char))
+                .collect(),
+            None => number.into(),
+        };
+        format!("<mn>{number}</mn>")
+    }
+
+    /// Reads a command and its arguments.
+    fn command_atom(&mut self) -> Option<Atom> {
+        let name = self.command();
+        let mathml = match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.argument();
+                let denominator = self.argument();
+                format!("<mfrac>{numerator}{denominator}</mfrac>")
+            }
+            "binom" => {
+                let n = self.argument();
+                let k = self.argument();
+                format!(
+                    "<mrow><mo>(</mo><mfrac linethickness=\"0\">{n}{k}</mfrac><mo>)</mo></mrow>"
+                )
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                let index = if self.peek() == Some('[') {
+                    self.pos += 1;
+                    Some(self.row(Until::Bracket))
+                } else {
+                    None
+                };
+                let radicand = self.argument();
+                match index {
+                    Some(index) => format!("<mroot>{radicand}{index}</mroot>"),
+                    None => format!("<msqrt>{radicand}</msqrt>"),
+                }
+            }
+            "text" | "textrm" | "textit" | "textbf" | "mbox" => {
+                // Spaces at the ends of text would be trimmed
+                let text = escape(self.raw_argument()).replace(' ', "\u{a0}");
+                format!("<mtext>{text}</mtext>")
+            }
+            "operatorname" => format!("<mi>{}</mi>", escape(self.raw_argument().trim())),
+            "mathrm" | "mathbf" | "mathit" | "boldsymbol" | "bm" | "mathcal" | "mathscr"
+            | "mathfrak" | "mathbb" | "mathsf" | "mathtt" => {
+                let font = match name {
+                    "mathrm" => Font::Roman,
+                    "mathbf" => Font::Bold,
+                    "mathit" => Font::Italic,
+                    "boldsymbol" | "bm" => Font::BoldItalic,
+                    "mathcal" | "mathscr" => Font::Script,
+                    "mathfrak" => Font::Fraktur,
+                    "mathbb" => Font::DoubleStruck,
+                    "mathsf" => Font::SansSerif,
+                    _ => Font::Monospace,
+                };
+                let outer = self.font.replace(font);
+                let argument = self.argument();
+                self.font = outer;
+                argument
+            }
+            "hat" | "widehat" | "bar" | "overline" | "vec" | "overrightarrow" | "dot" | "ddot"
+            | "tilde" | "widetilde" => {
+                let accent = match name {
+                    "hat" | "widehat" => "^",
+                    "bar" | "overline" => "¯",
+                    "vec" | "overrightarrow" => "→",
+                    "dot" => "˙",
+                    "ddot" => "¨",
+                    _ => "~",
+                };
+                let base = self.argument();
+                format!("<mover accent=\"true\">{base}<mo>{accent}</mo></mover>")
+            }
+            "underline" => {
+                let base = self.argument();
+                format!("<munder accentunder=\"true\">{base}<mo>_</mo></munder>")
+            }
+            "left" => {
+                let open = self.delimiter();
+                let inner = self.row(Until::Right);
+                let close = if self.peek_command() == Some("right") {
+                    self.command();
+                    self.delimiter()
+                } else {
+                    ""
+                };
+                let mut mathml = String::from("<mrow>");
+                if !open.is_empty() {
+                    write!(mathml, "<mo fence=\"true\">{}</mo>", escape(open)).unwrap();
+                }
+                mathml += &inner;
+                if !close.is_empty() {
+                    write!(mathml, "<mo fence=\"true\">{}</mo>", escape(close)).unwrap();
+                }
+                mathml + "</mrow>"
+            }
+            // Sizes aren't supported, but the delimiter is still shown
+            "right" | "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl" | "Bigr"
+            | "biggl" | "biggr" | "Biggl" | "Biggr" => match self.delimiter() {
+                "" => return None,
+                delimiter => operator(delimiter),
+            },
+            "begin" => self.environment(),
+            // `\end`s without a `\begin`, and line breaks outside of tables
+            "end" => {
+                self.raw_argument();
+                return None;
+            }
+            "\\" => return None,
+            "," => space("0.1667em"),
+            ":" | ">" => space("0.2222em"),
+            ";" => space("0.2778em"),
+            "!" => space("-0.1667em"),
+            "quad" => space("1em"),
+            "qquad" => space("2em"),
+            " " => "<mtext>\u{a0}</mtext>".into(),
+            "{" | "}" | "%" | "$" | "#" | "&" => operator(name),
+            "_" => "<mi>_</mi>".into(),
+            "|" => operator("‖"),
+            name => match symbol(name) {
+                Some(Symbol::Identifier(char)) => format!("<mi>{char}</mi>"),
+                Some(Symbol::Upright(char)) => format!("<mi mathvariant=\"normal\">{char}</mi>"),
+                Some(Symbol::Operator(char)) => operator(char),
+                Some(Symbol::Big(char, limits)) => {
+                    return Some(Atom {
+                        mathml: operator(char),
+                        limits,
+                    })
+                }
+                Some(Symbol::Function(name, false)) => format!("<mi>{name}</mi>"),
+                Some(Symbol::Function(name, true)) => {
+                    return Some(Atom {
+                        mathml: format!("<mo movablelimits=\"true\" form=\"prefix\">{name}</mo>"),
+                        limits: true,
+                    })
+                }
+                None => format!("<merror><mtext>\\{}</mtext></merror>", escape(name)),
+            },
+        };
+
+        Some(mathml.into())
+    }
+
+    /// Reads the delimiter after `\left`, `\right` or `\big`. `.` means there
+    /// isn't one.
+    fn delimiter(&mut self) -> &'static str {
+        self.skip_whitespace();
+        if self.peek() == Some('\\') {
+            return match self.command() {
+                "{" | "lbrace" => "{",
+                "}" | "rbrace" => "}",
+                "|" | "Vert" | "lVert" | "rVert" => "‖",
+                "vert" | "lvert" | "rvert" => "|",
+                "langle" => "⟨",
+                "rangle" => "⟩",
+                "lfloor" => "⌊",
+                "rfloor" => "⌋",
+                "lceil" => "⌈",
+                "rceil" => "⌉",
+                _ => "",
+            };
+        }
+        let Some(char) = self.peek() else {
+            return "";
+        };
+        self.pos += char.len_utf8();
+        match char {
+            '(' => "(",
+            ')' => ")",
+            '[' => "[",
+            ']' => "]",
+            '|' => "|",
+            '/' => "/",
+            '<' => "⟨",
+            '>' => "⟩",
+            _ => "",
+        }
+    }
+
+    /// Reads a `\begin{...}` environment as a table, up to its `\end`.
+    fn environment(&mut self) -> String {
+        let name = self.raw_argument();
+        let (open, close, align) = match name.trim_end_matches('*') {
+            "pmatrix" => ("(", ")", None),
+            "bmatrix" => ("[", "]", None),
+            "Bmatrix" => ("{", "}", None),
+            "vmatrix" => ("|", "|", None),
+            "Vmatrix" => ("‖", "‖", None),
+            "cases" => ("{", "", Some("left left")),
+            "aligned" | "align" | "split" => ("", "", Some("right left")),
+            _ => ("", "", None),
+        };
+
+        let mut rows = Vec::new();
+        loop {
+            let mut cells = Vec::new();
+            loop {
+                cells.push(self.row(Until::Cell));
+                if self.peek() != Some('&') {
+                    break;
+                }
+                self.pos += 1;
+            }
+            rows.push(cells);
+
+            match self.peek_command() {
+                Some("\\") => {
+                    self.command();
+                }
+                Some("end") => {
+                    self.command();
+                    self.raw_argument();
+                    break;
+                }
+                _ => break,
+            }
+        }
+        // A `\\` after the last row doesn't start another one
+        if rows.len() > 1
+            && rows
+                .last()
+                .is_some_and(|cells| cells.iter().all(|cell| cell == "<mrow></mrow>"))
+        {
+            rows.pop();
+        }
+
+        let mut table = String::from("<mtable");
+        if let Some(align) = align {
+            write!(table, " columnalign=\"{align}\"").unwrap();
+        }
+        table.push('>');
+        for cells in rows {
+            table += "<mtr>";
+            for cell in cells {
+                write!(table, "<mtd>{cell}</mtd>").unwrap();
+            }
+            table += "</mtr>";
+        }
+        table += "</mtable>";
+
+        if open.is_empty() && close.is_empty() {
+            return table;
+        }
+        let mut mathml = String::from("<mrow>");
+        if !open.is_empty() {
+            write!(mathml, "<mo fence=\"true\">{open}</mo>").unwrap();
+        }
+        mathml += &table;
+        if !close.is_empty() {
+            write!(mathml, "<mo fence=\"true\">{close}</mo>").unwrap();
+        }
+        mathml + "</mrow>"
+    }
+}
+
+fn operator(text: &str) -> String {
+    format!("<mo>{}</mo>", escape(text))
+}
+
+fn space(width: &str) -> String {
+    format!("<mspace width=\"{width}\"/>")
+}
+
+fn escape_char(char: char) -> String {
+    escape(char.encode_utf8(&mut [0; 4]))
+}
+
+/// Writes a letter or digit in a font, using Unicode's mathematical
+/// alphanumeric symbols. Returns `None` if the font doesn't have it.
+fn styled(char: char, font: Font) -> Option<char> {
+    // Letters that were in Unicode before the rest of their font, so are
+    // somewhere else
+    let exception = match (font, char) {
+        (Font::Italic, 'h') => Some('ℎ'),
+        (Font::Script, 'B') => Some('ℬ'),
+        (Font::Script, 'E') => Some('ℰ'),
+        (Font::Script, 'F') => Some('ℱ'),
+        (Font::Script, 'H') => Some('ℋ'),
+        (Font::Script, 'I') => Some('ℐ'),
+        (Font::Script, 'L') => Some('ℒ'),
+        (Font::Script, 'M') => Some('ℳ'),
+        (Font::Script, 'R') => Some('ℛ'),
+        (Font::Script, 'e') => Some('ℯ'),
+        (Font::Script, 'g') => Some('ℊ'),
+        (Font::Script, 'o') => Some('ℴ'),
+        (Font::Fraktur, 'C') => Some('ℭ'),
+        (Font::Fraktur, 'H') => Some('ℌ'),
+        (Font::Fraktur, 'I') => Some('ℑ'),
+        (Font::Fraktur, 'R') => Some('ℜ'),
+        (Font::Fraktur, 'Z') => Some('ℨ'),
+        (Font::DoubleStruck, 'C') => Some('ℂ'),
+        (Font::DoubleStruck, 'H') => Some('ℍ'),
+        (Font::DoubleStruck, 'N') => Some('ℕ'),
+        (Font::DoubleStruck, 'P') => Some('ℙ'),
+        (Font::DoubleStruck, 'Q') => Some('ℚ'),
+        (Font::DoubleStruck, 'R') => Some('ℝ'),
+        (Font::DoubleStruck, 'Z') => Some('ℤ'),
+        _ => None,
+    };
+    if exception.is_some() {
+        return exception;
+    }
+
+    // Where `A`, `a` and `0` are in the font
+    let (upper, lower, digit) = match font {
+        Font::Roman => return None,
+        Font::Bold => (0x1D400, 0x1D41A, Some(0x1D7CE)),
+        Font::Italic => (0x1D434, 0x1D44E, None),
+        Font::BoldItalic => (0x1D468, 0x1D482, None),
+        Font::Script => (0x1D49C, 0x1D4B6, None),
+        Font::Fraktur => (0x1D504, 0x1D51E, None),
+        Font::DoubleStruck => (0x1D538, 0x1D552, Some(0x1D7D8)),
+        Font::SansSerif => (0x1D5A0, 0x1D5BA, Some(0x1D7E2)),
+        Font::Monospace => (0x1D670, 0x1D68A, Some(0x1D7F6)),
+    };
+    let code = match char {
+        'A'..='Z' => upper + (char as u32 - 'A' as u32),
+        'a'..='z' => lower + (char as u32 - 'a' as u32),
+        '0'..='9' => digit? + (char as u32 - '0' as u32),
+        _ => return None,
+    };
+    char::from_u32(code)
+}
+
+fn symbol(name: &str) -> Option<Symbol> {
+    use Symbol::*;
+
+    Some(match name {
+        "alpha" => Identifier("α"),
+        "beta" => Identifier("β"),
+        "gamma" => Identifier("γ"),
+        "delta" => Identifier("δ"),
+        "epsilon" => Identifier("ϵ"),
+        "varepsilon" => Identifier("ε"),
+        "zeta" => Identifier("ζ"),
+        "eta" => Identifier("η"),
+        "theta" => Identifier("θ"),
+        "vartheta" => Identifier("ϑ"),
+        "iota" => Identifier("ι"),
+        "kappa" => Identifier("κ"),
+        "lambda" => Identifier("λ"),
+        "mu" => Identifier("μ"),
+        "nu" => Identifier("ν"),
+        "xi" => Identifier("ξ"),
+        "omicron" => Identifier("ο"),
+        "pi" => Identifier("π"),
+        "varpi" => Identifier("ϖ"),
+        "rho" => Identifier("ρ"),
+        "varrho" => Identifier("ϱ"),
+        "sigma" => Identifier("σ"),
+        "varsigma" => Identifier("ς"),
+        "tau" => Identifier("τ"),
+        "upsilon" => Identifier("υ"),
+        "phi" => Identifier("ϕ"),
+        "varphi" => Identifier("φ"),
+        "chi" => Identifier("χ"),
+        "psi" => Identifier("ψ"),
+        "omega" => Identifier("ω"),
+        "infty" => Identifier("∞"),
+        "partial" => Identifier("∂"),
+        "nabla" => Identifier("∇"),
+        "hbar" => Identifier("ℏ"),
+        "ell" => Identifier("ℓ"),
+        "aleph" => Identifier("ℵ"),
+        "emptyset" | "varnothing" => Identifier("∅"),
+        "Re" => Identifier("ℜ"),
+        "Im" => Identifier("ℑ"),
+        "Gamma" => Upright("Γ"),
+        "Delta" => Upright("Δ"),
+        "Theta" => Upright("Θ"),
+        "Lambda" => Upright("Λ"),
+        "Xi" => Upright("Ξ"),
+        "Pi" => Upright("Π"),
+        "Sigma" => Upright("Σ"),
+        "Upsilon" => Upright("Υ"),
+        "Phi" => Upright("Φ"),
+        "Psi" => Upright("Ψ"),
+        "Omega" => Upright("Ω"),
+        "pm" => Operator("±"),
+        "mp" => Operator("∓"),
+        "times" => Operator("×"),
+        "div" => Operator("÷"),
+        "cdot" => Operator("⋅"),
+        "ast" => Operator("∗"),
+        "star" => Operator("⋆"),
+        "circ" => Operator("∘"),
+        "bullet" => Operator("∙"),
+        "oplus" => Operator("⊕"),
+        "otimes" => Operator("⊗"),
+        "cup" => Operator("∪"),
+        "cap" => Operator("∩"),
+        "setminus" => Operator("∖"),
+        "wedge" | "land" => Operator("∧"),
+        "vee" | "lor" => Operator("∨"),
+        "neg" | "lnot" => Operator("¬"),
+        "leq" | "le" => Operator("≤"),
+        "geq" | "ge" => Operator("≥"),
+        "neq" | "ne" => Operator("≠"),
+        "ll" => Operator("≪"),
+        "gg" => Operator("≫"),
+        "approx" => Operator("≈"),
+        "equiv" => Operator("≡"),
+        "sim" => Operator("∼"),
+        "simeq" => Operator("≃"),
+        "cong" => Operator("≅"),
+        "propto" => Operator("∝"),
+        "in" => Operator("∈"),
+        "notin" => Operator("∉"),
+        "ni" => Operator("∋"),
+        "subset" => Operator("⊂"),
+        "subseteq" => Operator("⊆"),
+        "supset" => Operator("⊃"),
+        "supseteq" => Operator("⊇"),
+        "perp" => Operator("⊥"),
+        "parallel" => Operator("∥"),
+        "mid" => Operator("∣"),
+        "forall" => Operator("∀"),
+        "exists" => Operator("∃"),
+        "angle" => Operator("∠"),
+        "to" | "rightarrow" => Operator("→"),
+        "gets" | "leftarrow" => Operator("←"),
+        "leftrightarrow" => Operator("↔"),
+        "Rightarrow" => Operator("⇒"),
+        "Leftarrow" => Operator("⇐"),
+        "Leftrightarrow" | "iff" => Operator("⇔"),
+        "implies" => Operator("⟹"),
+        "mapsto" => Operator("↦"),
+        "uparrow" => Operator("↑"),
+        "downarrow" => Operator("↓"),
+        "langle" => Operator("⟨"),
+        "rangle" => Operator("⟩"),
+        "lfloor" => Operator("⌊"),
+        "rfloor" => Operator("⌋"),
+        "lceil" => Operator("⌈"),
+        "rceil" => Operator("⌉"),
+        "vert" => Operator("|"),
+        "Vert" => Operator("‖"),
+        "colon" => Operator(":"),
+        "ldots" | "dots" => Operator("…"),
+        "cdots" => Operator("⋯"),
+        "vdots" => Operator("⋮"),
+        "ddots" => Operator("⋱"),
+        "prime" => Operator("′"),
+        "sum" => Big("∑", true),
+        "prod" => Big("∏", true),
+        "coprod" => Big("∐", true),
+        "bigcup" => Big("⋃", true),
+        "bigcap" => Big("⋂", true),
+        "bigoplus" => Big("⨁", true),
+        "bigotimes" => Big("⨂", true),
+        "int" => Big("∫", false),
+        "iint" => Big("∬", false),
+        "iiint" => Big("∭", false),
+        "oint" => Big("∮", false),
+        "lim" => Function("lim", true),
+        "liminf" => Function("lim inf", true),
+        "limsup" => Function("lim sup", true),
+        "max" => Function("max", true),
+        "min" => Function("min", true),
+        "sup" => Function("sup", true),
+        "inf" => Function("inf", true),
+        "det" => Function("det", true),
+        "gcd" => Function("gcd", true),
+        "sin" => Function("sin", false),
+        "cos" => Function("cos", false),
+        "tan" => Function("tan", false),
+        "cot" => Function("cot", false),
+        "sec" => Function("sec", false),
+        "csc" => Function("csc", false),
+        "arcsin" => Function("arcsin", false),
+        "arccos" => Function("arccos", false),
+        "arctan" => Function("arctan", false),
+        "sinh" => Function("sinh", false),
+        "cosh" => Function("cosh", false),
+        "tanh" => Function("tanh", false),
+        "coth" => Function("coth", false),
+        "log" => Function("log", false),
+        "ln" => Function("ln", false),
+        "lg" => Function("lg", false),
+        "exp" => Function("exp", false),
+        "dim" => Function("dim", false),
+        "ker" => Function("ker", false),
+        "deg" => Function("deg", false),
+        "arg" => Function("arg", false),
+        "hom" => Function("hom", false),
+        _ => return None,
+    })
+}
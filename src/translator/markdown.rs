@@ -1,64 +1,73 @@
-use markdown::{
-    to_html_with_options, CompileOptions, Constructs, LineEnding, Options, ParseOptions,
-};
+pub use markdown::{CompileOptions, Constructs};
 
-const OPTIONS: Options = Options {
-    parse: ParseOptions {
-        constructs: Constructs {
-            attention: true,
-            autolink: true,
-            block_quote: true,
-            character_escape: true,
-            character_reference: true,
-            code_indented: true,
-            code_fenced: true,
-            code_text: true,
-            definition: true,
-            frontmatter: false,
-            gfm_autolink_literal: true,
-            gfm_label_start_footnote: true,
-            gfm_footnote_definition: true,
-            gfm_strikethrough: true,
-            gfm_table: true,
-            gfm_task_list_item: true,
-            hard_break_escape: true,
-            hard_break_trailing: true,
-            heading_atx: true,
-            heading_setext: true,
-            html_flow: true,
-            html_text: true,
-            label_start_image: true,
-            label_start_link: true,
-            label_end: true,
-            list_item: true,
-            math_flow: false,
-            math_text: false,
-            mdx_esm: false,
-            mdx_expression_flow: false,
-            mdx_expression_text: false,
-            mdx_jsx_flow: false,
-            mdx_jsx_text: false,
-            thematic_break: true,
-        },
-        gfm_strikethrough_single_tilde: false,
-        math_text_single_dollar: false,
-        mdx_expression_parse: None,
-        mdx_esm_parse: None,
-    },
-    compile: CompileOptions {
-        allow_dangerous_html: true,
-        allow_dangerous_protocol: true,
-        default_line_ending: LineEnding::LineFeed,
-        gfm_footnote_label: None,
-        gfm_footnote_label_tag_name: None,
-        gfm_footnote_label_attributes: None,
-        gfm_footnote_back_label: None,
-        gfm_footnote_clobber_prefix: None,
-        gfm_task_list_item_checkable: true,
-        gfm_tagfilter: false,
-    },
+use {
+    super::latex,
+    markdown::{to_html_with_options, LineEnding, Options, ParseOptions},
 };
 
+/// Changes how Markdown is parsed and translated. The defaults turn on GitHub
+/// flavoured Markdown and allow raw HTML, but leave math off.
+#[derive(Clone, Debug)]
+pub struct MarkdownOptions {
+    /// Which Markdown syntax is recognised.
+    pub constructs: Constructs,
+    /// Let `~one tilde~` be strikethrough, as well as `~~two~~`.
+    pub gfm_strikethrough_single_tilde: bool,
+    /// Let `$one dollar$` be inline math, as well as `$$two$$`.
+    pub math_text_single_dollar: bool,
+    /// How the parsed Markdown is written as HTML, like whether raw HTML is
+    /// allowed and what footnotes are labelled with.
+    pub compile: CompileOptions,
+}
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            constructs: Constructs::gfm(),
+            gfm_strikethrough_single_tilde: false,
+            math_text_single_dollar: false,
+            compile: CompileOptions {
+                allow_dangerous_html: true,
+                allow_dangerous_protocol: true,
+                default_line_ending: LineEnding::LineFeed,
+                gfm_task_list_item_checkable: true,
+                gfm_tagfilter: false,
+                ..CompileOptions::default()
+            },
+        }
+    }
+}
+impl MarkdownOptions {
+    /// The options for the `markdown` crate.
+    pub(super) fn to_options(&self) -> Options {
+        Options {
+            parse: self.parse_options(),
+            compile: self.compile.clone(),
+        }
+    }
+
+    pub(super) fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            constructs: self.constructs.clone(),
+            gfm_strikethrough_single_tilde: self.gfm_strikethrough_single_tilde,
+            math_text_single_dollar: self.math_text_single_dollar,
+            mdx_expression_parse: None,
+            mdx_esm_parse: None,
+        }
+    }
+}
+
+/// Translates Markdown to HTML with the default [`MarkdownOptions`].
 pub fn translate_markdown(src: &str) -> String {
-    to_html_with_options(src, &OPTIONS).unwrap()
+    translate_markdown_with_options(src, &MarkdownOptions::default())
+}
+
+/// Translates Markdown to HTML. If math is turned on, it's translated to
+/// MathML.
+pub fn translate_markdown_with_options(src: &str, options: &MarkdownOptions) -> String {
+    let html = to_html_with_options(src, &options.to_options()).unwrap();
+    if !options.constructs.math_flow && !options.constructs.math_text {
+        return html;
+    }
+
+    latex::translate_math_in(&html).unwrap_or(html)
 }
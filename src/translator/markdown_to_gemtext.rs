@@ -0,0 +1,222 @@
+//! Translates Markdown to Gemtext, so posts can be written once and published
+//! to both a website and a Gemini capsule.
+//!
+//! Gemtext has no inline formatting or inline links, so paragraphs become
+//! single lines of plain text, and the links (and images) in each block are
+//! listed as `=>` lines right after it.
+
+use {
+    super::markdown::MarkdownOptions,
+    markdown::{mdast::Node, to_mdast},
+    std::collections::HashMap,
+};
+
+/// Translates Markdown to Gemtext with the default [`MarkdownOptions`].
+pub fn translate_markdown_to_gemtext(src: &str) -> String {
+    translate_markdown_to_gemtext_with_options(src, &MarkdownOptions::default())
+}
+
+/// Translates Markdown to Gemtext. Only the options for parsing Markdown are
+/// used, since Gemtext can't show HTML.
+pub fn translate_markdown_to_gemtext_with_options(src: &str, options: &MarkdownOptions) -> String {
+    let root = to_mdast(src, &options.parse_options()).unwrap();
+    let mut translator = Translator::default();
+    translator.find_definitions(&root);
+
+    let mut blocks = Vec::new();
+    for node in root.children().into_iter().flatten() {
+        let mut lines = Vec::new();
+        let mut links = Vec::new();
+        translator.block(node, &mut lines, &mut links);
+
+        for (url, label) in links {
+            if label.is_empty() || label == url {
+                lines.push(format!("=> {url}"));
+            } else {
+                lines.push(format!("=> {url} {label}"));
+            }
+        }
+        if !lines.is_empty() {
+            blocks.push(lines.join("\n"));
+        }
+    }
+
+    let mut output = blocks.join("\n\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+#[derive(Default)]
+struct Translator<'a> {
+    /// The URLs of link reference definitions, like `[label]: url`, by their
+    /// identifier.
+    definitions: HashMap<&'a str, &'a str>,
+}
+impl<'a> Translator<'a> {
+    fn find_definitions(&mut self, node: &'a Node) {
+        if let Node::Definition(definition) = node {
+            self.definitions
+                .insert(&definition.identifier, &definition.url);
+        }
+        for child in node.children().into_iter().flatten() {
+            self.find_definitions(child);
+        }
+    }
+
+    /// Translates a block, like a paragraph or a list, to lines of Gemtext.
+    /// Links in the block are added to `links` as `(url, label)`.
+    fn block(&self, node: &Node, lines: &mut Vec<String>, links: &mut Vec<(String, String)>) {
+        match node {
+            Node::Paragraph(paragraph) => {
+                let text = self.inline(&paragraph.children, links);
+                if !text.is_empty() {
+                    lines.push(text);
+                }
+            }
+            Node::Heading(heading) => {
+                // Gemtext only has three levels of headings
+                let level = "#".repeat(heading.depth.clamp(1, 3) as usize);
+                lines.push(format!("{level} {}", self.inline(&heading.children, links)));
+            }
+            Node::Code(code) => {
+                let alt = match (&code.lang, &code.meta) {
+                    (Some(lang), Some(meta)) => format!("{lang} {meta}"),
+                    (Some(lang), None) => lang.clone(),
+                    (None, _) => String::new(),
+                };
+                lines.push(format!("```{alt}"));
+                lines.extend(code.value.lines().map(str::to_string));
+                lines.push("```".into());
+            }
+            Node::Math(math) => {
+                lines.push("```math".into());
+                lines.extend(math.value.lines().map(str::to_string));
+                lines.push("```".into());
+            }
+            Node::Blockquote(quote) => {
+                let mut quoted = Vec::new();
+                for child in &quote.children {
+                    self.block(child, &mut quoted, links);
+                }
+                lines.extend(quoted.into_iter().map(|line| format!("> {line}")));
+            }
+            Node::List(list) => {
+                for item in &list.children {
+                    let Node::ListItem(item) = item else {
+                        continue;
+                    };
+                    let mut text = match item.checked {
+                        Some(true) => String::from("[x]"),
+                        Some(false) => String::from("[ ]"),
+                        None => String::new(),
+                    };
+                    let mut nested = Vec::new();
+                    for child in &item.children {
+                        match child {
+                            Node::Paragraph(paragraph) => {
+                                let paragraph = self.inline(&paragraph.children, links);
+                                if !text.is_empty() && !paragraph.is_empty() {
+                                    text.push(' ');
+                                }
+                                text += &paragraph;
+                            }
+                            // Nested lists are flattened into this one
+                            other => self.block(other, &mut nested, links),
+                        }
+                    }
+                    lines.push(format!("* {text}"));
+                    lines.append(&mut nested);
+                }
+            }
+            Node::Table(table) => {
+                lines.push("```".into());
+                for row in &table.children {
+                    let cells: Vec<String> = row
+                        .children()
+                        .into_iter()
+                        .flatten()
+                        .map(|cell| self.inline(cell.children().map_or(&[], Vec::as_slice), links))
+                        .collect();
+                    lines.push(cells.join(" | "));
+                }
+                lines.push("```".into());
+            }
+            Node::FootnoteDefinition(footnote) => {
+                let mut text = Vec::new();
+                for child in &footnote.children {
+                    self.block(child, &mut text, links);
+                }
+                let label = footnote.label.as_deref().unwrap_or(&footnote.identifier);
+                lines.push(format!("[{label}] {}", text.join(" ")));
+            }
+            // Gemtext can't show HTML or thematic breaks, and definitions and
+            // front matter aren't shown at all
+            Node::Html(_)
+            | Node::ThematicBreak(_)
+            | Node::Definition(_)
+            | Node::Toml(_)
+            | Node::Yaml(_) => {}
+            other => {
+                for child in other.children().into_iter().flatten() {
+                    self.block(child, lines, links);
+                }
+            }
+        }
+    }
+
+    /// Translates inline content, like the contents of a paragraph, to a single
+    /// line of plain text. Links and images are added to `links`.
+    fn inline(&self, nodes: &[Node], links: &mut Vec<(String, String)>) -> String {
+        let mut text = String::new();
+        for node in nodes {
+            match node {
+                Node::Text(node) => text += &node.value.replace('\n', " "),
+                Node::Break(_) => text.push(' '),
+                Node::InlineCode(code) => {
+                    text.push('`');
+                    text += &code.value;
+                    text.push('`');
+                }
+                Node::InlineMath(math) => text += &math.value,
+                Node::Link(link) => {
+                    let label = self.inline(&link.children, links);
+                    push_link(links, &link.url, &label);
+                    text += &label;
+                }
+                Node::LinkReference(reference) => {
+                    let label = self.inline(&reference.children, links);
+                    if let Some(url) = self.definitions.get(reference.identifier.as_str()) {
+                        push_link(links, url, &label);
+                    }
+                    text += &label;
+                }
+                // Images are only shown as links
+                Node::Image(image) => push_link(links, &image.url, &image.alt),
+                Node::ImageReference(reference) => {
+                    if let Some(url) = self.definitions.get(reference.identifier.as_str()) {
+                        push_link(links, url, &reference.alt);
+                    }
+                }
+                Node::FootnoteReference(reference) => {
+                    let label = reference.label.as_deref().unwrap_or(&reference.identifier);
+                    text += &format!("[{label}]");
+                }
+                Node::Html(_) => {}
+                other => {
+                    text += &self.inline(other.children().map_or(&[], Vec::as_slice), links);
+                }
+            }
+        }
+
+        text.trim().to_string()
+    }
+}
+
+/// Adds a link, unless the block already links to the same URL.
+fn push_link(links: &mut Vec<(String, String)>, url: &str, label: &str) {
+    if !links.iter().any(|(existing, _)| existing == url) {
+        links.push((url.to_string(), label.to_string()));
+    }
+}
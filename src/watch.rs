@@ -0,0 +1,197 @@
+//! Watches a webby project for changes and incrementally rebuilds it.
+//!
+//! This polls modification times instead of using OS-specific file watching
+//! APIs, so it works everywhere without extra dependencies. Each target
+//! remembers every file it read during its last build (its source files, the
+//! folders it walked, and anything pulled in with `#!INCLUDE` or
+//! `#!INCLUDE_BASE64`), and is only rebuilt when one of those changes.
+
+use {
+    crate::{build_targets, config::Overrides, BuildResult, Cache, Config, WebbyError},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        thread,
+        time::{Duration, SystemTime},
+    },
+};
+
+/// How long to wait between checking files for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The result of a rebuild.
+#[derive(Default)]
+pub struct Rebuild {
+    /// If `webby.toml` changed and was reloaded.
+    pub config_reloaded: bool,
+    /// The source paths of the targets that were rebuilt.
+    pub rebuilt: Vec<PathBuf>,
+    /// The output files whose contents changed.
+    pub written: Vec<PathBuf>,
+    pub errors: Vec<WebbyError>,
+}
+
+/// Tracks the files a project depends on and rebuilds the targets that use
+/// them when they change.
+pub struct Watcher {
+    config_path: PathBuf,
+    overrides: Overrides,
+    config_modified: Option<SystemTime>,
+    config: Option<Config>,
+    cache: Option<Cache>,
+    /// The files each target in `config` read during its last build, and
+    /// when they were last modified. Indices match `config.targets`.
+    targets: Vec<Vec<(PathBuf, Option<SystemTime>)>>,
+}
+impl Watcher {
+    /// Loads the config at `config_path` and builds every target in it.
+    /// `overrides` are reapplied every time the config is reloaded.
+    pub fn new(config_path: &Path, overrides: Overrides) -> (Self, Rebuild) {
+        let mut this = Self {
+            config_path: config_path.to_path_buf(),
+            overrides,
+            config_modified: None,
+            config: None,
+            cache: None,
+            targets: Vec::new(),
+        };
+        let rebuild = this.reload_config();
+
+        (this, rebuild)
+    }
+
+    /// The currently loaded config, if it loaded successfully.
+    pub fn config(&self) -> Option<&Config> {
+        self.config.as_ref()
+    }
+
+    /// Checks if any files changed since the last build, and rebuilds the
+    /// targets that depend on them. Returns `None` if nothing changed.
+    pub fn poll(&mut self) -> Option<Rebuild> {
+        if modified(&self.config_path) != self.config_modified {
+            return Some(self.reload_config());
+        }
+
+        let (config, cache) = (self.config.as_ref()?, self.cache.as_ref()?);
+        let stale: Vec<usize> = self
+            .targets
+            .iter()
+            .enumerate()
+            .filter(|(_, files)| {
+                files
+                    .iter()
+                    .any(|(path, last_modified)| modified(path) != *last_modified)
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+
+        if stale.is_empty() {
+            return None;
+        }
+
+        let results = build_targets(config, stale.iter().map(|idx| &config.targets[*idx]), cache);
+        let mut rebuild = Rebuild::default();
+        for (idx, result) in stale.into_iter().zip(results) {
+            rebuild.rebuilt.push(config.targets[idx].path.clone());
+            self.targets[idx] = Self::record(result, &mut rebuild);
+        }
+        Self::write_generated(config, &mut rebuild);
+        if let Err(err) = cache.save() {
+            rebuild.errors.push(err);
+        }
+
+        Some(rebuild)
+    }
+
+    fn reload_config(&mut self) -> Rebuild {
+        self.config_modified = modified(&self.config_path);
+
+        let mut config = match Config::load(&self.config_path) {
+            Ok(config) => config,
+            Err(err) => {
+                // Keep the old config, so a typo in `webby.toml` doesn't stop
+                // the other files from being watched
+                return Rebuild {
+                    errors: vec![err],
+                    ..Default::default()
+                };
+            }
+        };
+
+        config.apply(&self.overrides);
+
+        let mut rebuild = Rebuild {
+            config_reloaded: true,
+            ..Default::default()
+        };
+        let cache = Cache::load(&config.output_dir);
+        self.targets = build_targets(&config, &config.targets, &cache)
+            .into_iter()
+            .map(|result| Self::record(result, &mut rebuild))
+            .collect();
+        Self::write_generated(&config, &mut rebuild);
+        if let Err(err) = cache.save() {
+            rebuild.errors.push(err);
+        }
+        rebuild.rebuilt = config
+            .targets
+            .iter()
+            .map(|target| target.path.clone())
+            .collect();
+        self.config = Some(config);
+        self.cache = Some(cache);
+
+        rebuild
+    }
+
+    /// Rewrites the sitemap, `robots.txt`, and highlighting theme after
+    /// targets are rebuilt, since pages may have been added, removed, or
+    /// changed.
+    fn write_generated(config: &Config, rebuild: &mut Rebuild) {
+        let (mut files, mut errors) = crate::write_generated(config);
+        rebuild.written.append(&mut files);
+        rebuild.errors.append(&mut errors);
+    }
+
+    fn record(
+        mut result: BuildResult,
+        rebuild: &mut Rebuild,
+    ) -> Vec<(PathBuf, Option<SystemTime>)> {
+        rebuild.written.append(&mut result.written);
+        if let Err(err) = result.result {
+            rebuild.errors.push(err);
+        }
+
+        result
+            .dependencies
+            .into_iter()
+            .map(|path| {
+                let modified = modified(&path);
+                (path, modified)
+            })
+            .collect()
+    }
+}
+
+/// Builds the project at `config_path`, then rebuilds it whenever its files
+/// change. `on_rebuild` is called after every build, including the first one.
+/// This never returns.
+pub fn watch(config_path: &Path, overrides: Overrides, mut on_rebuild: impl FnMut(&Rebuild)) -> ! {
+    let (mut watcher, rebuild) = Watcher::new(config_path, overrides);
+    on_rebuild(&rebuild);
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        if let Some(rebuild) = watcher.poll() {
+            on_rebuild(&rebuild);
+        }
+    }
+}
+
+/// When a file was last modified, or `None` if it doesn't exist.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
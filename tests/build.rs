@@ -0,0 +1,1217 @@
+use {
+    std::{env, fs, path::PathBuf, time::Duration},
+    webby::{
+        build_target, cache::CACHE_FILE, config::Overrides, highlight, sitemap, watch::Watcher,
+        write_generated, Cache, Config, ErrorKind, FileType, Mode, Profile,
+    },
+};
+
+/// Makes an empty folder for a test to build a project in.
+fn project_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("webby-test-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("out")).unwrap();
+    dir
+}
+
+#[test]
+fn cache_skips_unchanged_files() {
+    let dir = project_dir("cache");
+    fs::write(dir.join("index.html"), "<p>#!INCLUDE(part.html)</p>").unwrap();
+    fs::write(dir.join("part.html"), "<b>one</b>").unwrap();
+    let config = Config::parse(
+        "output = \"out\"\n\n[[target]]\npath = \"index.html\"",
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let target = &config.targets[0];
+    let output = dir.join("out/index.html");
+
+    let cache = Cache::load(&dir.join("out"));
+    let result = build_target(&config, target, &cache);
+    result.result.unwrap();
+    assert!(result.dependencies.contains(&dir.join("part.html")));
+    cache.save().unwrap();
+    assert!(dir.join("out").join(CACHE_FILE).is_file());
+
+    // Unchanged: the output shouldn't be touched, but the include should still
+    // be reported as a dependency
+    let modified = fs::metadata(&output).unwrap().modified().unwrap();
+    let cache = Cache::load(&dir.join("out"));
+    let result = build_target(&config, target, &cache);
+    result.result.unwrap();
+    assert!(result.dependencies.contains(&dir.join("part.html")));
+    assert!(result.written.is_empty());
+    assert_eq!(fs::metadata(&output).unwrap().modified().unwrap(), modified);
+
+    // Changing an included file rebuilds the output
+    fs::write(dir.join("part.html"), "<b>two</b>").unwrap();
+    build_target(&config, target, &cache).result.unwrap();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "<p><b>two</b></p>");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn cache_rebuilds_pages_when_urls_change() {
+    let dir = project_dir("cache-urls");
+    fs::create_dir_all(dir.join("posts")).unwrap();
+    fs::write(dir.join("posts/hello.md"), "# Hello").unwrap();
+    fs::write(dir.join("index.html"), "#!FOREACH(posts/*.md, item.html)").unwrap();
+    fs::write(dir.join("item.html"), "<a href=\"#!META(url)\"></a>").unwrap();
+    let config = |clean_urls| {
+        Config::parse(
+            &format!(
+                r#"
+output = "out"
+
+[[target]]
+path = "index.html"
+
+[[target]]
+path = "posts/*.md"
+clean_urls = {clean_urls}
+"#
+            ),
+            &dir.join("webby.toml"),
+        )
+        .unwrap()
+    };
+
+    let cache = Cache::new(&dir.join("out"));
+    let before = config(false);
+    build_target(&before, &before.targets[0], &cache)
+        .result
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<a href=\"/posts/hello.html\"></a>"
+    );
+
+    // Only the posts' target changed, but the index links to them, so it's
+    // rebuilt
+    let after = config(true);
+    let result = build_target(&after, &after.targets[0], &cache);
+    result.result.unwrap();
+    assert_eq!(result.written, [dir.join("out/index.html")]);
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<a href=\"/posts/hello/\"></a>"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn watcher_rebuilds_targets_that_use_changed_files() {
+    let dir = project_dir("watch");
+    fs::write(dir.join("index.html"), "<p>#!INCLUDE(part.html)</p>").unwrap();
+    fs::write(dir.join("part.html"), "one").unwrap();
+    fs::write(dir.join("about.html"), "<p>About</p>").unwrap();
+    let config = "output = \"out\"\n\n[[target]]\npath = \"index.html\"\n\n[[target]]\npath = \"about.html\"\n";
+    fs::write(dir.join("webby.toml"), config).unwrap();
+    // Writes a file and moves its modification time forward, so the change
+    // is seen even if the file system's clock is coarse
+    let edit = |name: &str, contents: &str| {
+        let path = dir.join(name);
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        fs::write(&path, contents).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified + Duration::from_secs(2))
+            .unwrap();
+    };
+
+    let (mut watcher, rebuild) = Watcher::new(&dir.join("webby.toml"), Overrides::default());
+    assert!(rebuild.errors.is_empty());
+    assert!(rebuild.config_reloaded);
+    assert_eq!(rebuild.rebuilt.len(), 2);
+    assert!(watcher.poll().is_none());
+
+    // Only the target that includes the partial is rebuilt
+    edit("part.html", "two");
+    let rebuild = watcher.poll().unwrap();
+    assert!(rebuild.errors.is_empty());
+    assert!(!rebuild.config_reloaded);
+    assert_eq!(rebuild.rebuilt, [dir.join("index.html")]);
+    assert_eq!(rebuild.written, [dir.join("out/index.html")]);
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<p>two</p>"
+    );
+    assert!(watcher.poll().is_none());
+
+    // Changing webby.toml reloads it
+    edit(
+        "webby.toml",
+        &config.replace("about.html", "about.html\"\noutput = \"info.html"),
+    );
+    let rebuild = watcher.poll().unwrap();
+    assert!(rebuild.errors.is_empty());
+    assert!(rebuild.config_reloaded);
+    assert_eq!(
+        fs::read_to_string(dir.join("out/info.html")).unwrap(),
+        "<p>About</p>"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn globs_and_excludes_pick_target_files() {
+    let dir = project_dir("glob");
+    for (path, contents) in [
+        ("posts/one.md", "# One"),
+        ("posts/2024/two.md", "# Two"),
+        ("posts/2024/photo.psd", "not markdown"),
+        ("posts/2024/notes.txt", "not markdown"),
+        ("posts/drafts/three.md", "# Three"),
+        ("posts/.hidden.md", "# Hidden"),
+        ("posts/one.md~", "# Backup"),
+        ("posts/.one.md.swp", "swap"),
+    ] {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    let config = Config::parse(
+        "output = \"out\"\n[[target]]\npath = \"posts/**\"\nexclude = [\"drafts/**\", \"*.psd\"]",
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let target = &config.targets[0];
+    assert_eq!(target.path, dir.join("posts"));
+    assert_eq!(target.output, dir.join("out/posts"));
+
+    let cache = Cache::new(&dir.join("out"));
+    let result = build_target(&config, target, &cache);
+    result.result.unwrap();
+    let mut written = result.written;
+    written.sort();
+    assert_eq!(
+        written,
+        [
+            dir.join("out/posts/2024/notes.txt"),
+            dir.join("out/posts/2024/two.md"),
+            dir.join("out/posts/one.md"),
+        ]
+    );
+    assert!(!dir.join("out/posts/drafts").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn translated_files_get_html_extensions() {
+    let dir = project_dir("extensions");
+    fs::create_dir_all(dir.join("posts")).unwrap();
+    fs::write(dir.join("about.md"), "# About").unwrap();
+    fs::write(dir.join("posts/first.md"), "# First").unwrap();
+    fs::write(dir.join("posts/index.gmi"), "# Posts").unwrap();
+    fs::write(dir.join("notes.md"), "# Notes").unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+clean_urls = true
+
+[output_extensions]
+gemtext = "htm"
+
+[[target]]
+path = "about.md"
+
+[[target]]
+path = "posts/*"
+mode = "compile"
+
+[[target]]
+path = "notes.md"
+output = "notes.txt"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+    assert!(dir.join("out/about/index.html").is_file());
+    assert!(dir.join("out/posts/first/index.html").is_file());
+    assert!(dir.join("out/posts/index.htm").is_file());
+    assert!(dir.join("out/notes.txt").is_file());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn layouts_wrap_translated_files() {
+    let dir = project_dir("layout");
+    fs::create_dir_all(dir.join("layouts")).unwrap();
+    fs::write(
+        dir.join("layouts/post.html"),
+        "<html>\n  <head><title>#!TITLE() - My Site</title></head>\n  <body>\n    #!INCLUDE(nav.html)\n    <main>#!CONTENT()</main>\n  </body>\n</html>\n",
+    )
+    .unwrap();
+    fs::write(dir.join("layouts/nav.html"), "<nav>Home</nav>").unwrap();
+    fs::write(dir.join("post.md"), "# Hello *there*\n\nSome text.").unwrap();
+    fs::write(dir.join("plain.md"), "No heading here.").unwrap();
+    fs::write(
+        dir.join("titled.md"),
+        "+++\ntitle = \"Hello & <world>\"\n+++\n",
+    )
+    .unwrap();
+    fs::write(dir.join("heading.md"), "# Tom & Jerry").unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+layout = "layouts/post.html"
+
+[[target]]
+path = "post.md"
+
+[[target]]
+path = "plain.md"
+layout = false
+
+[[target]]
+path = "titled.md"
+
+[[target]]
+path = "heading.md"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+
+    let cache = Cache::new(&dir.join("out"));
+    let result = build_target(&config, &config.targets[0], &cache);
+    result.result.unwrap();
+    assert!(result.dependencies.contains(&dir.join("layouts/nav.html")));
+    assert_eq!(
+        fs::read_to_string(dir.join("out/post.html")).unwrap(),
+        "<html><head><title>Hello there - My Site</title></head><body><nav>Home</nav><main><h1 id=\"hello-there\">Hello <em>there</em></h1><p>Some text.</p></main></body></html>"
+    );
+
+    build_target(&config, &config.targets[1], &cache)
+        .result
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("out/plain.html")).unwrap(),
+        "<p>No heading here.</p>"
+    );
+
+    // Titles are escaped the same way whether they come from the front matter
+    // or the first heading
+    for target in &config.targets[2..] {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+    assert!(fs::read_to_string(dir.join("out/titled.html"))
+        .unwrap()
+        .contains("<title>Hello &amp; &lt;world&gt; - My Site</title>"));
+    assert!(fs::read_to_string(dir.join("out/heading.html"))
+        .unwrap()
+        .contains("<title>Tom &amp; Jerry - My Site</title>"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn front_matter_is_stripped_and_readable() {
+    let dir = project_dir("front-matter");
+    fs::write(
+        dir.join("layout.html"),
+        "<html><head><title>#!TITLE()</title></head><body><time>#!META(date)</time>#!CONTENT()<p>#!META(tags)</p><p>#!META(author.name, Anonymous)</p></body></html>",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("post.md"),
+        "+++\ntitle = \"Front matter\"\ndate = \"2024-06-01\"\ntags = [\"rust\", \"web\"]\n+++\n\n# A heading\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("draft.gmi"),
+        "+++\ndraft = true\n+++\n# Not done yet\n",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        "output = \"out\"\nlayout = \"layout.html\"\n[[target]]\npath = \"post.md\"\n[[target]]\npath = \"draft.gmi\"",
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.join("out/post.html")).unwrap(),
+        "<html><head><title>Front matter</title></head><body><time>2024-06-01</time><h1 id=\"a-heading\">A heading</h1><p>rust, web</p><p>Anonymous</p></body></html>"
+    );
+    assert!(!dir.join("out/draft.html").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn unquoted_dates_in_front_matter_are_errors() {
+    let dir = project_dir("front-matter-dates");
+    fs::create_dir_all(dir.join("posts")).unwrap();
+    fs::write(
+        dir.join("posts/hello.md"),
+        "+++\ntitle = \"Hello # 2024-01-02\"\ntags = [\"a\", 2024-01-02]\n+++\n# Hello",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("posts/later.md"),
+        "+++\ntitle = \"Later\"\n\ndate = 2024-01-02\n+++\n# Later",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("index.html"),
+        "#!FOREACH(posts/later.md, item.html)",
+    )
+    .unwrap();
+    fs::write(dir.join("item.html"), "#!META(title)").unwrap();
+
+    let config = Config::parse(
+        "output = \"out\"\n[[target]]\npath = \"posts/hello.md\"\n[[target]]\npath = \"index.html\"",
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    let err = build_target(&config, &config.targets[0], &cache)
+        .result
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::FrontMatter);
+    assert_eq!(err.path, Some(dir.join("posts/hello.md")));
+    assert_eq!(err.position.map(|position| position.line), Some(3));
+
+    // Pages that list the post fail with an error instead of a panic too
+    let err = build_target(&config, &config.targets[1], &cache)
+        .result
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::FrontMatter);
+    let cause = err.cause.unwrap().to_string();
+    assert!(
+        cause.contains("later.md:4:8") && cause.contains("isn't in quotes"),
+        "{cause}"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn variables_from_config_targets_and_front_matter() {
+    let dir = project_dir("vars");
+    fs::write(
+        dir.join("index.html"),
+        "<p>#!VAR(site_name) by #!VAR(author.name) in #!VAR(lang)</p>",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("post.md"),
+        "+++\nlang = \"fr\"\n+++\n#!VAR(site_name) in #!VAR(lang), #!VAR(missing, no default needed)",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+
+[vars]
+site_name = "My Site"
+lang = "en"
+author = { name = "Ash" }
+
+[[target]]
+path = "index.html"
+vars = { lang = "de" }
+
+[[target]]
+path = "post.md"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<p>My Site by Ash in de</p>"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/post.html")).unwrap(),
+        "<p>My Site in fr, no default needed</p>"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn profiles_control_minification_output_and_vars() {
+    let dir = project_dir("profiles");
+    fs::write(
+        dir.join("index.html"),
+        "<html>\n  <!-- nav -->\n  <style>p { color: red; /* note */ }</style>\n  <p>#!VAR(env)</p>\n</html>\n",
+    )
+    .unwrap();
+
+    let mut config = Config::parse(
+        r#"
+output = "out"
+
+[vars]
+env = "shared"
+
+[profile.dev]
+output = "dev-out"
+minify = false
+
+[profile.dev.vars]
+env = "dev"
+
+[profile.release]
+strip_comments = false
+
+[[target]]
+path = "index.html"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+
+    assert_eq!(config.output_dir, dir.join("dev-out"));
+    let cache = Cache::new(&config.output_dir);
+    build_target(&config, &config.targets[0], &cache)
+        .result
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("dev-out/index.html")).unwrap(),
+        "<html>\n  \n  <style>p { color: red;  }</style>\n  <p>dev</p>\n</html>\n"
+    );
+
+    config.set_profile(Profile::Release);
+    assert_eq!(config.output_dir, dir.join("out"));
+    let cache = Cache::new(&config.output_dir);
+    build_target(&config, &config.targets[0], &cache)
+        .result
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<html><style>p{color:red;}</style><p>shared</p></html>"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn javascript_is_minified() {
+    let dir = project_dir("javascript");
+    fs::write(
+        dir.join("app.js"),
+        "// Greets the reader\nconst name = `reader`\nalert('hi ' + name) /* done */\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("cli.js"),
+        "#!/usr/bin/env node\n// Prints the version\nconsole.log(\"#!VAR(version)\")\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("index.html"),
+        "<body>\n  <script>\n    let x = 1 // one\n    x++\n  </script>\n  <script type=\"text/html\">  <p>kept</p>  </script>\n  #!MINIFY(js, if (a) { b() })\n</body>\n",
+    )
+    .unwrap();
+
+    let mut config = Config::parse(
+        r#"
+output = "out"
+
+[profile.dev]
+minify = false
+
+[[target]]
+path = "app.js"
+
+[[target]]
+path = "index.html"
+
+[[target]]
+path = "cli.js"
+vars = { version = "1.0" }
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    assert!(matches!(config.targets[0].mode, Mode::Compile));
+    assert_eq!(config.targets[0].file_type, FileType::Js);
+
+    let cache = Cache::new(&config.output_dir);
+    build_target(&config, &config.targets[0], &cache)
+        .result
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("out/app.js")).unwrap(),
+        "\nconst name = `reader`\nalert('hi ' + name)  \n"
+    );
+
+    config.set_profile(Profile::Release);
+    let cache = Cache::new(&config.output_dir);
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+    assert_eq!(
+        fs::read_to_string(dir.join("out/app.js")).unwrap(),
+        "const name=`reader`\nalert('hi '+name)"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<body><script>let x=1\nx++</script><script type=\"text/html\">  <p>kept</p>  </script>if(a){b()}</body>"
+    );
+    // A hashbang isn't read as a macro, and is kept when minifying
+    assert_eq!(
+        fs::read_to_string(dir.join("out/cli.js")).unwrap(),
+        "#!/usr/bin/env node\nconsole.log(\"1.0\")"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn foreach_lists_collections() {
+    let dir = project_dir("foreach");
+    fs::create_dir_all(dir.join("posts")).unwrap();
+    fs::create_dir_all(dir.join("partials")).unwrap();
+    fs::write(
+        dir.join("index.html"),
+        "<ul>#!FOREACH(posts/*.md, partials/card.html, sort=date, limit=2)</ul>",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("partials/card.html"),
+        "<li><a href=\"#!META(url)\">#!META(title)</a></li>",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("posts/first.md"),
+        "+++\ntitle = \"First\"\ndate = \"2024-01-01\"\n+++\n# First",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("posts/second.md"),
+        "+++\ntitle = \"Second\"\ndate = \"2024-02-01\"\n+++\n# Second",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("posts/secret.md"),
+        "+++\ntitle = \"Secret\"\ndate = \"2024-03-01\"\ndraft = true\n+++\n# Secret",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+clean_urls = true
+
+[[target]]
+path = "index.html"
+
+[[target]]
+path = "posts/*.md"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let index = &config.targets[0];
+    let cache = Cache::new(&dir.join("out"));
+    build_target(&config, index, &cache).result.unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<ul><li><a href=\"/posts/second/\">Second</a></li><li><a href=\"/posts/first/\">First</a></li></ul>"
+    );
+
+    // Adding a post should rebuild the listing
+    fs::write(
+        dir.join("posts/third.md"),
+        "+++\ntitle = \"Third\"\ndate = \"2024-04-01\"\n+++\n# Third",
+    )
+    .unwrap();
+    build_target(&config, index, &cache).result.unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<ul><li><a href=\"/posts/third/\">Third</a></li><li><a href=\"/posts/second/\">Second</a></li></ul>"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn feeds_list_posts_with_absolute_urls() {
+    let dir = project_dir("feed");
+    fs::create_dir_all(dir.join("posts")).unwrap();
+    fs::write(
+        dir.join("posts/hello.md"),
+        "+++\ntitle = \"Hello & welcome\"\ndate = \"2024-06-01\"\nsummary = \"The first post\"\n+++\n# Hello\n\nBy #!VAR(author)",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("posts/later.gmi"),
+        "+++\ntitle = \"Later\"\ndate = \"2024-07-02T09:30:00+02:00\"\n+++\n# Later",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+base_url = "https://example.com/"
+clean_urls = true
+
+[vars]
+author = "Ash"
+
+[[target]]
+path = "posts/*"
+mode = "compile"
+
+[[feed]]
+path = "posts/*"
+title = "My Blog"
+author = "Ash"
+atom = "atom.xml"
+rss = "rss.xml"
+
+[[feed]]
+path = "posts/*"
+title = "Anonymous"
+atom = "anonymous.xml"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+
+    let atom = fs::read_to_string(dir.join("out/atom.xml")).unwrap();
+    assert!(atom.contains("<link rel=\"self\" href=\"https://example.com/atom.xml\"/>"));
+    assert!(atom.contains("<updated>2024-07-02T09:30:00+02:00</updated>"));
+    assert!(atom.contains("<title>Hello &amp; welcome</title>"));
+    assert!(atom.contains("<link href=\"https://example.com/posts/hello/\"/>"));
+    assert!(atom.contains("<summary>The first post</summary>"));
+    assert!(atom.contains("&lt;h1 id=&quot;hello&quot;&gt;Hello&lt;/h1&gt;"));
+    assert!(atom.contains("By Ash"));
+    assert!(atom.contains("<author><name>Ash</name></author>"));
+    assert!(atom.contains(
+        "<feed xmlns=\"http://www.w3.org/2005/Atom\" xml:base=\"https://example.com/\">"
+    ));
+    assert!(atom.contains("<content type=\"html\" xml:base=\"https://example.com/posts/hello/\">"));
+    // Atom feeds always have an author
+    assert!(fs::read_to_string(dir.join("out/anonymous.xml"))
+        .unwrap()
+        .contains("<author><name>Anonymous</name></author>"));
+    // Newest first
+    assert!(atom.find("Later").unwrap() < atom.find("Hello").unwrap());
+
+    let rss = fs::read_to_string(dir.join("out/rss.xml")).unwrap();
+    assert!(rss.contains("<rss version=\"2.0\""));
+    assert!(rss.contains("<pubDate>Sat, 01 Jun 2024 00:00:00 +0000</pubDate>"));
+    assert!(rss.contains("<pubDate>Tue, 02 Jul 2024 09:30:00 +0200</pubDate>"));
+    assert!(rss.contains("<link>https://example.com/posts/later/</link>"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sitemap_lists_built_pages() {
+    let dir = project_dir("sitemap");
+    fs::create_dir_all(dir.join("posts")).unwrap();
+    fs::write(dir.join("index.html"), "<p>home</p>").unwrap();
+    fs::write(dir.join("404.html"), "<p>missing</p>").unwrap();
+    fs::write(dir.join("style.css"), "p { color: red; }").unwrap();
+    fs::write(dir.join("posts/hello.md"), "# Hello").unwrap();
+    fs::write(
+        dir.join("posts/draft.md"),
+        "+++\ndraft = true\n+++\n# Draft",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+base_url = "https://example.com"
+clean_urls = true
+sitemap = true
+robots = true
+
+[[target]]
+path = "index.html"
+
+[[target]]
+path = "404.html"
+sitemap = false
+
+[[target]]
+path = "style.css"
+
+[[target]]
+path = "posts/*.md"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+    let written = sitemap::write(&config).unwrap();
+    assert_eq!(written.len(), 2);
+
+    let sitemap = fs::read_to_string(dir.join("out/sitemap.xml")).unwrap();
+    let urls: Vec<_> = sitemap
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("<loc>"))
+        .collect();
+    assert_eq!(
+        urls,
+        [
+            "https://example.com/</loc>",
+            "https://example.com/posts/hello/</loc>"
+        ]
+    );
+    assert_eq!(sitemap.matches("<lastmod>").count(), 2);
+    assert_eq!(
+        fs::read_to_string(dir.join("out/robots.txt")).unwrap(),
+        "User-agent: *\nAllow: /\n\nSitemap: https://example.com/sitemap.xml\n"
+    );
+
+    // Nothing changed, so nothing is rewritten
+    assert!(sitemap::write(&config).unwrap().is_empty());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn targets_can_publish_to_several_outputs() {
+    let dir = project_dir("outputs");
+    fs::create_dir_all(dir.join("posts")).unwrap();
+    fs::write(
+        dir.join("posts/hello.gmi"),
+        "+++\ntitle = \"Hello\"\n+++\n# Hello\n=> /about.gmi About",
+    )
+    .unwrap();
+    fs::write(dir.join("index.gmi"), "#!FOREACH(posts/*.gmi, item.gmi)").unwrap();
+    fs::write(dir.join("item.gmi"), "=> #!META(url) #!META(title)\n").unwrap();
+    fs::write(
+        dir.join("about.html"),
+        "<nav><a href=\"/\">Home</a></nav><h1>About</h1><p>Hi!</p>",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+
+[outputs]
+site = "site"
+capsule = { path = "capsule", format = "gemtext" }
+
+[[target]]
+path = "posts/*.gmi"
+outputs = ["site", "capsule"]
+
+[[target]]
+path = "index.gmi"
+outputs = ["capsule"]
+
+[[target]]
+path = "about.html"
+outputs = ["site", "capsule"]
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    assert_eq!(config.targets.len(), 5);
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+
+    assert!(fs::read_to_string(dir.join("site/posts/hello.html"))
+        .unwrap()
+        .contains("<h1 id=\"hello\">Hello</h1>"));
+    assert_eq!(
+        fs::read_to_string(dir.join("capsule/posts/hello.gmi")).unwrap(),
+        "# Hello\n=> /about.gmi About"
+    );
+    // Links in the capsule go to the capsule's copy of the post
+    assert_eq!(
+        fs::read_to_string(dir.join("capsule/index.gmi")).unwrap(),
+        "=> /posts/hello.gmi Hello\n"
+    );
+    // HTML pages are translated for the capsule
+    assert_eq!(
+        fs::read_to_string(dir.join("capsule/about.gmi")).unwrap(),
+        "# About\n\nHi!\n"
+    );
+    assert!(dir.join("site/about.html").is_file());
+    assert!(!dir.join("out").join("posts").exists());
+
+    // The project's `output` is cleaned along with the extra outputs
+    assert_eq!(config.clean().unwrap().len(), 3);
+    assert!(!dir.join("site").exists() && !dir.join("capsule").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn targets_can_be_converted_to_gemtext() {
+    let dir = project_dir("convert");
+    fs::create_dir_all(dir.join("old")).unwrap();
+    fs::write(
+        dir.join("old/page.html"),
+        "<nav><a href=\"/\">Home</a></nav><h1>Old page</h1><p>Still here.</p>",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+
+[[target]]
+path = "old/*.html"
+format = "gemtext"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    build_target(&config, &config.targets[0], &cache)
+        .result
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(dir.join("out/old/page.gmi")).unwrap(),
+        "# Old page\n\nStill here.\n"
+    );
+    assert!(!dir.join("out/old/page.html").exists());
+
+    let error = Config::parse(
+        "[[target]]\npath = \"a.html\"\nformat = \"pdf\"",
+        &dir.join("webby.toml"),
+    )
+    .err()
+    .unwrap();
+    assert!(error.message.contains("Unknown format `pdf`"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn targets_override_gemtext_options() {
+    let dir = project_dir("gemtext-options");
+    fs::write(
+        dir.join("index.gmi"),
+        "# Home\n=> about.gmi About\n=> gemini://example.com/ Capsule\n=> cat.png A cat",
+    )
+    .unwrap();
+    fs::write(dir.join("plain.gmi"), "=> about.gmi About").unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+
+[gemtext]
+wrapper = "main"
+rewrite_gmi_links = true
+gemini_proxy = "https://proxy.example/gemini/"
+
+[gemtext.classes]
+h1 = "title"
+
+[[target]]
+path = "index.gmi"
+gemtext = { inline_images = true, classes = { link = "link" } }
+
+[[target]]
+path = "plain.gmi"
+gemtext = { wrapper = "article", rewrite_gmi_links = false }
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.join("out/index.html")).unwrap(),
+        "<main>\n<h1 class=\"title\" id=\"home\">Home</h1>\n<p class=\"link\"><a href=\"about.html\">About</a></p>\n<p class=\"link\"><a href=\"https://proxy.example/gemini/example.com/\">Capsule</a></p>\n<p><img src=\"cat.png\" alt=\"A cat\"></p>\n</main>\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/plain.html")).unwrap(),
+        "<article>\n<p><a href=\"about.gmi\">About</a></p>\n</article>\n"
+    );
+
+    let error = Config::parse(
+        "[gemtext]\nnot_an_option = true\n[[target]]\npath = \"index.gmi\"",
+        &dir.join("webby.toml"),
+    )
+    .err()
+    .unwrap();
+    assert!(error.to_string().contains("not_an_option"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn targets_override_markdown_options() {
+    let dir = project_dir("markdown-options");
+    let page = "<b>raw</b> ~~struck~~ $$x$$[^1]\n\n[^1]: Note\n";
+    fs::write(dir.join("docs.md"), page).unwrap();
+    fs::write(dir.join("guestbook.md"), page).unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+
+[markdown]
+math = true
+gfm_footnote_label = "Notes"
+
+[[target]]
+path = "docs.md"
+
+[[target]]
+path = "guestbook.md"
+markdown = { gfm = false, math_text = false, allow_dangerous_html = false }
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+
+    let docs = fs::read_to_string(dir.join("out/docs.html")).unwrap();
+    assert!(docs.starts_with("<p><b>raw</b> <del>struck</del> <math><mi>x</mi></math><sup>"));
+    assert!(docs.contains(">Notes</h2>"));
+    assert_eq!(
+        fs::read_to_string(dir.join("out/guestbook.html")).unwrap(),
+        // Without GFM, the footnote is a plain link reference
+        "<p>&lt;b&gt;raw&lt;/b&gt; ~~struck~~ $$x$$<a href=\"Note\">^1</a></p>\n"
+    );
+
+    let error = Config::parse(
+        "[markdown]\nmdx_jsx_flow = true\n[[target]]\npath = \"docs.md\"",
+        &dir.join("webby.toml"),
+    )
+    .err()
+    .unwrap();
+    assert!(error.to_string().contains("mdx_jsx_flow"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn targets_highlight_code() {
+    let dir = project_dir("highlight");
+    fs::write(dir.join("post.md"), "```toml\nkey = true\n```\n").unwrap();
+    fs::write(dir.join("capsule.gmi"), "```toml config\nkey = true\n```").unwrap();
+    fs::write(
+        dir.join("plain.html"),
+        "<pre><code class=\"language-toml\">key = true</code></pre>",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+
+[highlight]
+theme = "css/highlight.css"
+
+[[target]]
+path = "post.md"
+
+[[target]]
+path = "capsule.gmi"
+
+[[target]]
+path = "plain.html"
+highlight = false
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+    let (written, errors) = write_generated(&config);
+    assert!(errors.is_empty());
+    assert_eq!(written, vec![dir.join("out/css/highlight.css")]);
+    assert_eq!(
+        fs::read_to_string(dir.join("out/css/highlight.css")).unwrap(),
+        highlight::THEME
+    );
+
+    let highlighted =
+        "<span class=\"hl-property\">key</span> = <span class=\"hl-literal\">true</span>";
+    assert_eq!(
+        fs::read_to_string(dir.join("out/post.html")).unwrap(),
+        format!("<pre><code class=\"language-toml\">{highlighted}\n</code></pre>\n")
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/capsule.html")).unwrap(),
+        format!("<pre aria-label=\"toml config\">{highlighted}</pre>\n")
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/plain.html")).unwrap(),
+        "<pre><code class=\"language-toml\">key = true</code></pre>"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn table_of_contents() {
+    let dir = project_dir("toc");
+    fs::write(
+        dir.join("post.md"),
+        "#!TOC()\n\n# Post\n\n## One\n\n### Deep\n\n## Two\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("short.md"),
+        "#!TOC(1)\n\n## One\n\n### Deep\n\n## Two\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("capsule.gmi"),
+        "# Capsule\n#!TOC()\n## Part\n```\n# not a heading\n```",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("page.html"),
+        "<nav>#!TOC()</nav><h2>One</h2><h4>Deep</h4><h3>Less deep</h3>",
+    )
+    .unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+
+[[target]]
+path = "post.md"
+
+[[target]]
+path = "short.md"
+
+[[target]]
+path = "capsule.gmi"
+
+[[target]]
+path = "page.html"
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.join("out/post.html")).unwrap(),
+        "<ul><li><a href=\"#post\">Post</a><ul><li><a href=\"#one\">One</a><ul><li><a href=\"#deep\">Deep</a></li></ul></li><li><a href=\"#two\">Two</a></li></ul></li></ul>\n<h1 id=\"post\">Post</h1>\n<h2 id=\"one\">One</h2>\n<h3 id=\"deep\">Deep</h3>\n<h2 id=\"two\">Two</h2>\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/short.html")).unwrap(),
+        "<ul><li><a href=\"#one\">One</a></li><li><a href=\"#two\">Two</a></li></ul>\n<h2 id=\"one\">One</h2>\n<h3 id=\"deep\">Deep</h3>\n<h2 id=\"two\">Two</h2>\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/capsule.html")).unwrap(),
+        "<h1 id=\"capsule\">Capsule</h1>\n<ul><li><a href=\"#capsule\">Capsule</a><ul><li><a href=\"#part\">Part</a></li></ul></li></ul>\n<h2 id=\"part\">Part</h2>\n<pre># not a heading</pre>\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/page.html")).unwrap(),
+        "<nav><ul><li><a href=\"#one\">One</a><ul><li><a href=\"#deep\">Deep</a></li><li><a href=\"#less-deep\">Less deep</a></li></ul></li></ul></nav><h2 id=\"one\">One</h2><h4 id=\"deep\">Deep</h4><h3 id=\"less-deep\">Less deep</h3>"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn targets_override_heading_options() {
+    let dir = project_dir("heading-options");
+    fs::write(dir.join("post.md"), "# Post\n").unwrap();
+    fs::write(dir.join("capsule.gmi"), "#!TOC()\n# Capsule\n## Part").unwrap();
+
+    let config = Config::parse(
+        r#"
+output = "out"
+
+[outputs]
+capsule = { path = "capsule", format = "gemtext" }
+
+[headings]
+permalink = "¶"
+
+[[target]]
+path = "post.md"
+
+[[target]]
+path = "post.md"
+output = "plain.html"
+headings = { permalink = false }
+
+[[target]]
+path = "post.md"
+output = "bare.html"
+headings = { ids = false, permalink = false }
+
+[[target]]
+path = "capsule.gmi"
+outputs = ["capsule"]
+"#,
+        &dir.join("webby.toml"),
+    )
+    .unwrap();
+    let cache = Cache::new(&dir.join("out"));
+    for target in &config.targets {
+        build_target(&config, target, &cache).result.unwrap();
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.join("out/post.html")).unwrap(),
+        "<h1 id=\"post\">Post <a class=\"permalink\" href=\"#post\" aria-hidden=\"true\">¶</a></h1>\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/plain.html")).unwrap(),
+        "<h1 id=\"post\">Post</h1>\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("out/bare.html")).unwrap(),
+        "<h1>Post</h1>\n"
+    );
+    // Gemtext can't link to headings, so the contents are just listed
+    assert_eq!(
+        fs::read_to_string(dir.join("capsule/capsule.gmi")).unwrap(),
+        "* Capsule\n* Part\n# Capsule\n## Part"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
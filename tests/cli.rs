@@ -0,0 +1,65 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+fn project_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("webby-test-cli-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn init_creates_a_project() {
+    let dir = project_dir("init");
+    let output = Command::new(env!("CARGO_BIN_EXE_webby"))
+        .arg("init")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("Created a new webby project in {}\n", dir.display())
+    );
+    assert!(dir.join("webby.toml").is_file());
+    assert!(dir.join("index.html").is_file());
+
+    // The project can be built straight away
+    let output = Command::new(env!("CARGO_BIN_EXE_webby"))
+        .args(["-q", "build", "-c"])
+        .arg(dir.join("webby.toml"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.join("webby/index.html").is_file());
+
+    // Running it again doesn't overwrite anything
+    fs::write(dir.join("webby.toml"), "output = \"out\"").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_webby"))
+        .arg("init")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.join("webby.toml")).unwrap(),
+        "output = \"out\""
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn init_is_quiet_with_quiet() {
+    let dir = project_dir("init-quiet");
+    let output = Command::new(env!("CARGO_BIN_EXE_webby"))
+        .args(["init", "-q"])
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+    assert!(dir.join("webby.toml").is_file());
+    assert!(dir.join("index.html").is_file());
+
+    let _ = fs::remove_dir_all(&dir);
+}
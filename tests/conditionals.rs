@@ -0,0 +1,84 @@
+use {
+    std::path::Path,
+    webby::{compiler, front_matter::Value, Context, Profile},
+};
+
+fn compile(src: &str, ctx: &mut Context) -> String {
+    compiler::compile_macros(src, Path::new("index.html"), ctx)
+        .unwrap()
+        .into_owned()
+}
+
+fn context(profile: Profile) -> Context<'static> {
+    let mut ctx = Context {
+        profile,
+        ..Default::default()
+    };
+    ctx.vars
+        .insert("analytics".into(), Value::String("plausible".into()));
+    ctx.vars.insert("banner".into(), Value::Boolean(false));
+    ctx
+}
+
+#[test]
+fn profile_conditions() {
+    let src = "<p>#!IF(profile == release, <b>live</b>) #!ELSE(<i>debug, build</i>)</p>";
+    assert_eq!(
+        compile(src, &mut context(Profile::Release)),
+        "<p><b>live</b></p>"
+    );
+    assert_eq!(
+        compile(src, &mut context(Profile::Dev)),
+        "<p><i>debug, build</i></p>"
+    );
+}
+
+#[test]
+fn variable_conditions() {
+    let mut ctx = context(Profile::Dev);
+    assert_eq!(
+        compile("#!IF(analytics == plausible, yes)", &mut ctx),
+        "yes"
+    );
+    assert_eq!(compile("#!IF(analytics != plausible, yes)", &mut ctx), "");
+    assert_eq!(compile("#!IF(banner, yes)#!ELSE(no)", &mut ctx), "no");
+    assert_eq!(compile("#!IF(!banner && analytics, yes)", &mut ctx), "yes");
+    assert_eq!(compile("#!IF(banner || missing, yes)", &mut ctx), "");
+    assert_eq!(compile("#!IFDEF(banner, yes)", &mut ctx), "yes");
+    assert_eq!(compile("#!IFDEF(missing, yes)#!ELSE(no)", &mut ctx), "no");
+}
+
+#[test]
+fn conditions_with_commas() {
+    let mut ctx = context(Profile::Dev);
+    ctx.vars
+        .insert("company".into(), Value::String("Acme, Inc".into()));
+    assert_eq!(
+        compile("#!IF(#!VAR(which, analytics) == plausible, yes)", &mut ctx),
+        "yes"
+    );
+    assert_eq!(
+        compile("#!IF(company == \"Acme, Inc\", yes)", &mut ctx),
+        "yes"
+    );
+    assert_eq!(
+        compile(
+            "#!IF(analytics == 'plausible, inc', yes)#!ELSE(no)",
+            &mut ctx
+        ),
+        "no"
+    );
+}
+
+#[test]
+fn unused_branches_are_not_compiled() {
+    let mut ctx = context(Profile::Dev);
+    assert_eq!(
+        compile(
+            "#!IF(profile == release, #!INCLUDE(missing.html))#!ELSE(#!VAR(analytics))",
+            &mut ctx
+        ),
+        "plausible"
+    );
+    assert!(ctx.dependencies.is_empty());
+}
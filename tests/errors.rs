@@ -0,0 +1,113 @@
+use {
+    std::{error::Error, path::Path},
+    webby::{compiler, Config, Context, ErrorKind, Position},
+};
+
+#[test]
+fn unknown_macro() {
+    let src = "<p>\n  #!NOT_A_MACRO(hi)</p>";
+    let err = compiler::compile_macros(src, Path::new("index.html"), &mut Context::default())
+        .unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::UnknownMacro);
+    assert_eq!(err.path.as_deref(), Some(Path::new("index.html")));
+    assert_eq!(err.position, Some(Position { line: 2, column: 3 }));
+}
+
+#[test]
+fn unclosed_macro() {
+    let src = "#!BASE64(#!BASE64(hi)";
+    let err = compiler::compile_macros(src, Path::new("index.html"), &mut Context::default())
+        .unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::MacroSyntax);
+}
+
+#[test]
+fn nested_macro_position() {
+    let src = "#!BASE64(\n\n #!NOPE())";
+    let err = compiler::compile_macros(src, Path::new("index.html"), &mut Context::default())
+        .unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::UnknownMacro);
+    assert_eq!(err.position, Some(Position { line: 3, column: 2 }));
+}
+
+#[test]
+fn missing_include() {
+    let src = "#!INCLUDE(does/not/exist.html)";
+    let err = compiler::compile_macros(src, Path::new("tests/index.html"), &mut Context::default())
+        .unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::Io);
+    assert_eq!(err.position, Some(Position { line: 1, column: 1 }));
+    assert!(err
+        .source()
+        .is_some_and(|cause| cause.is::<std::io::Error>()));
+}
+
+#[test]
+fn content_outside_layout() {
+    let src = "<main>#!CONTENT()</main>";
+    let err = compiler::compile_macros(src, Path::new("index.html"), &mut Context::default())
+        .unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::MacroSyntax);
+    assert_eq!(err.position, Some(Position { line: 1, column: 7 }));
+}
+
+#[test]
+fn unclosed_front_matter() {
+    let src = "+++\ntitle = \"Oops\"\n\n# Oops\n";
+    let err = webby::front_matter::split(src, Path::new("post.md")).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::FrontMatter);
+    assert_eq!(err.position, Some(Position { line: 1, column: 1 }));
+}
+
+#[test]
+fn unknown_variable() {
+    let src = "<html>\n<p>#!VAR(nope)</p>";
+    let err = compiler::compile_macros(src, Path::new("index.html"), &mut Context::default())
+        .unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::UnknownVariable);
+    assert_eq!(err.path.as_deref(), Some(Path::new("index.html")));
+    assert_eq!(err.position, Some(Position { line: 2, column: 4 }));
+    assert!(err.to_string().contains("`nope`"));
+}
+
+#[test]
+fn else_without_if() {
+    let src = "<p>#!ELSE(hi)</p>";
+    let err = compiler::compile_macros(src, Path::new("index.html"), &mut Context::default())
+        .unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::MacroSyntax);
+}
+
+#[test]
+fn feed_limit_with_wrong_type() {
+    let src = "base_url = \"https://example.com\"\n\n[[target]]\npath = \"posts\"\n\n[[feed]]\npath = \"posts/*.md\"\ntitle = \"Blog\"\natom = \"atom.xml\"\nlimit = \"10\"\n";
+    let err = Config::parse(src, Path::new("webby.toml")).err().unwrap();
+
+    assert_eq!(err.kind, ErrorKind::Config);
+    assert!(err.to_string().contains("`limit`"));
+}
+
+#[test]
+fn unquoted_date_in_config() {
+    let src =
+        "[vars]\nname = \"Site\"\nupdated = 2024-01-01\n\n[[target]]\npath = \"index.html\"\n";
+    let err = Config::parse(src, Path::new("webby.toml")).err().unwrap();
+
+    assert_eq!(err.kind, ErrorKind::Config);
+    assert_eq!(err.path.as_deref(), Some(Path::new("webby.toml")));
+    assert_eq!(
+        err.position,
+        Some(Position {
+            line: 3,
+            column: 11
+        })
+    );
+}
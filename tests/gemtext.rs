@@ -1,11 +1,14 @@
 use {
     std::{fs, path::PathBuf},
-    webby::translator,
+    webby::{
+        translator::{self, GemtextClasses, GemtextOptions},
+        ErrorKind,
+    },
 };
 
 #[test]
 fn test() {
-    let tests = ["link", "header", "text", "list"];
+    let tests = ["link", "header", "text", "list", "quote", "preformatted"];
 
     for test in tests {
         let gmi_path = PathBuf::from(format!("tests/gemtext/{test}.gmi"));
@@ -13,9 +16,178 @@ fn test() {
         let html =
             translator::translate_gemtext(&gmi_path, &fs::read_to_string(&gmi_path).unwrap())
                 .unwrap();
-        assert_eq!(
-            html,
-            format!("<p>{}</p>", fs::read_to_string(&html_path).unwrap())
-        )
+        assert_eq!(html, fs::read_to_string(&html_path).unwrap(), "{test}");
     }
 }
+
+#[test]
+fn link_without_url() {
+    let error =
+        translator::translate_gemtext(&PathBuf::from("test.gmi"), "text\n=>   \n").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::GemtextSyntax);
+}
+
+#[test]
+fn options() {
+    let options = GemtextOptions {
+        classes: GemtextClasses {
+            text: Some("text".into()),
+            quote: Some("quote".into()),
+            preformatted: Some("code".into()),
+            image: Some("image".into()),
+            ..Default::default()
+        },
+        wrapper: Some("section".into()),
+        inline_images: true,
+        rewrite_gmi_links: true,
+        gemini_proxy: Some("https://proxy.example/?url=gemini://".into()),
+        highlight: false,
+    };
+    let gemtext = "hi
+> quoted
+```sh
+ls
+```
+=> /posts/one.gmi#top One
+=> two.gmi?a=b
+=> https://example.com/three.gmi
+=> gemini://example.com/four.gmi
+=> /img/cat.JPG
+";
+    assert_eq!(
+        translator::translate_gemtext_with_options(&PathBuf::from("test.gmi"), gemtext, &options)
+            .unwrap(),
+        r#"<section>
+<p class="text">hi</p>
+<blockquote class="quote">
+<p>quoted</p>
+</blockquote>
+<pre class="code" aria-label="sh">ls</pre>
+<p><a href="/posts/one.html#top">One</a></p>
+<p><a href="two.html?a=b">two.gmi?a=b</a></p>
+<p><a href="https://example.com/three.gmi">https://example.com/three.gmi</a></p>
+<p><a href="https://proxy.example/?url=gemini://example.com/four.gmi">gemini://example.com/four.gmi</a></p>
+<p class="image"><img src="/img/cat.JPG" alt=""></p>
+</section>
+"#
+    );
+}
+
+#[test]
+fn markdown_to_gemtext() {
+    let markdown = r#"# Title
+
+Some *wrapped*
+text with [a link](https://example.com) and `code`.
+
+#### Deep heading
+
+- one
+- [two][ref]
+  - nested
+
+![A cat](cat.png)
+
+> quoted
+> text
+
+```rust
+fn main() {}
+```
+
+[ref]: /two.gmi
+"#;
+    assert_eq!(
+        translator::translate_markdown_to_gemtext(markdown),
+        r#"# Title
+
+Some wrapped text with a link and `code`.
+=> https://example.com a link
+
+### Deep heading
+
+* one
+* two
+* nested
+=> /two.gmi two
+
+=> cat.png A cat
+
+> quoted text
+
+```rust
+fn main() {}
+```
+"#
+    );
+}
+
+#[test]
+fn html_to_gemtext() {
+    let html = r##"<!DOCTYPE html>
+<html>
+<head><title>Ignored</title><style>p { color: red; }</style></head>
+<body>
+<nav><a href="/">Home</a></nav>
+<h1>Title</h1>
+<p>Some <em>wrapped</em>
+text with <a href="https://example.com">a link</a> &amp; <a href="#top">an anchor</a>.</p>
+<h4>Deep heading</h4>
+<ul>
+  <li>one</li>
+  <li><a href="/two.html">two</a></li>
+</ul>
+<img src="cat.png" alt="A cat">
+<blockquote><p>quoted<br>text</p></blockquote>
+<pre><code class="language-rust">fn main() {
+    println!("&lt;hi&gt;");
+}
+</code></pre>
+<script>document.write("<p>no</p>");</script>
+</body>
+</html>"##;
+    assert_eq!(
+        translator::translate_html_to_gemtext(&PathBuf::from("test.html"), html).unwrap(),
+        r##"# Title
+
+Some wrapped text with a link & an anchor.
+=> https://example.com a link
+
+### Deep heading
+
+* one
+* two
+=> /two.html two
+
+=> cat.png A cat
+
+> quoted
+> text
+
+```rust
+fn main() {
+    println!("<hi>");
+}
+```
+"##
+    );
+    // Navigation is dropped until its own closing tag, even when the elements
+    // inside it leave theirs out
+    assert_eq!(
+        translator::translate_html_to_gemtext(
+            &PathBuf::from("test.html"),
+            r#"<nav><ul><li><a href="/">Home</a><li><a href="/about">About</a></ul></nav><h1>Title</h1><p>Body text</p>"#
+        )
+        .unwrap(),
+        "# Title\n\nBody text\n"
+    );
+    // Text can start with characters that are more than one byte long
+    assert_eq!(
+        translator::translate_html_to_gemtext(
+            &PathBuf::from("test.html"),
+            "<h1>Été</h1><p>é</p>\n<p>&nbsp;\u{a0}a&nbsp;b</p><p>日本語 < テキスト</p>é"
+        )
+        .unwrap(),
+        "# Été\n\né\n\na b\n\n日本語 < テキスト\n\né\n"
+    );
+}
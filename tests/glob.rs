@@ -0,0 +1,47 @@
+use {std::path::Path, webby::Pattern};
+
+fn matches(pattern: &str, path: &str) -> bool {
+    Pattern::new(pattern).unwrap().matches(Path::new(path))
+}
+
+#[test]
+fn wildcards() {
+    assert!(matches("*.md", "post.md"));
+    assert!(!matches("*.md", "posts/post.md"));
+    assert!(matches("post?.md", "post1.md"));
+    assert!(!matches("post?.md", "post.md"));
+    assert!(matches("[a-c]*.html", "blog.html"));
+    assert!(!matches("[!a-c]*.html", "blog.html"));
+    assert!(matches("*.{png,jpg}", "cat.jpg"));
+    assert!(!matches("*.{png,jpg}", "cat.gif"));
+}
+
+#[test]
+fn any_folders() {
+    assert!(matches("**/*.md", "post.md"));
+    assert!(matches("**/*.md", "2024/06/post.md"));
+    assert!(matches("posts/**", "posts"));
+    assert!(matches("posts/**", "posts/2024/post.md"));
+    assert!(!matches("posts/**", "drafts/post.md"));
+}
+
+#[test]
+fn excludes_match_names_anywhere() {
+    let psd = Pattern::exclude("*.psd").unwrap();
+    assert!(psd.matches(Path::new("art/cover.psd")));
+    assert!(psd.matches(Path::new("cover.psd")));
+
+    let drafts = Pattern::exclude("drafts/").unwrap();
+    assert!(drafts.matches(Path::new("drafts")));
+    assert!(drafts.matches(Path::new("posts/drafts")));
+
+    let drafts = Pattern::exclude("drafts/**").unwrap();
+    assert!(drafts.matches(Path::new("drafts/post.md")));
+    assert!(!drafts.matches(Path::new("posts/drafts/post.md")));
+}
+
+#[test]
+fn invalid_patterns() {
+    assert!(Pattern::new("[abc").is_err());
+    assert!(Pattern::new("*.{md,gmi").is_err());
+}
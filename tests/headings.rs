@@ -0,0 +1,38 @@
+use webby::headings::{link_headings, slug, HeadingOptions};
+
+#[test]
+fn slugs() {
+    assert_eq!(slug("Getting Started"), "getting-started");
+    assert_eq!(slug("  What&#39;s new in v2.0?  "), "whats-new-in-v20");
+    assert_eq!(slug("snake_case -- and dashes"), "snake-case-and-dashes");
+    assert_eq!(slug("Ünïcode Wörds"), "ünïcode-wörds");
+    assert_eq!(slug("!!!"), "section");
+}
+
+#[test]
+fn ids_and_permalinks() {
+    let html = "<h1>Intro</h1><h2 class=\"x\">Intro</h2><h2 id=\"kept\">Kept</h2><h3>A <em>b</em></h3><p id=\"intro-2\"></p><h3>Intro</h3>";
+
+    let off = HeadingOptions {
+        ids: false,
+        permalink: None,
+    };
+    assert!(link_headings(html, &off).is_none());
+    // Every heading gets an id by default
+    assert_eq!(
+        link_headings(html, &HeadingOptions::default()).unwrap(),
+        "<h1 id=\"intro\">Intro</h1><h2 class=\"x\" id=\"intro-1\">Intro</h2><h2 id=\"kept\">Kept</h2><h3 id=\"a-b\">A <em>b</em></h3><p id=\"intro-2\"></p><h3 id=\"intro-3\">Intro</h3>"
+    );
+
+    let permalinks = HeadingOptions {
+        ids: false,
+        permalink: Some("#".into()),
+    };
+    let linked = link_headings("<h2>One</h2><h2 id=\"two\">Two</h2>", &permalinks).unwrap();
+    assert_eq!(
+        linked,
+        "<h2 id=\"one\">One <a class=\"permalink\" href=\"#one\" aria-hidden=\"true\">#</a></h2><h2 id=\"two\">Two <a class=\"permalink\" href=\"#two\" aria-hidden=\"true\">#</a></h2>"
+    );
+    // Linking twice, like for a page in a layout, doesn't add more permalinks
+    assert_eq!(link_headings(&linked, &permalinks).unwrap(), linked);
+}
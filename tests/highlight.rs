@@ -0,0 +1,60 @@
+use webby::highlight::{self, highlight};
+
+#[test]
+fn languages() {
+    let tests = [
+        (
+            "rust",
+            "fn main() { println!(\"hi\"); } // 'a",
+            "<span class=\"hl-keyword\">fn</span> <span class=\"hl-function\">main</span>() { <span class=\"hl-function\">println</span>!(<span class=\"hl-string\">&quot;hi&quot;</span>); } <span class=\"hl-comment\">// 'a</span>",
+        ),
+        (
+            "toml",
+            "[table]\nkey = 'v' # c",
+            "<span class=\"hl-type\">[table]</span>\n<span class=\"hl-property\">key</span> = <span class=\"hl-string\">'v'</span> <span class=\"hl-comment\"># c</span>",
+        ),
+        (
+            "sh",
+            "echo \"$HOME\" $1#not-a-comment",
+            "echo <span class=\"hl-string\">&quot;$HOME&quot;</span> <span class=\"hl-variable\">$1</span>#not-a-comment",
+        ),
+        (
+            "css",
+            "a:hover { color: #fff; }",
+            "<span class=\"hl-tag\">a</span>:hover { <span class=\"hl-property\">color</span>: <span class=\"hl-number\">#fff</span>; }",
+        ),
+        (
+            "js",
+            "let x = null;",
+            "<span class=\"hl-keyword\">let</span> x = <span class=\"hl-literal\">null</span>;",
+        ),
+        (
+            "json",
+            "{\"a\": 1}",
+            "{<span class=\"hl-property\">&quot;a&quot;</span>: <span class=\"hl-number\">1</span>}",
+        ),
+        (
+            "html",
+            "<p class=x>&amp;</p>",
+            "&lt;<span class=\"hl-tag\">p</span> <span class=\"hl-attribute\">class</span>=<span class=\"hl-string\">x</span>&gt;<span class=\"hl-literal\">&amp;amp;</span>&lt;/<span class=\"hl-tag\">p</span>&gt;",
+        ),
+    ];
+
+    for (language, code, expected) in tests {
+        assert_eq!(highlight(language, code).unwrap(), expected, "{language}");
+    }
+    assert!(highlight("brainfuck", "+").is_none());
+}
+
+#[test]
+fn code_blocks() {
+    let html = "<pre><code class=\"language-rust\">let x = &quot;&lt;&quot;;\n</code></pre><pre><code>plain</code></pre><code class=\"language-rust\">inline</code>";
+    assert_eq!(
+        highlight::highlight_code_blocks(html).unwrap(),
+        "<pre><code class=\"language-rust\"><span class=\"hl-keyword\">let</span> x = <span class=\"hl-string\">&quot;&lt;&quot;</span>;\n</code></pre><pre><code>plain</code></pre><code class=\"language-rust\">inline</code>"
+    );
+    assert!(
+        highlight::highlight_code_blocks("<pre><code class=\"language-nope\">x</code></pre>")
+            .is_none()
+    );
+}
@@ -0,0 +1,66 @@
+use webby::{
+    translator::{translate_latex, translate_markdown, translate_markdown_with_options},
+    MarkdownOptions,
+};
+
+#[test]
+fn latex() {
+    let tests = [
+        ("x", "<math><mi>x</mi></math>"),
+        (
+            "x^2 + 3.5",
+            "<math><mrow><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><mn>3.5</mn></mrow></math>",
+        ),
+        (
+            "a_{ij}^n f'",
+            "<math><mrow><msubsup><mi>a</mi><mrow><mi>i</mi><mi>j</mi></mrow><mi>n</mi></msubsup><msup><mi>f</mi><mo>′</mo></msup></mrow></math>",
+        ),
+        (
+            "\\frac{1}{\\sqrt[3]{x}} \\leq \\alpha\\Gamma",
+            "<math><mrow><mfrac><mn>1</mn><mroot><mi>x</mi><mn>3</mn></mroot></mfrac><mo>≤</mo><mi>α</mi><mi mathvariant=\"normal\">Γ</mi></mrow></math>",
+        ),
+        (
+            "\\sum_{i=0}^n i < \\int_0^1",
+            "<math><mrow><munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>0</mn></mrow><mi>n</mi></munderover><mi>i</mi><mo>&lt;</mo><msubsup><mo>∫</mo><mn>0</mn><mn>1</mn></msubsup></mrow></math>",
+        ),
+        (
+            "\\lim_{x \\to 0} \\sin x",
+            "<math><mrow><munder><mo movablelimits=\"true\" form=\"prefix\">lim</mo><mrow><mi>x</mi><mo>→</mo><mn>0</mn></mrow></munder><mi>sin</mi><mi>x</mi></mrow></math>",
+        ),
+        (
+            "\\mathbb{R} \\mathbf{v1} \\text{if } \\hat{y}",
+            "<math><mrow><mi>ℝ</mi><mrow><mi>𝐯</mi><mn>𝟏</mn></mrow><mtext>if\u{a0}</mtext><mover accent=\"true\"><mi>y</mi><mo>^</mo></mover></mrow></math>",
+        ),
+        (
+            "\\left( x \\right.",
+            "<math><mrow><mo fence=\"true\">(</mo><mi>x</mi></mrow></math>",
+        ),
+        ("\\nope", "<math><merror><mtext>\\nope</mtext></merror></math>"),
+    ];
+    for (latex, expected) in tests {
+        assert_eq!(translate_latex(latex, false), expected, "{latex}");
+    }
+
+    assert_eq!(
+        translate_latex("\\begin{pmatrix} a & b \\\\ c & d \\\\ \\end{pmatrix}", true),
+        "<math display=\"block\"><mrow><mo fence=\"true\">(</mo><mtable><mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr><mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr></mtable><mo fence=\"true\">)</mo></mrow></math>"
+    );
+}
+
+#[test]
+fn markdown_math() {
+    let mut options = MarkdownOptions::default();
+    options.constructs.math_flow = true;
+    options.constructs.math_text = true;
+    options.math_text_single_dollar = true;
+
+    assert_eq!(
+        translate_markdown_with_options(
+            "Where $a < b$:\n\n$$\n\\frac{a}{b}\n$$\n\n```math\nc\n```\n",
+            &options
+        ),
+        "<p>Where <math><mrow><mi>a</mi><mo>&lt;</mo><mi>b</mi></mrow></math>:</p>\n<math display=\"block\"><mfrac><mi>a</mi><mi>b</mi></mfrac></math>\n<math display=\"block\"><mi>c</mi></math>\n"
+    );
+    // Math is off by default, so dollars are left alone
+    assert_eq!(translate_markdown("$a$"), "<p>$a$</p>");
+}